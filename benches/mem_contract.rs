@@ -0,0 +1,117 @@
+// RGB standard library for working with smart contracts on Bitcoin & Lightning
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks of the outpoint-based lookups in the in-memory contract state,
+//! comparing indexed access with the full scan of the contract allocations.
+
+use amplify::confinement::Confined;
+use amplify::Wrapper;
+use bp::dbc::Method;
+use bp::{Outpoint, Txid, Vout};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rgb::vm::{ContractStateAccess, ContractStateEvolve, OrdOpRef};
+use rgb::{
+    Assign, AssignmentType, Assignments, Genesis, GenesisSeal, Schema, TypedAssigns, XChain,
+    XOutpoint,
+};
+use rgbstd::persistence::{ContractStateRead, MemContract, MemContractState};
+use strict_encoding::StrictDumb;
+
+const SIZES: [u32; 3] = [1_000, 10_000, 50_000];
+
+fn txid(no: u32) -> Txid {
+    let mut bytes = [0u8; 32];
+    bytes[..4].copy_from_slice(&no.to_le_bytes());
+    Txid::from(bytes)
+}
+
+fn outpoint(no: u32) -> XOutpoint { XChain::Bitcoin(Outpoint::new(txid(no), Vout::from_u32(0))) }
+
+fn contract(allocations: u32) -> MemContract<MemContractState> {
+    let rights = (0..allocations).map(|no| Assign::Revealed {
+        seal: XChain::Bitcoin(GenesisSeal::with_blinding(
+            Method::OpretFirst,
+            txid(no),
+            Vout::from_u32(0),
+            no as u64,
+        )),
+        state: Default::default(),
+        lock: Default::default(),
+    });
+    let rights = TypedAssigns::Declarative(Confined::from_iter_unsafe(rights));
+
+    let assignments = Confined::from_iter_unsafe([(AssignmentType::strict_dumb(), rights)]);
+
+    let mut genesis = Genesis::strict_dumb();
+    genesis.assignments = Assignments::from_inner(assignments);
+
+    let schema = Schema::strict_dumb();
+    let mut contract = MemContract::init((&schema, genesis.contract_id()));
+    contract
+        .evolve_state(OrdOpRef::Genesis(&genesis))
+        .expect("genesis state fits the contract");
+    contract
+}
+
+fn rights_lookup(c: &mut Criterion) {
+    let ty = AssignmentType::strict_dumb();
+    let mut group = c.benchmark_group("rights_lookup");
+    for allocations in SIZES {
+        let contract = contract(allocations);
+        let outpoint = outpoint(allocations / 2);
+        // The first lookup constructs the index, so we do it before the measurements
+        assert_eq!(contract.rights(outpoint, ty), 1);
+
+        group.bench_with_input(BenchmarkId::new("indexed", allocations), &outpoint, |b, o| {
+            b.iter(|| contract.rights(black_box(*o), ty))
+        });
+        group.bench_with_input(BenchmarkId::new("scan", allocations), &outpoint, |b, o| {
+            b.iter(|| {
+                let outpoint = black_box(*o);
+                contract
+                    .rights_all()
+                    .filter(|a| a.seal.to_outpoint() == outpoint && a.opout.ty == ty)
+                    .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn allocations(c: &mut Criterion) {
+    let ty = AssignmentType::strict_dumb();
+    let mut group = c.benchmark_group("allocations");
+    group.sample_size(10);
+    for allocations in SIZES {
+        let contract = contract(allocations);
+        group.bench_function(BenchmarkId::from_parameter(allocations), |b| {
+            b.iter(|| {
+                (0..allocations)
+                    .map(|no| contract.rights(outpoint(no), ty))
+                    .sum::<u32>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, rights_lookup, allocations);
+criterion_main!(benches);
@@ -263,6 +263,12 @@ impl StrictDumb for AnchorSet {
 
 impl AnchorSet {
     pub fn known_bundle_ids(&self) -> impl Iterator<Item = BundleId> {
+        self.known_bundles().map(|(_, bundle_id)| bundle_id)
+    }
+
+    /// Returns bundles committed by the anchor, together with the contracts
+    /// they belong to.
+    pub fn known_bundles(&self) -> impl Iterator<Item = (ContractId, BundleId)> {
         let map = match self {
             AnchorSet::Tapret(tapret) => tapret.mpc_proof.to_known_message_map().into_inner(),
             AnchorSet::Opret(opret) => opret.mpc_proof.to_known_message_map().into_inner(),
@@ -272,8 +278,12 @@ impl AnchorSet {
                 map
             }
         };
-        map.into_values()
-            .map(|msg| BundleId::from_byte_array(msg.to_byte_array()))
+        map.into_iter().map(|(proto, msg)| {
+            (
+                ContractId::from_byte_array(proto.to_byte_array()),
+                BundleId::from_byte_array(msg.to_byte_array()),
+            )
+        })
     }
 
     pub fn has_tapret(&self) -> bool { matches!(self, Self::Tapret(_) | Self::Double { .. }) }
@@ -446,6 +456,47 @@ impl AnchoredBundles {
             }),
         }
     }
+
+    /// Verifies that the witness transaction commits to each of the anchored
+    /// bundles under the given contract.
+    ///
+    /// # Returns
+    ///
+    /// Id of the first bundle which is not committed by the transaction.
+    pub fn verify(&self, contract_id: ContractId, witness: &Tx) -> Result<(), BundleId> {
+        let proto = mpc::ProtocolId::from_byte_array(contract_id.to_byte_array());
+        let msg = |bundle_id: BundleId| mpc::Message::from_byte_array(bundle_id.to_byte_array());
+        match self {
+            Self::Tapret(anchor, bundle) => {
+                let bundle_id = bundle.bundle_id();
+                anchor
+                    .verify(proto, msg(bundle_id), witness)
+                    .map_err(|_| bundle_id)?;
+            }
+            Self::Opret(anchor, bundle) => {
+                let bundle_id = bundle.bundle_id();
+                anchor
+                    .verify(proto, msg(bundle_id), witness)
+                    .map_err(|_| bundle_id)?;
+            }
+            Self::Double {
+                tapret_anchor,
+                tapret_bundle,
+                opret_anchor,
+                opret_bundle,
+            } => {
+                let bundle_id = tapret_bundle.bundle_id();
+                tapret_anchor
+                    .verify(proto, msg(bundle_id), witness)
+                    .map_err(|_| bundle_id)?;
+                let bundle_id = opret_bundle.bundle_id();
+                opret_anchor
+                    .verify(proto, msg(bundle_id), witness)
+                    .map_err(|_| bundle_id)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl AnchoredBundles {
@@ -0,0 +1,209 @@
+// RGB standard library for working with smart contracts on Bitcoin & Lightning
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use aluvm::library::Lib;
+use amplify::confinement::{
+    LargeOrdMap, MediumBlob, MediumOrdSet, SmallOrdMap, SmallOrdSet, TinyOrdMap, TinyOrdSet,
+    TinyVec,
+};
+use amplify::{ByteArray, Bytes32};
+use armor::{ArmorHeader, AsciiArmor, StrictArmor};
+use baid64::{Baid64ParseError, DisplayBaid64, FromBaid64Str};
+use commit_verify::{CommitId, CommitmentId, DigestExt, Sha256};
+use rgb::{
+    impl_serde_baid64, AttachId, BundleId, ContractId, Extension, Genesis, GraphSeal, Identity,
+    OpId, Schema, TransitionBundle, XChain, XWitnessId,
+};
+use strict_encoding::{StrictDeserialize, StrictSerialize, TypeName};
+use strict_types::TypeSystem;
+
+use super::{
+    ContainerVer, ContentId, ContentSigs, SealWitness, Supplement, TrustLevel,
+    ASCII_ARMOR_CONTRACT, ASCII_ARMOR_VERSION,
+};
+use crate::interface::{Iface, IfaceImpl, ImplId};
+use crate::LIB_NAME_RGB_STD;
+
+/// Backup identifier.
+///
+/// Backup identifier commits to all data provided within the backup.
+#[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
+#[wrapper(Deref, BorrowSlice, Hex, Index, RangeOps)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB_STD)]
+pub struct BackupId(
+    #[from]
+    #[from([u8; 32])]
+    Bytes32,
+);
+
+impl From<Sha256> for BackupId {
+    fn from(hasher: Sha256) -> Self { hasher.finish().into() }
+}
+
+impl CommitmentId for BackupId {
+    const TAG: &'static str = "urn:lnp-bp:rgb:backup#2024-10-18";
+}
+
+impl DisplayBaid64 for BackupId {
+    const HRI: &'static str = "rgb:bkp";
+    const CHUNKING: bool = true;
+    const PREFIX: bool = true;
+    const EMBED_CHECKSUM: bool = false;
+    const MNEMONIC: bool = false;
+    fn to_baid64_payload(&self) -> [u8; 32] { self.to_byte_array() }
+}
+impl FromBaid64Str for BackupId {}
+impl FromStr for BackupId {
+    type Err = Baid64ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_baid64_str(s) }
+}
+impl Display for BackupId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { self.fmt_baid64(f) }
+}
+
+impl_serde_baid64!(BackupId);
+
+impl BackupId {
+    pub const fn from_array(id: [u8; 32]) -> Self { BackupId(Bytes32::from_array(id)) }
+}
+
+/// Stash backup, which doesn't depend on a specific persistence provider.
+///
+/// Backup contains stash data either for all contracts known to the stock, or
+/// for a selected set of contracts. Contract state and index are not a part of
+/// the backup, since they are re-computed from the stash data on import.
+#[derive(Clone, Default, Debug, Display, PartialEq)]
+#[display(AsciiArmor::to_ascii_armored_string)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB_STD)]
+#[derive(CommitEncode)]
+#[commit_encode(strategy = strict, id = BackupId)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct Backup {
+    /// Version.
+    pub version: ContainerVer,
+
+    pub ifaces: TinyOrdSet<Iface>,
+
+    pub schemata: TinyOrdSet<Schema>,
+
+    /// Interface implementations, including the ones superseded by newer
+    /// revisions.
+    pub iimpls: SmallOrdSet<IfaceImpl>,
+
+    pub geneses: SmallOrdMap<ContractId, Genesis>,
+
+    pub extensions: LargeOrdMap<OpId, Extension>,
+
+    pub bundles: LargeOrdMap<BundleId, TransitionBundle>,
+
+    pub witnesses: LargeOrdMap<XWitnessId, SealWitness>,
+
+    /// Data containers. Present only in backups covering all contracts.
+    pub attachments: SmallOrdMap<AttachId, MediumBlob>,
+
+    pub secret_seals: MediumOrdSet<XChain<GraphSeal>>,
+
+    /// Type system covering all types used in schemata and interfaces.
+    pub types: TypeSystem,
+
+    /// Collection of scripts used by the schemata.
+    pub scripts: SmallOrdSet<Lib>,
+
+    /// Trust levels assigned to the identities.
+    pub identities: SmallOrdMap<Identity, TrustLevel>,
+
+    pub supplements: SmallOrdSet<Supplement>,
+
+    /// Signatures on the pieces of content which are the part of the backup.
+    pub signatures: SmallOrdMap<ContentId, ContentSigs>,
+
+    /// Contracts from the backup which were archived.
+    pub archived: SmallOrdSet<ContractId>,
+
+    /// History of the contract bindings to the interface implementations,
+    /// per interface name.
+    pub bindings: SmallOrdMap<ContractId, TinyOrdMap<TypeName, TinyVec<ImplId>>>,
+}
+
+impl StrictSerialize for Backup {}
+impl StrictDeserialize for Backup {}
+
+impl Backup {
+    #[inline]
+    pub fn backup_id(&self) -> BackupId { self.commit_id() }
+
+    #[inline]
+    pub fn contract_ids(&self) -> impl Iterator<Item = ContractId> + '_ {
+        self.geneses.keys().copied()
+    }
+}
+
+impl StrictArmor for Backup {
+    type Id = BackupId;
+    const PLATE_TITLE: &'static str = "RGB BACKUP";
+
+    fn armor_id(&self) -> Self::Id { self.backup_id() }
+    fn armor_headers(&self) -> Vec<ArmorHeader> {
+        let mut headers =
+            vec![ArmorHeader::new(ASCII_ARMOR_VERSION, format!("{:#}", self.version))];
+        if !self.geneses.is_empty() {
+            headers.push(ArmorHeader::with(
+                ASCII_ARMOR_CONTRACT,
+                self.contract_ids().map(|id| id.to_string()),
+            ));
+        }
+        headers
+    }
+}
+
+impl FromStr for Backup {
+    type Err = armor::StrictArmorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_ascii_armored_str(s) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backup_id_display() {
+        let id = Backup::default().backup_id();
+        assert_eq!(BackupId::from_str(&id.to_string()).unwrap(), id);
+    }
+
+    #[test]
+    fn armor_round_trip() {
+        let backup = Backup::default();
+        let armored = backup.to_string();
+        assert!(armored.contains("RGB BACKUP"));
+        assert_eq!(Backup::from_str(&armored).unwrap(), backup);
+    }
+}
@@ -19,7 +19,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::ops::Deref;
@@ -151,6 +151,14 @@ impl<const TRANSFER: bool> ValidConsignment<TRANSFER> {
     pub fn split(self) -> (Consignment<TRANSFER>, validation::Status) {
         (self.consignment, self.validation_status)
     }
+
+    #[cfg(test)]
+    pub(crate) fn assume_valid(consignment: Consignment<TRANSFER>) -> Self {
+        ValidConsignment {
+            validation_status: default!(),
+            consignment,
+        }
+    }
 }
 
 impl<const TRANSFER: bool> Deref for ValidConsignment<TRANSFER> {
@@ -421,19 +429,51 @@ impl<const TRANSFER: bool> StrictArmor for Consignment<TRANSFER> {
         }
         headers
     }
-    fn parse_armor_headers(&mut self, headers: Vec<ArmorHeader>) -> Result<(), StrictArmorError> {
-        // TODO: Check remaining headers - terminals, version, iface, contract, schema
-        if let Some(header) = headers
-            .iter()
-            .find(|header| header.title == ASCII_ARMOR_CONSIGNMENT_TYPE)
-        {
-            if self.transfer && header.values.len() != 1 && header.values[0] != "transfer" {
-                // TODO: Add header-specific errors to StrictArmorError
-                // return Err(Strict)
+}
+
+/// Titles of the ASCII armor headers, including the ones produced by the armor
+/// itself.
+const ASCII_ARMOR_TITLES: [&str; 8] = [
+    "Id",
+    ASCII_ARMOR_VERSION,
+    ASCII_ARMOR_CONSIGNMENT_TYPE,
+    ASCII_ARMOR_CONTRACT,
+    ASCII_ARMOR_SCHEMA,
+    ASCII_ARMOR_IFACE,
+    ASCII_ARMOR_TERMINAL,
+    "Check-SHA256",
+];
+
+/// Extracts the known ASCII armor headers from the armored string, joining
+/// multi-line header values.
+fn armor_headers(armored: &str) -> BTreeMap<&str, String> {
+    let mut headers = BTreeMap::<&str, String>::new();
+    let mut current = None;
+    let lines = armored
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| !line.starts_with("-----BEGIN"))
+        .skip(1)
+        .take_while(|line| !line.is_empty());
+    for line in lines {
+        match line.split_once(':') {
+            Some((title, value)) if ASCII_ARMOR_TITLES.contains(&title) => {
+                let header = headers.entry(title).or_default();
+                if !header.is_empty() {
+                    header.push('\n');
+                }
+                header.push_str(value.trim());
+                current = Some(title);
+            }
+            _ => {
+                if let Some(header) = current.and_then(|title| headers.get_mut(title)) {
+                    header.push('\n');
+                    header.push_str(line);
+                }
             }
         }
-        Ok(())
     }
+    headers
 }
 
 // TODO: Remove after header-specific variants are added to StrictArmorError
@@ -441,10 +481,48 @@ impl<const TRANSFER: bool> StrictArmor for Consignment<TRANSFER> {
 pub enum ConsignmentParseError {
     #[display(inner)]
     #[from]
-    Armor(armor::StrictArmorError),
+    Armor(StrictArmorError),
 
     #[display("required consignment type doesn't match the actual type")]
     Type,
+
+    #[display("consignment version doesn't match the one specified in the armor header")]
+    Version,
+
+    #[display("contract id doesn't match the one specified in the armor header")]
+    Contract,
+
+    #[display("schema id doesn't match the one specified in the armor header")]
+    Schema,
+
+    #[display("consignment interfaces don't match the ones specified in the armor header")]
+    Iface,
+
+    #[display("consignment terminals don't match the ones specified in the armor header")]
+    Terminal,
+}
+
+impl<const TRANSFER: bool> Consignment<TRANSFER> {
+    /// Checks that the headers of the ASCII-armored consignment match the
+    /// consignment data.
+    fn check_armor_headers(&self, armored: &str) -> Result<(), ConsignmentParseError> {
+        let expected = self.to_ascii_armored_string();
+        let expected = armor_headers(&expected);
+        let actual = armor_headers(armored);
+        for (title, err) in [
+            (ASCII_ARMOR_VERSION, ConsignmentParseError::Version),
+            (ASCII_ARMOR_CONSIGNMENT_TYPE, ConsignmentParseError::Type),
+            (ASCII_ARMOR_CONTRACT, ConsignmentParseError::Contract),
+            (ASCII_ARMOR_SCHEMA, ConsignmentParseError::Schema),
+            (ASCII_ARMOR_IFACE, ConsignmentParseError::Iface),
+            (ASCII_ARMOR_TERMINAL, ConsignmentParseError::Terminal),
+        ] {
+            if expected.get(title) != actual.get(title) {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<const TRANSFER: bool> FromStr for Consignment<TRANSFER> {
@@ -455,6 +533,7 @@ impl<const TRANSFER: bool> FromStr for Consignment<TRANSFER> {
         if consignment.transfer != TRANSFER {
             return Err(ConsignmentParseError::Type);
         }
+        consignment.check_armor_headers(s)?;
 
         Ok(consignment)
     }
@@ -579,8 +658,7 @@ Check-SHA256: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa
         );
 
         // Wrong type
-        // TODO: Uncomment once ASCII headers get checked
-        /*assert!(matches!(
+        assert!(matches!(
             Transfer::from_str(
                 r#"-----BEGIN RGB CONSIGNMENT-----
 Id: rgb:csg:9jMKgkmP-alPghZC-bu65ctP-GT5tKgM-cAbaTLT-rhu8xQo#urban-athena-adam
@@ -588,7 +666,7 @@ Version: 2
 Type: contract
 Contract: rgb:T24t0N1D-eiInTgb-BXlrrXz-$7OgV6n-WJWHPUD-BWNuqZw
 Schema: rgb:sch:CyqM42yAdM1moWyNZPQedAYt73BM$k9z$dKLUXY1voA#cello-global-deluxe
-Check-SHA256: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa
+Check-SHA256: 562a944631243e23a8de1d2aa2a5621be13351fc6f4d9aa8127c12ac4fb54d97
 
 0s#O3000000000000000000000000000000000000000000000000000000D0CRI`I$>^aZh38Qb#nj!
 0000000000000000000000d59ZDjxe00000000dDb8~4rVQz13d2MfXa{vGU00000000000000000000
@@ -597,10 +675,32 @@ Check-SHA256: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa
 -----END RGB CONSIGNMENT-----"#
             ),
             Err(ConsignmentParseError::Type)
-        ));*/
+        ));
         assert!(matches!(
             Transfer::from_str(include_str!("../../asset/armored_contract.default")),
             Err(ConsignmentParseError::Type)
         ));
     }
+
+    #[test]
+    fn tampered_headers() {
+        let s = include_str!("../../asset/armored_transfer.default");
+        let tampered = s.replace("Version: 2", "Version: 3");
+        assert!(matches!(Transfer::from_str(&tampered), Err(ConsignmentParseError::Version)));
+
+        let tampered = s.replace(
+            "Contract: rgb:T24t0N1D-eiInTgb-BXlrrXz-$7OgV6n-WJWHPUD-BWNuqZw",
+            "Contract: rgb:qm7P!06T-uuBQT56-ovwOLzx-9Gka7Nb-84Nwo8g-blLb8kw",
+        );
+        assert!(matches!(Transfer::from_str(&tampered), Err(ConsignmentParseError::Contract)));
+
+        let tampered = s.replace("#cello-global-deluxe", "#cello-global-deluxe\nSchema: other");
+        assert!(matches!(Transfer::from_str(&tampered), Err(ConsignmentParseError::Schema)));
+
+        let tampered = s.replace("Check-SHA256", "Interface: RGB20\nCheck-SHA256");
+        assert!(matches!(Transfer::from_str(&tampered), Err(ConsignmentParseError::Iface)));
+
+        let tampered = s.replace("Check-SHA256", "Terminal: bundle\nCheck-SHA256");
+        assert!(matches!(Transfer::from_str(&tampered), Err(ConsignmentParseError::Terminal)));
+    }
 }
@@ -19,5 +19,148 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-// TODO: Implement disclosures
-pub struct Disclosure;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use amplify::confinement::{LargeOrdSet, SmallOrdMap};
+use amplify::{ByteArray, Bytes32};
+use armor::{ArmorHeader, AsciiArmor, StrictArmor};
+use baid64::{Baid64ParseError, DisplayBaid64, FromBaid64Str};
+use commit_verify::{CommitId, CommitmentId, DigestExt, Sha256};
+use rgb::{impl_serde_baid64, ContractId, XWitnessId};
+use strict_encoding::{StrictDeserialize, StrictSerialize};
+
+use super::{BundledWitness, ContainerVer, ASCII_ARMOR_CONTRACT, ASCII_ARMOR_VERSION};
+use crate::LIB_NAME_RGB_STD;
+
+/// Disclosure identifier.
+///
+/// Disclosure identifier commits to all data provided within the disclosure.
+#[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
+#[wrapper(Deref, BorrowSlice, Hex, Index, RangeOps)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB_STD)]
+pub struct DisclosureId(
+    #[from]
+    #[from([u8; 32])]
+    Bytes32,
+);
+
+impl From<Sha256> for DisclosureId {
+    fn from(hasher: Sha256) -> Self { hasher.finish().into() }
+}
+
+impl CommitmentId for DisclosureId {
+    const TAG: &'static str = "urn:lnp-bp:rgb:disclosure#2024-10-18";
+}
+
+impl DisplayBaid64 for DisclosureId {
+    const HRI: &'static str = "rgb:dsc";
+    const CHUNKING: bool = true;
+    const PREFIX: bool = true;
+    const EMBED_CHECKSUM: bool = false;
+    const MNEMONIC: bool = false;
+    fn to_baid64_payload(&self) -> [u8; 32] { self.to_byte_array() }
+}
+impl FromBaid64Str for DisclosureId {}
+impl FromStr for DisclosureId {
+    type Err = Baid64ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_baid64_str(s) }
+}
+impl Display for DisclosureId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { self.fmt_baid64(f) }
+}
+
+impl_serde_baid64!(DisclosureId);
+
+impl DisclosureId {
+    pub const fn from_array(id: [u8; 32]) -> Self { DisclosureId(Bytes32::from_array(id)) }
+}
+
+/// Disclosure of the state transitions under (possibly) multiple contracts.
+///
+/// Unlike consignments, disclosures do not contain contract genesis and the
+/// history of the state; they reveal the state assigned by some transitions
+/// to the parties already knowing the contracts.
+#[derive(Clone, Default, Debug, Display, PartialEq)]
+#[display(AsciiArmor::to_ascii_armored_string)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB_STD)]
+#[derive(CommitEncode)]
+#[commit_encode(strategy = strict, id = DisclosureId)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct Disclosure {
+    /// Version.
+    pub version: ContainerVer,
+
+    /// Bundles containing the disclosed state transitions together with the
+    /// witnesses and anchors, per contract.
+    pub bundles: SmallOrdMap<ContractId, LargeOrdSet<BundledWitness>>,
+}
+
+impl StrictSerialize for Disclosure {}
+impl StrictDeserialize for Disclosure {}
+
+impl Disclosure {
+    #[inline]
+    pub fn disclosure_id(&self) -> DisclosureId { self.commit_id() }
+
+    #[inline]
+    pub fn contract_ids(&self) -> impl Iterator<Item = ContractId> + '_ {
+        self.bundles.keys().copied()
+    }
+
+    pub fn witness_ids(&self) -> impl Iterator<Item = XWitnessId> + '_ {
+        self.bundles
+            .values()
+            .flatten()
+            .map(BundledWitness::witness_id)
+    }
+}
+
+impl StrictArmor for Disclosure {
+    type Id = DisclosureId;
+    const PLATE_TITLE: &'static str = "RGB DISCLOSURE";
+
+    fn armor_id(&self) -> Self::Id { self.disclosure_id() }
+    fn armor_headers(&self) -> Vec<ArmorHeader> {
+        let mut headers =
+            vec![ArmorHeader::new(ASCII_ARMOR_VERSION, format!("{:#}", self.version))];
+        if !self.bundles.is_empty() {
+            headers.push(ArmorHeader::with(
+                ASCII_ARMOR_CONTRACT,
+                self.contract_ids().map(|id| id.to_string()),
+            ));
+        }
+        headers
+    }
+}
+
+impl FromStr for Disclosure {
+    type Err = armor::StrictArmorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_ascii_armored_str(s) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn disclosure_id_display() {
+        let id = Disclosure::default().disclosure_id();
+        assert_eq!(DisclosureId::from_str(&id.to_string()).unwrap(), id);
+    }
+
+    #[test]
+    fn armor_round_trip() {
+        let disclosure = Disclosure::default();
+        let armored = disclosure.to_string();
+        assert!(armored.contains("RGB DISCLOSURE"));
+        assert_eq!(Disclosure::from_str(&armored).unwrap(), disclosure);
+    }
+}
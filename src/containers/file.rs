@@ -26,10 +26,12 @@ use amplify::confinement::U32 as FILE_MAX_LEN;
 use armor::{AsciiArmor, StrictArmor};
 use strict_encoding::{StreamReader, StreamWriter, StrictDecode, StrictEncode};
 
-use crate::containers::{Contract, Kit, Transfer};
+use crate::containers::{
+    Backup, Batch, ConsignmentParseError, Contract, Disclosure, Fascia, Kit, Transfer,
+};
 
 const RGB_PREFIX: [u8; 4] = *b"RGB\x00";
-const MAGIC_LEN: usize = 3;
+pub(super) const MAGIC_LEN: usize = 3;
 
 #[derive(Debug, Display, Error, From)]
 #[display(doc_comments)]
@@ -45,6 +47,20 @@ pub enum LoadError {
     #[display(inner)]
     #[from]
     Armor(armor::StrictArmorError),
+
+    #[display(inner)]
+    #[from]
+    Consignment(ConsignmentParseError),
+}
+
+/// Reads RGB file prefix and magic bytes from the data, returning whether
+/// they match the provided `magic`.
+pub(super) fn check_magic(data: &mut impl Read, magic: [u8; MAGIC_LEN]) -> io::Result<bool> {
+    let mut rgb = [0u8; 4];
+    let mut read = [0u8; MAGIC_LEN];
+    data.read_exact(&mut rgb)?;
+    data.read_exact(&mut read)?;
+    Ok(rgb == RGB_PREFIX && read == magic)
 }
 
 pub trait FileContent: StrictArmor {
@@ -52,11 +68,7 @@ pub trait FileContent: StrictArmor {
     const MAGIC: [u8; MAGIC_LEN];
 
     fn load(mut data: impl Read) -> Result<Self, LoadError> {
-        let mut rgb = [0u8; 4];
-        let mut magic = [0u8; MAGIC_LEN];
-        data.read_exact(&mut rgb)?;
-        data.read_exact(&mut magic)?;
-        if rgb != RGB_PREFIX || magic != Self::MAGIC {
+        if !check_magic(&mut data, Self::MAGIC)? {
             return Err(LoadError::InvalidMagic);
         }
 
@@ -89,9 +101,13 @@ pub trait FileContent: StrictArmor {
     }
 
     #[cfg(feature = "fs")]
-    fn load_armored(path: impl AsRef<std::path::Path>) -> Result<Self, LoadError> {
+    fn load_armored(path: impl AsRef<std::path::Path>) -> Result<Self, LoadError>
+    where
+        Self: std::str::FromStr,
+        LoadError: From<<Self as std::str::FromStr>::Err>,
+    {
         let armor = std::fs::read_to_string(path)?;
-        let content = Self::from_ascii_armored_str(&armor)?;
+        let content = Self::from_str(&armor)?;
         Ok(content)
     }
 
@@ -113,8 +129,21 @@ impl FileContent for Transfer {
     const MAGIC: [u8; MAGIC_LEN] = *b"TFR";
 }
 
-// TODO: Add disclosure
-// TODO: Add batch and fascia
+impl FileContent for Backup {
+    const MAGIC: [u8; MAGIC_LEN] = *b"BKP";
+}
+
+impl FileContent for Disclosure {
+    const MAGIC: [u8; MAGIC_LEN] = *b"DSC";
+}
+
+impl FileContent for Batch {
+    const MAGIC: [u8; MAGIC_LEN] = *b"BAT";
+}
+
+impl FileContent for Fascia {
+    const MAGIC: [u8; MAGIC_LEN] = *b"FSC";
+}
 
 #[derive(Clone, Debug, From)]
 #[cfg_attr(
@@ -131,8 +160,18 @@ pub enum UniversalFile {
 
     #[from]
     Transfer(Transfer),
-    // TODO: Add disclosure
-    // TODO: Add batch and fascia
+
+    #[from]
+    Backup(Backup),
+
+    #[from]
+    Disclosure(Disclosure),
+
+    #[from]
+    Batch(Batch),
+
+    #[from]
+    Fascia(Fascia),
 }
 
 impl UniversalFile {
@@ -149,6 +188,10 @@ impl UniversalFile {
             x if x == Kit::MAGIC => Kit::strict_read(&mut reader)?.into(),
             x if x == Contract::MAGIC => Contract::strict_read(&mut reader)?.into(),
             x if x == Transfer::MAGIC => Transfer::strict_read(&mut reader)?.into(),
+            x if x == Backup::MAGIC => Backup::strict_read(&mut reader)?.into(),
+            x if x == Disclosure::MAGIC => Disclosure::strict_read(&mut reader)?.into(),
+            x if x == Batch::MAGIC => Batch::strict_read(&mut reader)?.into(),
+            x if x == Fascia::MAGIC => Fascia::strict_read(&mut reader)?.into(),
             _ => return Err(LoadError::InvalidMagic),
         })
     }
@@ -159,6 +202,10 @@ impl UniversalFile {
             UniversalFile::Kit(_) => Kit::MAGIC,
             UniversalFile::Contract(_) => Contract::MAGIC,
             UniversalFile::Transfer(_) => Transfer::MAGIC,
+            UniversalFile::Backup(_) => Backup::MAGIC,
+            UniversalFile::Disclosure(_) => Disclosure::MAGIC,
+            UniversalFile::Batch(_) => Batch::MAGIC,
+            UniversalFile::Fascia(_) => Fascia::MAGIC,
         };
         writer.write_all(&magic)?;
 
@@ -168,6 +215,10 @@ impl UniversalFile {
             UniversalFile::Kit(content) => content.strict_write(writer),
             UniversalFile::Contract(content) => content.strict_write(writer),
             UniversalFile::Transfer(content) => content.strict_write(writer),
+            UniversalFile::Backup(content) => content.strict_write(writer),
+            UniversalFile::Disclosure(content) => content.strict_write(writer),
+            UniversalFile::Batch(content) => content.strict_write(writer),
+            UniversalFile::Fascia(content) => content.strict_write(writer),
         }
     }
 
@@ -190,6 +241,10 @@ impl Display for UniversalFile {
             UniversalFile::Kit(content) => Display::fmt(&content.display_ascii_armored(), f),
             UniversalFile::Contract(content) => Display::fmt(&content.display_ascii_armored(), f),
             UniversalFile::Transfer(content) => Display::fmt(&content.display_ascii_armored(), f),
+            UniversalFile::Backup(content) => Display::fmt(&content.display_ascii_armored(), f),
+            UniversalFile::Disclosure(content) => Display::fmt(&content.display_ascii_armored(), f),
+            UniversalFile::Batch(content) => Display::fmt(&content.display_ascii_armored(), f),
+            UniversalFile::Fascia(content) => Display::fmt(&content.display_ascii_armored(), f),
         }
     }
 }
@@ -199,6 +254,8 @@ mod test {
     use std::fs::OpenOptions;
     use std::str::FromStr;
 
+    use strict_encoding::StrictDumb;
+
     use super::*;
     static DEFAULT_KIT_PATH: &str = "asset/kit.default";
     #[cfg(feature = "fs")]
@@ -438,4 +495,49 @@ mod test {
             Transfer::load_armored(ARMORED_TRANSFER_PATH).expect("fail to export armored transfer");
         assert_eq!(transfer, default_transfer, "armored transfer roudtrip does not work");
     }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn armored_transfer_tampered_headers() {
+        let armored = include_str!("../../asset/armored_transfer.default");
+        let path = std::env::temp_dir()
+            .join(format!("rgb-std-tampered-transfer-{}", std::process::id()));
+        std::fs::write(&path, armored.replace("Version: 2", "Version: 3")).unwrap();
+        let res = Transfer::load_armored(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(res, Err(LoadError::Consignment(ConsignmentParseError::Version))));
+    }
+
+    fn check_round_trip<T: FileContent + PartialEq + Debug>(content: &T) -> UniversalFile {
+        let mut data = vec![];
+        content.save(&mut data).expect("fail to save");
+        assert_eq!(&T::load(data.as_slice()).expect("fail to load"), content);
+
+        let armored = content.to_ascii_armored_string();
+        assert!(armored.contains(T::PLATE_TITLE));
+        assert_eq!(&T::from_ascii_armored_str(&armored).expect("fail to unarmor"), content);
+
+        UniversalFile::load(data.as_slice()).expect("fail to load universal file")
+    }
+
+    #[test]
+    fn batch_round_trip() {
+        let batch = Batch::strict_dumb();
+        let file = check_round_trip(&batch);
+        assert!(matches!(file, UniversalFile::Batch(content) if content == batch));
+    }
+
+    #[test]
+    fn fascia_round_trip() {
+        let fascia = Fascia::strict_dumb();
+        let file = check_round_trip(&fascia);
+        assert!(matches!(file, UniversalFile::Fascia(content) if content == fascia));
+    }
+
+    #[test]
+    fn disclosure_round_trip() {
+        let disclosure = Disclosure::default();
+        let file = check_round_trip(&disclosure);
+        assert!(matches!(file, UniversalFile::Disclosure(content) if content == disclosure));
+    }
 }
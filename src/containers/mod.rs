@@ -24,7 +24,7 @@
 //! 1. [`Consignment`]s, containing information about partial state of a *single
 //!    contract*, extending from its genesis up to certain contract endpoints.
 //! 2. [`Disclosure`]s, containing extracts from (possibly) independent state
-//!    transitions and extensions under multiple contracts. Useful fro
+//!    transitions and extensions under multiple contracts. Useful for
 //!    disclosing the concealed state for some other parties, and also for
 //!    performing "change" operations on inventory during state transfers.
 
@@ -38,23 +38,30 @@ mod indexed;
 mod file;
 mod kit;
 mod suppl;
+mod backup;
+mod stream;
 
 pub use anchors::{
     AnchorSet, AnchoredBundles, BundledWitness, PubWitness, SealWitness, ToWitnessId, XPubWitness,
 };
+pub use backup::{Backup, BackupId};
 pub use consignment::{
     Consignment, ConsignmentExt, ConsignmentId, ConsignmentParseError, Contract, Transfer,
     ValidConsignment, ValidContract, ValidTransfer,
 };
-pub use disclosure::Disclosure;
+pub use disclosure::{Disclosure, DisclosureId};
 pub use file::{FileContent, LoadError, UniversalFile};
 pub use indexed::IndexedConsignment;
 pub use kit::{Kit, KitId, ValidKit};
 pub use partials::{
-    Batch, BundleDichotomy, CloseMethodSet, Dichotomy, Fascia, TransitionDichotomy, TransitionInfo,
-    TransitionInfoError,
+    Batch, BatchId, BundleDichotomy, CloseMethodSet, Dichotomy, Fascia, FasciaId,
+    TransitionDichotomy, TransitionInfo, TransitionInfoError,
 };
 pub use seal::{BuilderSeal, VoutSeal};
+pub use stream::{
+    ConsignmentHeader, ConsignmentReader, ConsignmentTrailer, ContractReader, StreamError,
+    TransferReader,
+};
 pub use suppl::{
     AnnotationName, Annotations, ContentRef, SupplId, SupplItem, SupplMap, SupplSub, Supplement,
     TickerSuppl, VelocityHint, SUPPL_ANNOT_IFACE_CLASS, SUPPL_ANNOT_IFACE_FEATURES,
@@ -71,6 +78,7 @@ pub const ASCII_ARMOR_SCHEMA: &str = "Schema";
 pub const ASCII_ARMOR_CONTRACT: &str = "Contract";
 pub const ASCII_ARMOR_VERSION: &str = "Version";
 pub const ASCII_ARMOR_TERMINAL: &str = "Terminal";
+pub const ASCII_ARMOR_WITNESS: &str = "Witness";
 pub const ASCII_ARMOR_SUPPL: &str = "Supplement";
 pub const ASCII_ARMOR_SCRIPT: &str = "Alu-Lib";
 pub const ASCII_ARMOR_TYPE_SYSTEM: &str = "Type-System";
@@ -21,19 +21,25 @@
 
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::ops::{BitOr, BitOrAssign};
+use std::str::FromStr;
 use std::{iter, vec};
 
 use amplify::confinement::{Confined, U24};
+use amplify::{ByteArray, Bytes32};
+use armor::{ArmorHeader, AsciiArmor, StrictArmor};
+use baid64::{Baid64ParseError, DisplayBaid64, FromBaid64Str};
 use bp::seals::txout::CloseMethod;
+use commit_verify::{CommitId, CommitmentId, DigestExt, Sha256};
 use rgb::{
-    ContractId, OpId, Operation, Transition, TransitionBundle, TxoSeal, XOutpoint, XOutputSeal,
-    XWitnessId,
+    impl_serde_baid64, ContractId, OpId, Operation, Transition, TransitionBundle, TxoSeal,
+    XOutpoint, XOutputSeal, XWitnessId,
 };
 use strict_encoding::{StrictDecode, StrictDeserialize, StrictDumb, StrictEncode, StrictSerialize};
 
-use crate::containers::{AnchorSet, XPubWitness};
+use crate::containers::{AnchorSet, XPubWitness, ASCII_ARMOR_CONTRACT, ASCII_ARMOR_WITNESS};
 use crate::LIB_NAME_RGB_STD;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -182,12 +188,59 @@ pub enum TransitionInfoError {
     CloseMethodDivergence(OpId),
 }
 
+/// Batch identifier.
+///
+/// Batch identifier commits to all state transitions provided within the
+/// batch.
+#[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
+#[wrapper(Deref, BorrowSlice, Hex, Index, RangeOps)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB_STD)]
+pub struct BatchId(
+    #[from]
+    #[from([u8; 32])]
+    Bytes32,
+);
+
+impl From<Sha256> for BatchId {
+    fn from(hasher: Sha256) -> Self { hasher.finish().into() }
+}
+
+impl CommitmentId for BatchId {
+    const TAG: &'static str = "urn:lnp-bp:rgb:batch#2024-10-18";
+}
+
+impl DisplayBaid64 for BatchId {
+    const HRI: &'static str = "rgb:bat";
+    const CHUNKING: bool = true;
+    const PREFIX: bool = true;
+    const EMBED_CHECKSUM: bool = false;
+    const MNEMONIC: bool = false;
+    fn to_baid64_payload(&self) -> [u8; 32] { self.to_byte_array() }
+}
+impl FromBaid64Str for BatchId {}
+impl FromStr for BatchId {
+    type Err = Baid64ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_baid64_str(s) }
+}
+impl Display for BatchId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { self.fmt_baid64(f) }
+}
+
+impl_serde_baid64!(BatchId);
+
+impl BatchId {
+    pub const fn from_array(id: [u8; 32]) -> Self { BatchId(Bytes32::from_array(id)) }
+}
+
 /// A batch of state transitions under different contracts which are associated
 /// with some specific transfer and will be anchored within a single layer 1
 /// transaction.
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB_STD)]
+#[derive(CommitEncode)]
+#[commit_encode(strategy = strict, id = BatchId)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -217,6 +270,19 @@ impl IntoIterator for Batch {
 }
 
 impl Batch {
+    #[inline]
+    pub fn batch_id(&self) -> BatchId { self.commit_id() }
+
+    /// Returns identifiers of all contracts having state transitions in the
+    /// batch.
+    pub fn contract_ids(&self) -> BTreeSet<ContractId> {
+        iter::once(&self.main)
+            .chain(&self.blanks)
+            .flat_map(TransitionDichotomy::iter)
+            .map(|info| info.transition.contract_id)
+            .collect()
+    }
+
     pub fn close_method_set(&self) -> CloseMethodSet {
         let mut methods = CloseMethodSet::from(self.main.first.method);
         if let Some(info) = &self.main.second {
@@ -297,12 +363,59 @@ impl<T: StrictDumb + StrictEncode + StrictDecode> Dichotomy<T> {
     }
 }
 
+/// Fascia identifier.
+///
+/// Fascia identifier commits to the witness, anchors and all bundles provided
+/// within the fascia.
+#[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
+#[wrapper(Deref, BorrowSlice, Hex, Index, RangeOps)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB_STD)]
+pub struct FasciaId(
+    #[from]
+    #[from([u8; 32])]
+    Bytes32,
+);
+
+impl From<Sha256> for FasciaId {
+    fn from(hasher: Sha256) -> Self { hasher.finish().into() }
+}
+
+impl CommitmentId for FasciaId {
+    const TAG: &'static str = "urn:lnp-bp:rgb:fascia#2024-10-18";
+}
+
+impl DisplayBaid64 for FasciaId {
+    const HRI: &'static str = "rgb:fsc";
+    const CHUNKING: bool = true;
+    const PREFIX: bool = true;
+    const EMBED_CHECKSUM: bool = false;
+    const MNEMONIC: bool = false;
+    fn to_baid64_payload(&self) -> [u8; 32] { self.to_byte_array() }
+}
+impl FromBaid64Str for FasciaId {}
+impl FromStr for FasciaId {
+    type Err = Baid64ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_baid64_str(s) }
+}
+impl Display for FasciaId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { self.fmt_baid64(f) }
+}
+
+impl_serde_baid64!(FasciaId);
+
+impl FasciaId {
+    pub const fn from_array(id: [u8; 32]) -> Self { FasciaId(Bytes32::from_array(id)) }
+}
+
 /// Structure exported from a PSBT for merging into the stash. It contains a set
 /// of finalized state transitions (under multiple contracts), packed into
 /// bundles, and anchored to a single layer 1 transaction.
 #[derive(Clone, PartialEq, Eq, Debug)]
 #[derive(StrictType, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB_STD)]
+#[derive(CommitEncode)]
+#[commit_encode(strategy = strict, id = FasciaId)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -327,6 +440,9 @@ impl StrictSerialize for Fascia {}
 impl StrictDeserialize for Fascia {}
 
 impl Fascia {
+    #[inline]
+    pub fn fascia_id(&self) -> FasciaId { self.commit_id() }
+
     pub fn witness_id(&self) -> XWitnessId { self.witness.map_ref(|w| w.txid()) }
 
     pub fn into_bundles(self) -> impl IntoIterator<Item = (ContractId, TransitionBundle)> {
@@ -335,3 +451,39 @@ impl Fascia {
             .flat_map(|(id, d)| d.into_iter().map(move |b| (id, b)))
     }
 }
+
+impl StrictArmor for Batch {
+    type Id = BatchId;
+    const PLATE_TITLE: &'static str = "RGB BATCH";
+
+    fn armor_id(&self) -> Self::Id { self.batch_id() }
+    fn armor_headers(&self) -> Vec<ArmorHeader> {
+        vec![ArmorHeader::with(
+            ASCII_ARMOR_CONTRACT,
+            self.contract_ids().into_iter().map(|id| id.to_string()),
+        )]
+    }
+}
+
+impl FromStr for Batch {
+    type Err = armor::StrictArmorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_ascii_armored_str(s) }
+}
+
+impl StrictArmor for Fascia {
+    type Id = FasciaId;
+    const PLATE_TITLE: &'static str = "RGB FASCIA";
+
+    fn armor_id(&self) -> Self::Id { self.fascia_id() }
+    fn armor_headers(&self) -> Vec<ArmorHeader> {
+        vec![
+            ArmorHeader::new(ASCII_ARMOR_WITNESS, self.witness_id().to_string()),
+            ArmorHeader::with(ASCII_ARMOR_CONTRACT, self.bundles.keys().map(|id| id.to_string())),
+        ]
+    }
+}
+
+impl FromStr for Fascia {
+    type Err = armor::StrictArmorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_ascii_armored_str(s) }
+}
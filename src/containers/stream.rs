@@ -0,0 +1,487 @@
+// RGB standard library for working with smart contracts on Bitcoin & Lightning
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Streaming reader for consignment files.
+//!
+//! Unlike [`FileContent::load`], which decodes the whole consignment at once,
+//! [`ConsignmentReader`] keeps in memory only the data preceding the bundles
+//! (genesis, extensions and terminals) and the bundle which is currently being
+//! read. Each bundle is checked for internal consistency and for being
+//! committed under the consignment contract as soon as it is decoded. If the
+//! witness transaction is included into the consignment, it is also checked
+//! to commit to the bundles; otherwise this check is left to the consumer
+//! resolving the witness, like [`Stock::consume_stream`].
+//!
+//! Since schema, type system and scripts are serialized after the bundles,
+//! script-level validation of the state transitions can't be performed in a
+//! single pass and still requires [`Consignment::validate`].
+//!
+//! [`FileContent::load`]: super::FileContent::load
+//! [`Stock::consume_stream`]: crate::persistence::Stock::consume_stream
+
+use std::collections::BTreeSet;
+use std::io::{self, Read};
+
+use aluvm::library::Lib;
+use amplify::confinement::{
+    Confined, LargeOrdSet, MediumBlob, SmallOrdMap, TinyOrdMap, TinyOrdSet, U32 as FILE_MAX_LEN,
+};
+use rgb::validation::CONSIGNMENT_MAX_LIBS;
+use rgb::{
+    AttachId, BundleId, ContractId, Extension, Genesis, OpId, Operation, Schema, XChain,
+    XWitnessId,
+};
+use strict_encoding::{DecodeError, StreamReader, StrictDecode, StrictReader};
+use strict_types::TypeSystem;
+
+use super::file::check_magic;
+use super::{
+    BundledWitness, Consignment, ContainerVer, ContentId, ContentSigs, Contract, FileContent,
+    Supplement, Transfer, XPubWitness,
+};
+use crate::interface::{Iface, IfaceImpl};
+use crate::SecretSeal;
+
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum StreamError {
+    /// invalid file data.
+    InvalidMagic,
+
+    #[display(inner)]
+    #[from]
+    #[from(io::Error)]
+    Decode(DecodeError),
+
+    /// consignment type doesn't match the expected one.
+    Type,
+
+    /// bundled witness {0} is repeated or doesn't follow the consignment order.
+    Order(XWitnessId),
+
+    /// bundle {0} contains state transitions which are not a part of the
+    /// bundle, don't match their ids or belong to a different contract.
+    InvalidBundle(BundleId),
+
+    /// anchor of the bundle {0} doesn't commit to the consignment contract, or
+    /// the witness transaction doesn't commit to the anchor.
+    InvalidAnchor(BundleId),
+
+    /// terminal bundle {0} is not present in the consignment.
+    UnknownTerminal(BundleId),
+
+    /// consignment contract {0} is not known to the stock.
+    UnknownContract(ContractId),
+
+    /// state transition {0} doesn't match the contract schema.
+    SchemaMismatch(OpId),
+}
+
+/// Consignment data serialized before the bundles.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConsignmentHeader {
+    pub version: ContainerVer,
+    pub transfer: bool,
+    pub terminals: SmallOrdMap<BundleId, XChain<SecretSeal>>,
+    pub genesis: Genesis,
+    pub extensions: LargeOrdSet<Extension>,
+}
+
+/// Consignment data serialized after the bundles.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConsignmentTrailer {
+    pub schema: Schema,
+    pub ifaces: TinyOrdMap<Iface, IfaceImpl>,
+    pub supplements: TinyOrdSet<Supplement>,
+    pub types: TypeSystem,
+    pub scripts: Confined<BTreeSet<Lib>, 0, CONSIGNMENT_MAX_LIBS>,
+    pub attachments: SmallOrdMap<AttachId, MediumBlob>,
+    pub signatures: TinyOrdMap<ContentId, ContentSigs>,
+}
+
+/// Reader decoding consignment bundles one by one from a file or any other
+/// data stream.
+///
+/// Bundles are returned by the [`Iterator`] implementation; once all of them
+/// are read, [`ConsignmentReader::finish`] must be called to read and return
+/// the rest of the consignment data.
+pub struct ConsignmentReader<R: Read, const TRANSFER: bool> {
+    reader: StrictReader<StreamReader<R>>,
+    header: ConsignmentHeader,
+    remaining: u32,
+    last_witness: Option<XPubWitness>,
+    terminals: BTreeSet<BundleId>,
+}
+
+pub type TransferReader<R> = ConsignmentReader<R, true>;
+pub type ContractReader<R> = ConsignmentReader<R, false>;
+
+impl<R: Read, const TRANSFER: bool> ConsignmentReader<R, TRANSFER> {
+    /// Checks file magic bytes and reads consignment data preceding the
+    /// bundles.
+    pub fn new(mut data: R) -> Result<Self, StreamError> {
+        let magic = if TRANSFER { Transfer::MAGIC } else { Contract::MAGIC };
+        if !check_magic(&mut data, magic)? {
+            return Err(StreamError::InvalidMagic);
+        }
+
+        let mut reader = StrictReader::with(StreamReader::new::<FILE_MAX_LEN>(data));
+        let header = ConsignmentHeader {
+            version: StrictDecode::strict_decode(&mut reader)?,
+            transfer: StrictDecode::strict_decode(&mut reader)?,
+            terminals: StrictDecode::strict_decode(&mut reader)?,
+            genesis: StrictDecode::strict_decode(&mut reader)?,
+            extensions: StrictDecode::strict_decode(&mut reader)?,
+        };
+        if header.transfer != TRANSFER {
+            return Err(StreamError::Type);
+        }
+        let remaining = u32::strict_decode(&mut reader)?;
+        let terminals = header.terminals.keys().copied().collect();
+
+        Ok(Self {
+            reader,
+            header,
+            remaining,
+            last_witness: None,
+            terminals,
+        })
+    }
+
+    pub fn header(&self) -> &ConsignmentHeader { &self.header }
+
+    pub fn contract_id(&self) -> ContractId { self.header.genesis.contract_id() }
+
+    /// Number of bundled witnesses which are not read yet.
+    pub fn remaining_bundles(&self) -> u32 { self.remaining }
+
+    fn read_bundled_witness(&mut self) -> Result<BundledWitness, StreamError> {
+        let bw = BundledWitness::strict_decode(&mut self.reader)?;
+        let witness_id = bw.witness_id();
+        if matches!(&self.last_witness, Some(last) if *last >= bw.pub_witness) {
+            return Err(StreamError::Order(witness_id));
+        }
+
+        let contract_id = self.contract_id();
+        for bundle in bw.bundles() {
+            let bundle_id = bundle.bundle_id();
+            let opids = bundle.input_map.values().copied().collect::<BTreeSet<_>>();
+            if bundle.known_transitions.iter().any(|(opid, transition)| {
+                !opids.contains(opid) ||
+                    transition.id() != *opid ||
+                    transition.contract_id != contract_id
+            }) {
+                return Err(StreamError::InvalidBundle(bundle_id));
+            }
+            bw.anchored_bundles
+                .to_anchor_set(contract_id, bundle_id)
+                .map_err(|_| StreamError::InvalidAnchor(bundle_id))?;
+            self.terminals.remove(&bundle_id);
+        }
+        let tx = match &bw.pub_witness {
+            XChain::Bitcoin(witness) | XChain::Liquid(witness) => witness.tx(),
+            _ => unreachable!(),
+        };
+        if let Some(tx) = tx {
+            bw.anchored_bundles
+                .verify(contract_id, tx)
+                .map_err(StreamError::InvalidAnchor)?;
+        }
+
+        self.last_witness = Some(bw.pub_witness.clone());
+        Ok(bw)
+    }
+
+    /// Reads consignment data following the bundles, checking that all
+    /// terminal bundles were present.
+    ///
+    /// Bundles which were not read yet are read, validated and dropped.
+    pub fn finish(mut self) -> Result<(ConsignmentHeader, ConsignmentTrailer), StreamError> {
+        for bw in self.by_ref() {
+            bw?;
+        }
+        if let Some(bundle_id) = self.terminals.first() {
+            return Err(StreamError::UnknownTerminal(*bundle_id));
+        }
+
+        let reader = &mut self.reader;
+        let trailer = ConsignmentTrailer {
+            schema: StrictDecode::strict_decode(reader)?,
+            ifaces: StrictDecode::strict_decode(reader)?,
+            supplements: StrictDecode::strict_decode(reader)?,
+            types: StrictDecode::strict_decode(reader)?,
+            scripts: StrictDecode::strict_decode(reader)?,
+            attachments: StrictDecode::strict_decode(reader)?,
+            signatures: StrictDecode::strict_decode(reader)?,
+        };
+        Ok((self.header, trailer))
+    }
+
+    /// Reads the whole consignment into memory.
+    pub fn into_consignment(mut self) -> Result<Consignment<TRANSFER>, StreamError> {
+        let bundles = self.by_ref().collect::<Result<Vec<_>, _>>()?;
+        let (header, trailer) = self.finish()?;
+        Ok(Consignment {
+            version: header.version,
+            transfer: header.transfer,
+            terminals: header.terminals,
+            genesis: header.genesis,
+            extensions: header.extensions,
+            bundles: LargeOrdSet::from_iter_unsafe(bundles),
+            schema: trailer.schema,
+            ifaces: trailer.ifaces,
+            supplements: trailer.supplements,
+            types: trailer.types,
+            scripts: trailer.scripts,
+            attachments: trailer.attachments,
+            signatures: trailer.signatures,
+        })
+    }
+}
+
+impl<R: Read, const TRANSFER: bool> Iterator for ConsignmentReader<R, TRANSFER> {
+    type Item = Result<BundledWitness, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let res = self.read_bundled_witness();
+        if res.is_err() {
+            // The stream can't be continued after a decoding or validation failure
+            self.remaining = 0;
+        }
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+
+    use bp::Tx;
+    use strict_encoding::{StreamWriter, StrictEncode, StrictWriter};
+
+    use super::*;
+    use crate::containers::file::MAGIC_LEN;
+    use crate::containers::{ConsignmentExt, PubWitness};
+    use crate::persistence::fixtures;
+
+    static DEFAULT_CONTRACT_PATH: &str = "asset/contract.default";
+    static DEFAULT_TRANSFER_PATH: &str = "asset/transfer.default";
+
+    #[test]
+    fn stream_matches_load() {
+        let transfer = Transfer::load(File::open(DEFAULT_TRANSFER_PATH).unwrap()).unwrap();
+        let reader = TransferReader::new(File::open(DEFAULT_TRANSFER_PATH).unwrap()).unwrap();
+        assert_eq!(reader.contract_id(), transfer.contract_id());
+        assert_eq!(reader.remaining_bundles() as usize, transfer.bundles.len());
+        assert_eq!(reader.into_consignment().unwrap(), transfer);
+
+        let contract = Contract::load(File::open(DEFAULT_CONTRACT_PATH).unwrap()).unwrap();
+        let reader = ContractReader::new(File::open(DEFAULT_CONTRACT_PATH).unwrap()).unwrap();
+        let (header, trailer) = reader.finish().unwrap();
+        assert_eq!(header.genesis, contract.genesis);
+        assert_eq!(trailer.schema, contract.schema);
+    }
+
+    #[test]
+    fn stream_wrong_type() {
+        assert!(matches!(
+            TransferReader::new(File::open(DEFAULT_CONTRACT_PATH).unwrap()),
+            Err(StreamError::InvalidMagic)
+        ));
+
+        let mut data = vec![];
+        let mut contract = Contract::load(File::open(DEFAULT_CONTRACT_PATH).unwrap()).unwrap();
+        contract.transfer = true;
+        contract.save(&mut data).unwrap();
+        assert!(matches!(ContractReader::new(data.as_slice()), Err(StreamError::Type)));
+    }
+
+    /// Serializes the transfer with the bundled witnesses in the provided
+    /// order, which may violate the consignment rules.
+    fn serialize(
+        transfer: &Transfer,
+        bundles: impl IntoIterator<Item = BundledWitness>,
+    ) -> Vec<u8> {
+        let bundles = bundles.into_iter().collect::<Vec<_>>();
+        let mut data = vec![];
+        transfer.save(&mut data).unwrap();
+        data.truncate(4 + MAGIC_LEN);
+
+        let mut writer = StrictWriter::with(StreamWriter::new::<FILE_MAX_LEN>(&mut data));
+        writer = transfer.version.strict_encode(writer).unwrap();
+        writer = transfer.transfer.strict_encode(writer).unwrap();
+        writer = transfer.terminals.strict_encode(writer).unwrap();
+        writer = transfer.genesis.strict_encode(writer).unwrap();
+        writer = transfer.extensions.strict_encode(writer).unwrap();
+        writer = (bundles.len() as u32).strict_encode(writer).unwrap();
+        for bw in &bundles {
+            writer = bw.strict_encode(writer).unwrap();
+        }
+        writer = transfer.schema.strict_encode(writer).unwrap();
+        writer = transfer.ifaces.strict_encode(writer).unwrap();
+        writer = transfer.supplements.strict_encode(writer).unwrap();
+        writer = transfer.types.strict_encode(writer).unwrap();
+        writer = transfer.scripts.strict_encode(writer).unwrap();
+        writer = transfer.attachments.strict_encode(writer).unwrap();
+        transfer.signatures.strict_encode(writer).unwrap();
+        data
+    }
+
+    /// Transfer with the committed witness transactions and its bundled
+    /// witnesses, ordered as the transactions.
+    fn transfer() -> (Transfer, [BundledWitness; 2], [Tx; 2]) {
+        let (transfer, txs) = fixtures::committed_transfer();
+        let bundled_witness = |tx: &Tx| {
+            let witness_id = XWitnessId::Bitcoin(tx.txid());
+            transfer
+                .bundles
+                .iter()
+                .find(|bw| bw.witness_id() == witness_id)
+                .unwrap()
+                .clone()
+        };
+        let bundles = [bundled_witness(&txs[0]), bundled_witness(&txs[1])];
+        (transfer, bundles, txs)
+    }
+
+    #[test]
+    fn stream_bundles() {
+        let (transfer, ..) = transfer();
+        assert_eq!(transfer.bundles.len(), 2);
+        let mut data = vec![];
+        transfer.save(&mut data).unwrap();
+        assert_eq!(serialize(&transfer, transfer.bundles.iter().cloned()), data);
+
+        let mut reader = TransferReader::new(data.as_slice()).unwrap();
+        assert_eq!(reader.remaining_bundles(), 2);
+        for bw in &transfer.bundles {
+            assert_eq!(reader.next().unwrap().unwrap().disclose_hash(), bw.disclose_hash());
+        }
+        assert!(reader.next().is_none());
+        let (header, trailer) = reader.finish().unwrap();
+        assert_eq!(header.terminals, transfer.terminals);
+        assert_eq!(trailer.schema, transfer.schema);
+    }
+
+    #[test]
+    fn stream_order() {
+        let (transfer, ..) = transfer();
+        let [first, second] = [0, 1].map(|no| transfer.bundles.iter().nth(no).unwrap().clone());
+
+        let data = serialize(&transfer, [second.clone(), first.clone()]);
+        let mut reader = TransferReader::new(data.as_slice()).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next(),
+            Some(Err(StreamError::Order(id))) if id == first.witness_id()
+        ));
+        assert!(reader.next().is_none());
+
+        let data = serialize(&transfer, [first.clone(), first.clone(), second]);
+        let reader = TransferReader::new(data.as_slice()).unwrap();
+        assert!(matches!(
+            reader.finish(),
+            Err(StreamError::Order(id)) if id == first.witness_id()
+        ));
+    }
+
+    #[test]
+    fn stream_invalid_bundle() {
+        let (transfer, [mut bw, other], _) = transfer();
+        let bundle = bw.anchored_bundles.bundles_mut().next().unwrap();
+        let bundle_id = bundle.bundle_id();
+        for (_, transition) in bundle.known_transitions.keyed_values_mut() {
+            transition.nonce += 10;
+        }
+
+        let data = serialize(&transfer, [bw, other]);
+        let mut reader = TransferReader::new(data.as_slice()).unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(StreamError::InvalidBundle(id))) if id == bundle_id
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn stream_invalid_anchor() {
+        let (transfer, [bw, other], txs) = transfer();
+        let contract_id = transfer.contract_id();
+
+        // Bundle which is not committed under the contract
+        let mut forged = bw.clone();
+        let genesis_id = transfer.genesis.id();
+        let bundle = fixtures::bundle([fixtures::transition(
+            contract_id,
+            10,
+            [fixtures::opout(genesis_id, 0)],
+            [fixtures::seal(0)],
+        )]);
+        let bundle_id = bundle.bundle_id();
+        *forged.anchored_bundles.bundles_mut().next().unwrap() = bundle;
+        let data = serialize(&transfer, [forged, other.clone()]);
+        let mut reader = TransferReader::new(data.as_slice()).unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(StreamError::InvalidAnchor(id))) if id == bundle_id
+        ));
+
+        // Witness transaction which doesn't commit to the bundle
+        let mut forged = bw.clone();
+        let bundle_id = forged.bundles().next().unwrap().bundle_id();
+        forged.pub_witness = XChain::Bitcoin(PubWitness::with(txs[1].clone()));
+        let data = serialize(&transfer, [forged]);
+        let mut reader = TransferReader::new(data.as_slice()).unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(StreamError::InvalidAnchor(id))) if id == bundle_id
+        ));
+
+        // Witness transaction which is not included can't be checked
+        let mut concealed = bw;
+        concealed.pub_witness = XChain::Bitcoin(PubWitness::new(txs[0].txid()));
+        let data = serialize(&transfer, [concealed]);
+        let mut reader = TransferReader::new(data.as_slice()).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+    }
+
+    #[test]
+    fn stream_unknown_terminal() {
+        let (transfer, [bw, other], _) = transfer();
+        let terminal = *transfer.terminals.keys().next().unwrap();
+        assert!(other.bundles().any(|bundle| bundle.bundle_id() == terminal));
+
+        let data = serialize(&transfer, [bw]);
+        let reader = TransferReader::new(data.as_slice()).unwrap();
+        assert!(matches!(
+            reader.finish(),
+            Err(StreamError::UnknownTerminal(id)) if id == terminal
+        ));
+
+        let data = serialize(&transfer, [other]);
+        let reader = TransferReader::new(data.as_slice()).unwrap();
+        assert!(reader.finish().is_ok());
+    }
+}
@@ -26,7 +26,7 @@ use commit_verify::StrictHash;
 use rgb::{ContractId, Identity, SchemaId};
 use strict_encoding::StrictDumb;
 
-use super::SupplId;
+use super::{ContentRef, SupplId};
 use crate::interface::{IfaceId, ImplId};
 use crate::LIB_NAME_RGB_STD;
 
@@ -97,6 +97,17 @@ pub enum ContentId {
     Suppl(SupplId),
 }
 
+impl From<ContentRef> for ContentId {
+    fn from(content_ref: ContentRef) -> Self {
+        match content_ref {
+            ContentRef::Schema(id) => ContentId::Schema(id),
+            ContentRef::Genesis(id) => ContentId::Genesis(id),
+            ContentRef::Iface(id) => ContentId::Iface(id),
+            ContentRef::IfaceImpl(id) => ContentId::IfaceImpl(id),
+        }
+    }
+}
+
 #[derive(Wrapper, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, From, Display)]
 #[wrapper(Deref, AsSlice, BorrowSlice, Hex)]
 #[display(LowerHex)]
@@ -19,6 +19,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::borrow::Borrow;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::Debug;
@@ -88,6 +89,12 @@ impl<State: KnownState> Ord for OutputAssignment<State> {
     }
 }
 
+// Ordering and equality of the assignments are defined by the opout only, thus it
+// is safe to look them up in ordered collections by the opout.
+impl<State: KnownState> Borrow<Opout> for OutputAssignment<State> {
+    fn borrow(&self) -> &Opout { &self.opout }
+}
+
 impl<State: KnownState> OutputAssignment<State> {
     /// # Panics
     ///
@@ -156,6 +163,15 @@ impl<State: KnownState> OutputAssignment<State> {
 pub trait TypedAssignsExt<Seal: ExposedSeal> {
     fn reveal_seal(&mut self, seal: XChain<Seal>);
 
+    /// Conceals the seal of the assignment with the index `no`, keeping its
+    /// state.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the seal was previously revealed; `false` if it was already
+    /// concealed or there is no assignment with the index `no`.
+    fn conceal_seal(&mut self, no: u16) -> bool;
+
     fn filter_revealed_seals(&self) -> Vec<XChain<Seal>>;
 }
 
@@ -196,6 +212,43 @@ impl<Seal: ExposedSeal> TypedAssignsExt<Seal> for TypedAssigns<Seal> {
         }
     }
 
+    fn conceal_seal(&mut self, no: u16) -> bool {
+        fn conceal<State: ExposedState, Seal: ExposedSeal>(
+            vec: &mut SmallVec<Assign<State, Seal>>,
+            no: u16,
+        ) -> bool {
+            let Some(assign) = vec.iter_mut().nth(no as usize) else {
+                return false;
+            };
+            match assign {
+                Assign::Revealed { seal, state, lock } => {
+                    *assign = Assign::ConfidentialSeal {
+                        seal: seal.conceal(),
+                        state: state.clone(),
+                        lock: *lock,
+                    };
+                    true
+                }
+                Assign::ConfidentialState { seal, state, lock } => {
+                    *assign = Assign::Confidential {
+                        seal: seal.conceal(),
+                        state: *state,
+                        lock: *lock,
+                    };
+                    true
+                }
+                Assign::ConfidentialSeal { .. } | Assign::Confidential { .. } => false,
+            }
+        }
+
+        match self {
+            TypedAssigns::Declarative(v) => conceal(v, no),
+            TypedAssigns::Fungible(v) => conceal(v, no),
+            TypedAssigns::Structured(v) => conceal(v, no),
+            TypedAssigns::Attachment(v) => conceal(v, no),
+        }
+    }
+
     fn filter_revealed_seals(&self) -> Vec<XChain<Seal>> {
         match self {
             TypedAssigns::Declarative(s) => {
@@ -19,6 +19,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeSet;
+
+use amplify::confinement::Confined;
 use rgb::{GraphSeal, OpId, Operation, Transition, TransitionBundle, XChain};
 
 use crate::contract::TypedAssignsExt;
@@ -41,6 +44,14 @@ pub trait BundleExt {
     /// `true` if the transition was previously concealed; `false` if it was
     /// already revealed; error if the transition is unrelated to the bundle.
     fn reveal_transition(&mut self, transition: Transition) -> Result<bool, RevealError>;
+
+    /// Conceals all transitions inside the bundle except the ones from
+    /// `opids`.
+    ///
+    /// # Returns
+    ///
+    /// Number of transitions which were concealed.
+    fn conceal_transitions(&mut self, opids: &BTreeSet<OpId>) -> usize;
 }
 
 impl BundleExt for TransitionBundle {
@@ -65,4 +76,12 @@ impl BundleExt for TransitionBundle {
             .expect("same size as input map");
         Ok(true)
     }
+
+    fn conceal_transitions(&mut self, opids: &BTreeSet<OpId>) -> usize {
+        let mut known_transitions = self.known_transitions.clone().into_inner();
+        known_transitions.retain(|opid, _| opids.contains(opid));
+        let concealed = self.known_transitions.len() - known_transitions.len();
+        self.known_transitions = Confined::from_collection_unsafe(known_transitions);
+        concealed
+    }
 }
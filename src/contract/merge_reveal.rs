@@ -220,10 +220,11 @@ impl MergeReveal for TransitionBundle {
 
         let mut self_transitions = self.known_transitions.into_inner();
         for (opid, other_transition) in other.known_transitions {
-            if let Some(mut transition) = self_transitions.remove(&opid) {
-                transition = transition.merge_reveal(other_transition)?;
-                self_transitions.insert(opid, transition);
-            }
+            let transition = match self_transitions.remove(&opid) {
+                Some(transition) => transition.merge_reveal(other_transition)?,
+                None => other_transition,
+            };
+            self_transitions.insert(opid, transition);
         }
         self.known_transitions = Confined::from_collection_unsafe(self_transitions);
 
@@ -292,3 +293,36 @@ impl MergeReveal for Extension {
         Ok(self)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::persistence::fixtures;
+    use crate::BundleExt;
+
+    #[test]
+    fn bundle_transitions() {
+        let genesis = fixtures::genesis(1, 2);
+        let contract_id = genesis.contract_id();
+        let spend = |no: u8| {
+            let input = fixtures::opout(genesis.id(), no as u16);
+            fixtures::transition(contract_id, no, [input], [fixtures::seal(no as u32)])
+        };
+        let bundle = fixtures::bundle([spend(0), spend(1)]);
+        let opids = bundle.known_transitions.keys().copied().collect::<Vec<_>>();
+        let concealed = |opid: OpId| {
+            let mut bundle = bundle.clone();
+            bundle.conceal_transitions(&BTreeSet::from([opid]));
+            bundle
+        };
+        let (first, second) = (concealed(opids[0]), concealed(opids[1]));
+
+        // Transitions known only to the other bundle are revealed
+        assert_eq!(first.clone().merge_reveal(second.clone()).unwrap(), bundle);
+        assert_eq!(second.clone().merge_reveal(first.clone()).unwrap(), bundle);
+        assert_eq!(first.clone().merge_reveal(first.clone()).unwrap(), first);
+        assert_eq!(bundle.clone().merge_reveal(second).unwrap(), bundle);
+    }
+}
@@ -25,7 +25,7 @@ use std::str::FromStr;
 
 use amplify::confinement::TinyOrdSet;
 use chrono::{DateTime, TimeZone, Utc};
-use rgb::{AltLayer1Set, ContractId, Genesis, Identity, Operation, SchemaId};
+use rgb::{AltLayer1, AltLayer1Set, ContractId, Genesis, Identity, Layer1, Operation, SchemaId};
 use strict_encoding::stl::{AlphaCapsLodash, AlphaNumLodash};
 use strict_encoding::{FieldName, RString, StrictDeserialize, StrictSerialize, TypeName};
 
@@ -299,6 +299,15 @@ impl ContractInfo {
             alt_layers1: genesis.alt_layers1.clone(),
         }
     }
+
+    /// Checks whether the contract may have its state assigned to seals on the
+    /// provided layer 1.
+    pub fn has_layer1(&self, layer1: Layer1) -> bool {
+        match layer1 {
+            Layer1::Bitcoin => true,
+            Layer1::Liquid => self.alt_layers1.contains(&AltLayer1::Liquid),
+        }
+    }
 }
 
 impl Display for ContractInfo {
@@ -234,6 +234,12 @@ impl IfaceImpl {
     #[inline]
     pub fn impl_id(&self) -> ImplId { self.commit_id() }
 
+    /// Checks whether the implementation is a newer revision than the `other`
+    /// one, comparing their versions and, for the same version, timestamps.
+    pub fn supersedes(&self, other: &IfaceImpl) -> bool {
+        (self.version, self.timestamp) > (other.version, other.timestamp)
+    }
+
     pub fn meta_name(&self, id: MetaType) -> Option<&FieldName> {
         self.metadata
             .iter()
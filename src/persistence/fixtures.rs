@@ -0,0 +1,404 @@
+// RGB standard library for working with smart contracts on Bitcoin & Lightning
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contract histories with real transition bundles and anchors for the tests.
+//!
+//! Transitions assign declarative rights only; they match the structure of
+//! the fixture schema, but are not valid against any scripts, so the data can
+//! be added to a stock only bypassing the validation (by consuming a fascia,
+//! accepting a disclosure or replaying the stash).
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use amplify::confinement::{Confined, SmallOrdSet, U32};
+use amplify::num::u5;
+use amplify::{ByteArray, Wrapper};
+use bp::dbc::{Anchor, Method};
+use bp::{Sats, ScriptPubkey, Tx, TxOut, Txid, Vin, Vout};
+use commit_verify::{mpc, CommitId, TryCommitVerify};
+use rgb::validation::{ResolveWitness, WitnessResolverError};
+use rgb::vm::{WitnessOrd, WitnessPos, XWitnessTx};
+use rgb::{
+    Assign, AssignmentType, Assignments, BundleId, ContractId, Genesis, GenesisSeal, GraphSeal,
+    Input, InputMap, Occurrences, OpId, Operation, Opout, OwnedStateSchema, Schema, Transition,
+    TransitionBundle, TransitionSchema, TransitionType, TypedAssigns, VoidState, XChain,
+    XWitnessId,
+};
+use strict_encoding::{StrictDumb, StrictSerialize};
+
+use super::{
+    IndexProvider, MemIndex, MemStash, MemState, StashProvider, StashWriteProvider, StateProvider,
+    Stock,
+};
+#[cfg(feature = "sqlite")]
+use super::{SqlIndex, SqlStash, SqlState};
+use crate::containers::{AnchorSet, Dichotomy, Fascia, PubWitness, Transfer};
+
+pub fn txid(no: u8) -> Txid { Txid::from([no; 32]) }
+
+pub fn witness_id(no: u8) -> XWitnessId { XWitnessId::Bitcoin(txid(no)) }
+
+pub fn mined(height: u32) -> WitnessOrd {
+    WitnessOrd::Mined(WitnessPos::new(height, 1_700_000_000 + height as i64).unwrap())
+}
+
+/// Schema declaring the rights and the transition type used by the fixtures.
+pub fn schema() -> Schema {
+    let mut transition = TransitionSchema::strict_dumb();
+    transition.assignments =
+        Confined::from_iter_unsafe([(AssignmentType::strict_dumb(), Occurrences::OnceOrMore)]);
+    let mut schema = Schema::strict_dumb();
+    schema.owned_types = Confined::from_iter_unsafe([(
+        AssignmentType::strict_dumb(),
+        OwnedStateSchema::Declarative,
+    )]);
+    schema.transitions = Confined::from_iter_unsafe([(TransitionType::strict_dumb(), transition)]);
+    schema
+}
+
+/// Opout of the declarative rights assigned by an operation.
+pub fn opout(opid: OpId, no: u16) -> Opout { Opout::new(opid, AssignmentType::strict_dumb(), no) }
+
+/// Seal of the `vout` output of the genesis transaction of the contract `no`.
+pub fn genesis_seal(no: u8, vout: u32) -> XChain<GenesisSeal> {
+    XChain::Bitcoin(GenesisSeal::with_blinding(
+        Method::OpretFirst,
+        txid(0xF0 - no),
+        Vout::from_u32(vout),
+        no as u64 * 1000 + vout as u64,
+    ))
+}
+
+/// Genesis of the contract `no` assigning rights to `outputs` outputs.
+pub fn genesis(no: u8, outputs: u32) -> Genesis {
+    let mut genesis = Genesis::strict_dumb();
+    genesis.timestamp = no as i64;
+    genesis.schema_id = schema().schema_id();
+    if outputs > 0 {
+        let rights = (0..outputs).map(|vout| Assign::Revealed {
+            seal: genesis_seal(no, vout),
+            state: VoidState::default(),
+            lock: Default::default(),
+        });
+        let rights = TypedAssigns::Declarative(Confined::from_iter_unsafe(rights));
+        genesis.assignments = Assignments::from_inner(Confined::from_iter_unsafe([(
+            AssignmentType::strict_dumb(),
+            rights,
+        )]));
+    }
+    genesis
+}
+
+/// Seal of the `vout` output of a witness transaction.
+pub fn seal(vout: u32) -> XChain<GraphSeal> {
+    XChain::Bitcoin(GraphSeal::new_random_vout(Method::OpretFirst, Vout::from_u32(vout)))
+}
+
+pub fn transition(
+    contract_id: ContractId,
+    nonce: u8,
+    inputs: impl IntoIterator<Item = Opout>,
+    seals: impl IntoIterator<Item = XChain<GraphSeal>>,
+) -> Transition {
+    let mut transition = Transition::strict_dumb();
+    transition.contract_id = contract_id;
+    transition.nonce = nonce;
+    transition.inputs = SmallOrdSet::from_iter_unsafe(inputs.into_iter().map(Input::with)).into();
+    let rights = seals.into_iter().map(|seal| Assign::Revealed {
+        seal,
+        state: VoidState::default(),
+        lock: Default::default(),
+    });
+    let rights = TypedAssigns::Declarative(Confined::from_iter_unsafe(rights));
+    transition.assignments = Assignments::from_inner(Confined::from_iter_unsafe([(
+        AssignmentType::strict_dumb(),
+        rights,
+    )]));
+    transition
+}
+
+/// Bundle of transitions, each of them spending its inputs from the witness
+/// transaction inputs following the ones of the previous transition.
+pub fn bundle(transitions: impl IntoIterator<Item = Transition>) -> TransitionBundle {
+    let mut input_map = BTreeMap::new();
+    let mut known_transitions = BTreeMap::new();
+    for transition in transitions {
+        let opid = transition.id();
+        for _ in &transition.inputs {
+            input_map.insert(Vin::from_u32(input_map.len() as u32), opid);
+        }
+        known_transitions.insert(opid, transition);
+    }
+    TransitionBundle {
+        input_map: InputMap::from_inner(Confined::from_collection_unsafe(input_map)),
+        known_transitions: Confined::from_collection_unsafe(known_transitions),
+    }
+}
+
+/// Opret anchor committing to the provided bundles.
+pub fn anchor(bundles: impl IntoIterator<Item = (ContractId, BundleId)>) -> AnchorSet {
+    let messages = bundles.into_iter().map(|(contract_id, bundle_id)| {
+        (
+            mpc::ProtocolId::from_byte_array(contract_id.to_byte_array()),
+            mpc::Message::from_byte_array(bundle_id.to_byte_array()),
+        )
+    });
+    let source = mpc::MultiSource {
+        method: mpc::Method::Sha256t,
+        min_depth: u5::with(3),
+        messages: Confined::from_iter_unsafe(messages),
+        static_entropy: Some(0),
+    };
+    let tree = mpc::MerkleTree::try_commit(&source).expect("valid MPC source");
+    AnchorSet::Opret(Anchor::new(mpc::MerkleBlock::from(tree), strict_dumb!()))
+}
+
+/// Fascia of the witness transaction `txid(witness)` with a single bundle per
+/// contract.
+pub fn fascia(
+    witness: u8,
+    bundles: impl IntoIterator<Item = (ContractId, TransitionBundle)>,
+) -> Fascia {
+    let bundles = bundles.into_iter().collect::<BTreeMap<_, _>>();
+    Fascia {
+        witness: XChain::Bitcoin(PubWitness::new(txid(witness))),
+        anchor: anchor(
+            bundles
+                .iter()
+                .map(|(contract_id, bundle)| (*contract_id, bundle.bundle_id())),
+        ),
+        bundles: Confined::from_iter_unsafe(
+            bundles
+                .into_iter()
+                .map(|(contract_id, bundle)| (contract_id, Dichotomy::with(bundle, None))),
+        ),
+    }
+}
+
+/// Witness transaction committing to the anchor with an opret output.
+pub fn witness_tx(anchor: &AnchorSet) -> Tx {
+    let AnchorSet::Opret(anchor) = anchor else {
+        unreachable!("fixture anchors are opret anchors")
+    };
+    let commitment = anchor.mpc_proof.commit_id();
+    let mut tx = Tx::strict_dumb();
+    tx.outputs = Confined::from_iter_unsafe([TxOut {
+        value: Sats::ZERO,
+        script_pubkey: ScriptPubkey::op_return(&commitment.to_byte_array()),
+    }]);
+    tx
+}
+
+/// Fascia with a single bundle per contract, anchored to a witness
+/// transaction which really commits to the bundles.
+///
+/// The transaction is returned together with the fascia, so it can be
+/// provided to [`Resolver::with_txs`].
+pub fn committed_fascia(
+    bundles: impl IntoIterator<Item = (ContractId, TransitionBundle)>,
+) -> (Fascia, Tx) {
+    let mut fascia = fascia(0, bundles);
+    let tx = witness_tx(&fascia.anchor);
+    fascia.witness = XChain::Bitcoin(PubWitness::with(tx.clone()));
+    (fascia, tx)
+}
+
+/// Transfer of the contract `genesis(1, 2)` with two bundles anchored to
+/// committed witness transactions, which are returned together with the
+/// transfer. The first bundle spends both genesis outputs, and the second one
+/// spends the first output of the first bundle, assigning the rights to the
+/// terminal seal.
+pub fn committed_transfer() -> (Transfer, [Tx; 2]) {
+    let genesis = genesis(1, 2);
+    let contract_id = genesis.contract_id();
+    let first = transition(contract_id, 0, [opout(genesis.id(), 0)], [seal(0)]);
+    let second = transition(contract_id, 1, [opout(genesis.id(), 1)], [seal(1)]);
+    let terminal = seal(0);
+    let third = transition(contract_id, 2, [opout(first.id(), 0)], [terminal]);
+    let (fascia1, tx1) = committed_fascia([(contract_id, bundle([first, second]))]);
+    let (fascia2, tx2) = committed_fascia([(contract_id, bundle([third]))]);
+    let witness_id = fascia2.witness_id();
+
+    let mut stock = stock([genesis]);
+    stock.consume_fascia(fascia1, Resolver::default()).unwrap();
+    stock.consume_fascia(fascia2, Resolver::default()).unwrap();
+    let output = terminal.try_to_output_seal(witness_id).unwrap();
+    let transfer = stock.transfer(contract_id, [output], []).unwrap();
+    (transfer, [tx1, tx2])
+}
+
+/// Set of persistence providers the stock tests are run against.
+pub trait Providers: Sized {
+    type Stash: StashProvider;
+    type State: StateProvider;
+    type Index: IndexProvider;
+    /// Data kept by the providers, allowing to check whether they were
+    /// modified.
+    type Dump: Eq + Debug;
+
+    fn stash() -> Self::Stash;
+    fn state() -> Self::State;
+    fn index() -> Self::Index;
+    fn dump(stock: &TestStock<Self>) -> Self::Dump;
+}
+
+pub type TestStock<P> =
+    Stock<<P as Providers>::Stash, <P as Providers>::State, <P as Providers>::Index>;
+
+/// In-memory providers.
+pub struct Mem;
+
+impl Providers for Mem {
+    type Stash = MemStash;
+    type State = MemState;
+    type Index = MemIndex;
+    type Dump = [Vec<u8>; 3];
+
+    fn stash() -> Self::Stash { MemStash::in_memory() }
+    fn state() -> Self::State { MemState::in_memory() }
+    fn index() -> Self::Index { MemIndex::in_memory() }
+
+    fn dump(stock: &TestStock<Self>) -> Self::Dump {
+        [
+            stock.as_stash_provider().to_strict_serialized::<U32>(),
+            stock.as_state_provider().to_strict_serialized::<U32>(),
+            stock.as_index_provider().to_strict_serialized::<U32>(),
+        ]
+        .map(|data| data.unwrap().into_inner())
+    }
+}
+
+/// Providers backed by in-memory SQLite databases.
+#[cfg(feature = "sqlite")]
+pub struct Sql;
+
+#[cfg(feature = "sqlite")]
+impl Providers for Sql {
+    type Stash = SqlStash;
+    type State = SqlState;
+    type Index = SqlIndex;
+    type Dump = [Vec<String>; 3];
+
+    fn stash() -> Self::Stash { SqlStash::in_memory().unwrap() }
+    fn state() -> Self::State { SqlState::in_memory().unwrap() }
+    fn index() -> Self::Index { SqlIndex::in_memory().unwrap() }
+
+    fn dump(stock: &TestStock<Self>) -> Self::Dump {
+        [
+            stock.as_stash_provider().debug_rows().unwrap(),
+            stock.as_state_provider().debug_rows().unwrap(),
+            stock.as_index_provider().debug_rows().unwrap(),
+        ]
+    }
+}
+
+/// Stock with no data.
+pub fn empty_stock<P: Providers>() -> TestStock<P> {
+    Stock::with(P::stash(), P::state(), P::index())
+}
+
+/// Stock with the provided contracts, which have no operations except their
+/// geneses.
+pub fn stock_with<P: Providers>(geneses: impl IntoIterator<Item = Genesis>) -> TestStock<P> {
+    let mut stash = P::stash();
+    stash.replace_schema(schema()).unwrap();
+    for genesis in geneses {
+        stash.replace_genesis(genesis).unwrap();
+    }
+    let mut stock = Stock::with(stash, P::state(), P::index());
+    stock
+        .reindex(P::state(), P::index(), Resolver::default())
+        .unwrap();
+    stock
+}
+
+/// In-memory stock with the provided contracts, which have no operations
+/// except their geneses.
+pub fn stock(geneses: impl IntoIterator<Item = Genesis>) -> Stock { stock_with::<Mem>(geneses) }
+
+/// Resolver returning the configured status of the witnesses, which are
+/// tentative unless configured otherwise, and the configured witness
+/// transactions.
+#[derive(Debug, Default)]
+pub struct Resolver {
+    ords: BTreeMap<XWitnessId, WitnessOrd>,
+    txs: BTreeMap<XWitnessId, Tx>,
+    /// Number of successful resolutions after which the resolver fails.
+    fail_after: Option<usize>,
+    calls: Cell<usize>,
+}
+
+impl Resolver {
+    pub fn with(ords: impl IntoIterator<Item = (XWitnessId, WitnessOrd)>) -> Self {
+        Resolver {
+            ords: ords.into_iter().collect(),
+            ..default!()
+        }
+    }
+
+    pub fn with_txs(txs: impl IntoIterator<Item = Tx>) -> Self {
+        Resolver {
+            txs: txs
+                .into_iter()
+                .map(|tx| (XWitnessId::Bitcoin(tx.txid()), tx))
+                .collect(),
+            ..default!()
+        }
+    }
+
+    pub fn failing_after(calls: usize) -> Self {
+        Resolver {
+            fail_after: Some(calls),
+            ..default!()
+        }
+    }
+}
+
+impl ResolveWitness for Resolver {
+    fn resolve_pub_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<XWitnessTx, WitnessResolverError> {
+        self.txs
+            .get(&witness_id)
+            .cloned()
+            .map(XChain::Bitcoin)
+            .ok_or(WitnessResolverError::Unknown(witness_id))
+    }
+
+    fn resolve_pub_witness_ord(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<WitnessOrd, WitnessResolverError> {
+        let calls = self.calls.get();
+        if self.fail_after.is_some_and(|max| calls >= max) {
+            return Err(WitnessResolverError::Unknown(witness_id));
+        }
+        self.calls.set(calls + 1);
+        Ok(self
+            .ords
+            .get(&witness_id)
+            .copied()
+            .unwrap_or(WitnessOrd::Tentative))
+    }
+}
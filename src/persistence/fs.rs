@@ -19,9 +19,46 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fs::{self, File};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
-use strict_encoding::{DeserializeError, SerializeError};
+use amplify::confinement::U8;
+use strict_encoding::{
+    DecodeError, DeserializeError, SerializeError, StrictDeserialize, StrictSerialize,
+};
+
+use crate::LIB_NAME_RGB_STORAGE;
+
+pub const STASH_FILENAME: &str = "stash.dat";
+pub const STATE_FILENAME: &str = "state.dat";
+pub const INDEX_FILENAME: &str = "index.dat";
+pub const MANIFEST_FILENAME: &str = "manifest.dat";
+
+/// Policy for flushing written files to the underlying storage device.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default)]
+pub enum SyncPolicy {
+    /// Do not call `fsync`. Writes are still atomic with respect to process
+    /// crashes, but an OS crash or a power loss may lose recent changes.
+    Never,
+    /// Call `fsync` on the file data before moving it into its place.
+    Data,
+    /// Call `fsync` on the file data and on the directory containing it, such
+    /// that the rename itself is durable.
+    #[default]
+    Full,
+}
+
+/// Configuration of the file-based storage.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct FsConfig {
+    pub sync: SyncPolicy,
+    /// Whether the file is a part of a multi-file storage (like a
+    /// [`crate::persistence::Stock`]). Staged files are written next to the
+    /// main file under the `.new` extension and are moved into place by the
+    /// owner of the storage only once all its parts are written.
+    pub staged: bool,
+}
 
 pub trait FsStored: Sized {
     fn new(path: impl ToOwned<Owned = PathBuf>) -> Self;
@@ -31,5 +68,293 @@ pub trait FsStored: Sized {
     fn filename(&self) -> Option<&Path>;
     fn set_filename(&mut self, filename: impl ToOwned<Owned = PathBuf>) -> Option<PathBuf>;
 
+    /// Returns configuration of the file-based storage. Defaults to the
+    /// non-staged configuration for the providers not supporting staged
+    /// writes.
+    fn fs_config(&self) -> FsConfig { FsConfig::default() }
+    /// Sets configuration of the file-based storage. Ignored by default, in
+    /// which case the provider writes its files in place, and the stock
+    /// publishing them is not atomic across its parts.
+    fn set_fs_config(&mut self, _config: FsConfig) {}
+
     fn store(&self) -> Result<(), SerializeError>;
 }
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Returns path of the file which is used to stage a new version of the file
+/// at `path` before it gets published.
+pub fn staging_path(path: &Path) -> PathBuf { with_suffix(path, "new") }
+
+fn sync_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+fn rename(from: &Path, to: &Path, sync: SyncPolicy) -> io::Result<()> {
+    fs::rename(from, to)?;
+    if sync == SyncPolicy::Full {
+        sync_dir(to)?;
+    }
+    Ok(())
+}
+
+/// Atomically replaces content of the file at `path` with `data`.
+///
+/// The data are written into a temporary file first, which is then renamed
+/// into `path`, such that a crash in the middle of the operation never leaves
+/// a partially written file.
+pub fn write_atomic(path: &Path, data: &[u8], sync: SyncPolicy) -> io::Result<()> {
+    let tmp = with_suffix(path, "tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    if sync >= SyncPolicy::Data {
+        file.sync_all()?;
+    }
+    drop(file);
+    rename(&tmp, path, sync)
+}
+
+/// Writes `data` either to the file at `path` or, for staged configurations,
+/// to its staging file.
+pub fn store_file(path: &Path, data: &[u8], config: FsConfig) -> io::Result<()> {
+    match config.staged {
+        true => write_atomic(&staging_path(path), data, config.sync),
+        false => write_atomic(path, data, config.sync),
+    }
+}
+
+/// Manifest tying together all files of a multi-file storage.
+///
+/// Publishing staged files is a two-phase process: first, the manifest listing
+/// all staged files is atomically written, which is the commit point; after
+/// that the staged files are moved into their places and the manifest is
+/// updated to list no pending files. On load, staged files listed in the
+/// manifest are moved into place, completing an interrupted publication, while
+/// all other staged files are discarded, since they belong to a write which
+/// has not reached its commit point.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB_STORAGE)]
+pub struct Manifest {
+    pub generation: u64,
+    pub pending_stash: bool,
+    pub pending_state: bool,
+    pub pending_index: bool,
+}
+
+impl StrictSerialize for Manifest {}
+impl StrictDeserialize for Manifest {}
+
+/// Multi-file storage of a [`crate::persistence::Stock`], kept in a single
+/// directory.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct StockDir {
+    path: PathBuf,
+    sync: SyncPolicy,
+    manifest: Manifest,
+}
+
+impl StockDir {
+    pub fn new(path: impl ToOwned<Owned = PathBuf>, sync: SyncPolicy) -> Self {
+        Self {
+            path: path.to_owned(),
+            sync,
+            manifest: default!(),
+        }
+    }
+
+    /// Opens storage directory, recovering from any interrupted writes.
+    pub fn open(
+        path: impl ToOwned<Owned = PathBuf>,
+        sync: SyncPolicy,
+    ) -> Result<Self, DeserializeError> {
+        let mut me = Self::new(path, sync);
+        let filename = me.path.join(MANIFEST_FILENAME);
+        if filename.exists() {
+            me.manifest = Manifest::strict_deserialize_from_file::<U8>(&filename).map_err(|err| {
+                DecodeError::DataIntegrityError(format!(
+                    "stock manifest {} is corrupted: {err}",
+                    filename.display()
+                ))
+            })?;
+        }
+        for (name, pending) in me.pending() {
+            let part = me.path.join(name);
+            let staged = staging_path(&part);
+            if !staged.exists() {
+                continue;
+            }
+            if pending {
+                rename(&staged, &part, sync)?;
+            } else {
+                fs::remove_file(&staged)?;
+            }
+        }
+        if me.pending().iter().any(|(_, pending)| *pending) {
+            me.clear_pending();
+            me.write_manifest()
+                .map_err(|err| DecodeError::DataIntegrityError(err.to_string()))?;
+        }
+        Ok(me)
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    pub fn set_path(&mut self, path: impl ToOwned<Owned = PathBuf>) { self.path = path.to_owned() }
+
+    pub fn sync_policy(&self) -> SyncPolicy { self.sync }
+
+    pub fn set_sync_policy(&mut self, sync: SyncPolicy) { self.sync = sync }
+
+    /// Returns the generation of the last published version of the storage.
+    pub fn generation(&self) -> u64 { self.manifest.generation }
+
+    pub fn stash_filename(&self) -> PathBuf { self.path.join(STASH_FILENAME) }
+    pub fn state_filename(&self) -> PathBuf { self.path.join(STATE_FILENAME) }
+    pub fn index_filename(&self) -> PathBuf { self.path.join(INDEX_FILENAME) }
+
+    fn pending(&self) -> [(&'static str, bool); 3] {
+        [
+            (STASH_FILENAME, self.manifest.pending_stash),
+            (STATE_FILENAME, self.manifest.pending_state),
+            (INDEX_FILENAME, self.manifest.pending_index),
+        ]
+    }
+
+    fn clear_pending(&mut self) {
+        self.manifest.pending_stash = false;
+        self.manifest.pending_state = false;
+        self.manifest.pending_index = false;
+    }
+
+    fn write_manifest(&self) -> Result<(), SerializeError> {
+        let data = self.manifest.to_strict_serialized::<U8>()?;
+        write_atomic(&self.path.join(MANIFEST_FILENAME), &data, self.sync)?;
+        Ok(())
+    }
+
+    /// Publishes all staged files, moving them into their places. Does nothing
+    /// if there are no staged files.
+    pub fn publish(&mut self) -> Result<(), SerializeError> {
+        let staged = |name: &str| staging_path(&self.path.join(name)).exists();
+        let (stash, state, index) =
+            (staged(STASH_FILENAME), staged(STATE_FILENAME), staged(INDEX_FILENAME));
+        if !stash && !state && !index {
+            return Ok(());
+        }
+
+        self.manifest.generation += 1;
+        self.manifest.pending_stash = stash;
+        self.manifest.pending_state = state;
+        self.manifest.pending_index = index;
+        self.write_manifest()?;
+
+        for (name, pending) in self.pending() {
+            if pending {
+                let part = self.path.join(name);
+                rename(&staging_path(&part), &part, self.sync)?;
+            }
+        }
+
+        self.clear_pending();
+        self.write_manifest()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rgb-std-fs-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn publish() {
+        let path = temp_dir("publish");
+        let mut dir = StockDir::new(path.clone(), SyncPolicy::Full);
+        let config = FsConfig {
+            sync: SyncPolicy::Full,
+            staged: true,
+        };
+        store_file(&dir.stash_filename(), b"stash", config).unwrap();
+        store_file(&dir.index_filename(), b"index", config).unwrap();
+        assert!(!dir.stash_filename().exists());
+
+        dir.publish().unwrap();
+        assert_eq!(dir.generation(), 1);
+        assert_eq!(fs::read(dir.stash_filename()).unwrap(), b"stash");
+        assert_eq!(fs::read(dir.index_filename()).unwrap(), b"index");
+        assert!(!staging_path(&dir.stash_filename()).exists());
+        assert!(!dir.state_filename().exists());
+
+        dir.publish().unwrap();
+        assert_eq!(dir.generation(), 1);
+        assert_eq!(StockDir::open(path, SyncPolicy::Never).unwrap().generation(), 1);
+    }
+
+    #[test]
+    fn recover_committed() {
+        let path = temp_dir("recover_committed");
+        let dir = StockDir::new(path.clone(), SyncPolicy::Data);
+        write_atomic(&dir.state_filename(), b"old", SyncPolicy::Data).unwrap();
+        write_atomic(&staging_path(&dir.state_filename()), b"new", SyncPolicy::Data).unwrap();
+        // Crash right after the commit point
+        let manifest = Manifest {
+            generation: 5,
+            pending_state: true,
+            ..default!()
+        };
+        let data = manifest.to_strict_serialized::<U8>().unwrap();
+        write_atomic(&path.join(MANIFEST_FILENAME), &data, SyncPolicy::Data).unwrap();
+
+        let dir = StockDir::open(path.clone(), SyncPolicy::Data).unwrap();
+        assert_eq!(dir.generation(), 5);
+        assert_eq!(fs::read(dir.state_filename()).unwrap(), b"new");
+        assert!(!staging_path(&dir.state_filename()).exists());
+        let manifest =
+            Manifest::strict_deserialize_from_file::<U8>(path.join(MANIFEST_FILENAME)).unwrap();
+        assert!(!manifest.pending_state);
+    }
+
+    #[test]
+    fn discard_uncommitted() {
+        let path = temp_dir("discard_uncommitted");
+        let mut dir = StockDir::new(path.clone(), SyncPolicy::Never);
+        let config = FsConfig {
+            sync: SyncPolicy::Never,
+            staged: true,
+        };
+        store_file(&dir.stash_filename(), b"old", config).unwrap();
+        dir.publish().unwrap();
+        // Crash before the commit point
+        store_file(&dir.stash_filename(), b"new", config).unwrap();
+
+        let dir = StockDir::open(path, SyncPolicy::Never).unwrap();
+        assert_eq!(dir.generation(), 1);
+        assert_eq!(fs::read(dir.stash_filename()).unwrap(), b"old");
+        assert!(!staging_path(&dir.stash_filename()).exists());
+    }
+
+    #[test]
+    fn corrupted_manifest() {
+        let path = temp_dir("corrupted_manifest");
+        fs::write(path.join(MANIFEST_FILENAME), b"garbage").unwrap();
+        assert!(StockDir::open(path, SyncPolicy::Never).is_err());
+    }
+}
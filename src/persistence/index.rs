@@ -33,7 +33,7 @@ use strict_encoding::SerializeError;
 
 use crate::containers::{BundledWitness, ConsignmentExt, ToWitnessId};
 use crate::persistence::StoreTransaction;
-use crate::SecretSeal;
+use crate::{SecretSeal, LIB_NAME_RGB_STORAGE};
 
 #[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
 #[display(inner)]
@@ -124,6 +124,22 @@ pub enum IndexInconsistency {
     BundleWitnessUnknown(BundleId),
 }
 
+/// State transition spending an output of another operation, anchored to a
+/// specific witness.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[display("{opid}@{witness_id}")]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB_STORAGE)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct Spender {
+    pub opid: OpId,
+    pub witness_id: XWitnessId,
+}
+
 #[derive(Clone, Debug)]
 pub struct Index<P: IndexProvider> {
     provider: P,
@@ -149,6 +165,12 @@ impl<P: IndexProvider> Index<P> {
     #[cfg(feature = "fs")]
     pub(super) fn as_provider_mut(&mut self) -> &mut P { &mut self.provider }
 
+    pub(super) fn serialized_size(&self) -> Result<usize, IndexError<P>> {
+        self.provider
+            .serialized_size()
+            .map_err(IndexError::ReadProvider)
+    }
+
     pub(super) fn index_consignment(
         &mut self,
         consignment: impl ConsignmentExt,
@@ -176,6 +198,14 @@ impl<P: IndexProvider> Index<P> {
         Ok(())
     }
 
+    pub(super) fn index_contract(&mut self, genesis: &Genesis) -> Result<(), IndexError<P>> {
+        let contract_id = genesis.contract_id();
+        self.provider
+            .register_contract(contract_id)
+            .map_err(IndexError::WriteProvider)?;
+        self.index_genesis(contract_id, genesis)
+    }
+
     fn index_genesis(&mut self, id: ContractId, genesis: &Genesis) -> Result<(), IndexError<P>> {
         let opid = genesis.id();
         for (type_id, assign) in genesis.assignments.iter() {
@@ -201,7 +231,7 @@ impl<P: IndexProvider> Index<P> {
         Ok(())
     }
 
-    fn index_extension(
+    pub(super) fn index_extension(
         &mut self,
         id: ContractId,
         extension: &Extension,
@@ -243,6 +273,13 @@ impl<P: IndexProvider> Index<P> {
 
         for (opid, transition) in &bundle.known_transitions {
             self.provider.register_operation(*opid, bundle_id)?;
+            for input in &transition.inputs {
+                let spender = Spender {
+                    opid: *opid,
+                    witness_id,
+                };
+                self.provider.register_spender(input.prev_out, spender)?;
+            }
             for (type_id, assign) in transition.assignments.iter() {
                 match assign {
                     TypedAssigns::Declarative(vec) => {
@@ -331,6 +368,39 @@ impl<P: IndexProvider> Index<P> {
     ) -> Result<(impl Iterator<Item = XWitnessId> + '_, ContractId), IndexError<P>> {
         Ok(self.provider.bundle_info(bundle_id)?)
     }
+
+    pub(super) fn spenders(
+        &self,
+        opout: Opout,
+    ) -> Result<impl Iterator<Item = Spender> + '_, IndexError<P>> {
+        self.provider
+            .spenders(opout)
+            .map_err(IndexError::ReadProvider)
+    }
+
+    pub(super) fn contract_ids(
+        &self,
+    ) -> Result<impl Iterator<Item = ContractId> + '_, IndexError<P>> {
+        self.provider
+            .contract_ids()
+            .map_err(IndexError::ReadProvider)
+    }
+
+    pub(super) fn bundle_ids(&self) -> Result<impl Iterator<Item = BundleId> + '_, IndexError<P>> {
+        self.provider
+            .bundle_ids()
+            .map_err(IndexError::ReadProvider)
+    }
+
+    pub(super) fn remove_contract(
+        &mut self,
+        contract_id: ContractId,
+        opids: &BTreeSet<OpId>,
+    ) -> Result<bool, IndexError<P>> {
+        self.provider
+            .remove_contract(contract_id, opids)
+            .map_err(IndexError::WriteProvider)
+    }
 }
 
 impl<P: IndexProvider> StoreTransaction for Index<P> {
@@ -379,10 +449,22 @@ pub trait IndexReadProvider {
 
     fn bundle_id_for_op(&self, opid: OpId) -> Result<BundleId, IndexReadError<Self::Error>>;
 
+    /// Lists known transitions spending the output, together with their
+    /// witnesses.
+    fn spenders(&self, opout: Opout) -> Result<impl Iterator<Item = Spender> + '_, Self::Error>;
+
     fn bundle_info(
         &self,
         bundle_id: BundleId,
     ) -> Result<(impl Iterator<Item = XWitnessId>, ContractId), IndexReadError<Self::Error>>;
+
+    fn contract_ids(&self) -> Result<impl Iterator<Item = ContractId> + '_, Self::Error>;
+
+    fn bundle_ids(&self) -> Result<impl Iterator<Item = BundleId> + '_, Self::Error>;
+
+    /// Size of the provider data in their strict-serialized form. Providers
+    /// which can't estimate the size of their data report zero.
+    fn serialized_size(&self) -> Result<usize, Self::Error> { Ok(0) }
 }
 
 pub trait IndexWriteProvider: StoreTransaction<TransactionErr = Self::Error> {
@@ -403,6 +485,15 @@ pub trait IndexWriteProvider: StoreTransaction<TransactionErr = Self::Error> {
         bundle_id: BundleId,
     ) -> Result<bool, IndexWriteError<Self::Error>>;
 
+    /// Records that the output `opout` is spent by the `spender` transition.
+    ///
+    /// Returns whether the spender was not known before.
+    fn register_spender(
+        &mut self,
+        opout: Opout,
+        spender: Spender,
+    ) -> Result<bool, IndexWriteError<Self::Error>>;
+
     fn index_genesis_assignments<State: ExposedState>(
         &mut self,
         contract_id: ContractId,
@@ -419,4 +510,15 @@ pub trait IndexWriteProvider: StoreTransaction<TransactionErr = Self::Error> {
         type_id: AssignmentType,
         witness_id: XWitnessId,
     ) -> Result<(), IndexWriteError<Self::Error>>;
+
+    /// Removes all index entries of the contract: its bundles, the provided
+    /// operations `opids`, the terminals defined by them and the spenders of
+    /// their outputs.
+    ///
+    /// Returns whether the contract was known to the index.
+    fn remove_contract(
+        &mut self,
+        contract_id: ContractId,
+        opids: &BTreeSet<OpId>,
+    ) -> Result<bool, Self::Error>;
 }
@@ -27,12 +27,13 @@ use std::fmt::{Debug, Formatter};
 use std::num::NonZeroU32;
 #[cfg(feature = "fs")]
 use std::path::PathBuf;
+use std::sync::OnceLock;
 use std::{iter, mem};
 
 use aluvm::library::{Lib, LibId};
 use amplify::confinement::{
     self, Confined, LargeOrdMap, LargeOrdSet, MediumBlob, MediumOrdMap, MediumOrdSet, SmallOrdMap,
-    TinyOrdMap, TinyOrdSet,
+    SmallOrdSet, TinyOrdMap, TinyOrdSet, TinyVec,
 };
 use amplify::num::u24;
 use bp::dbc::tapret::TapretCommitment;
@@ -45,33 +46,82 @@ use rgb::vm::{
 use rgb::{
     Assign, AssignmentType, Assignments, AssignmentsRef, AttachId, AttachState, BundleId,
     ContractId, DataState, ExposedSeal, ExposedState, Extension, FungibleState, Genesis,
-    GenesisSeal, GlobalStateType, GraphSeal, Identity, OpId, Operation, Opout, RevealedAttach,
-    RevealedData, RevealedValue, Schema, SchemaId, SecretSeal, Transition, TransitionBundle,
-    TypedAssigns, VoidState, XChain, XOutpoint, XOutputSeal, XWitnessId,
+    GenesisSeal, GlobalStateType, GraphSeal, Identity, Layer1, OpId, Operation, Opout,
+    RevealedAttach, RevealedData, RevealedValue, Schema, SchemaId, SecretSeal, Transition,
+    TransitionBundle, TypedAssigns, VoidState, XChain, XOutpoint, XOutputSeal, XWitnessId,
 };
-use strict_encoding::{SerializeError, StrictDeserialize, StrictSerialize};
+use strict_encoding::{SerializeError, StrictDeserialize, StrictSerialize, TypeName};
 use strict_types::TypeSystem;
 
 use super::{
-    ContractIfaceError, ContractStateRead, ContractStateWrite, IndexInconsistency, IndexProvider,
-    IndexReadError, IndexReadProvider, IndexWriteError, IndexWriteProvider, SchemaIfaces,
-    StashInconsistency, StashProvider, StashProviderError, StashReadProvider, StashWriteProvider,
-    StateInconsistency, StateProvider, StateReadProvider, StateWriteProvider, StoreTransaction,
-    UpdateRes,
+    serialized_size, ContractIfaceError, ContractStateRead, ContractStateWrite,
+    IndexInconsistency, IndexProvider, IndexReadError, IndexReadProvider, IndexWriteError,
+    IndexWriteProvider, SchemaIfaces, Spender, StashInconsistency, StashProvider,
+    StashProviderError, StashReadProvider, StashWriteProvider, StateInconsistency, StateProvider,
+    StateReadProvider, StateWriteProvider, StoreTransaction, UpdateRes,
 };
 use crate::containers::{
     AnchorSet, ContentId, ContentRef, ContentSigs, SealWitness, SigBlob, Supplement, TrustLevel,
 };
 use crate::contract::{GlobalOut, KnownState, OpWitness, OutputAssignment};
-use crate::interface::{Iface, IfaceClass, IfaceId, IfaceImpl, IfaceRef};
+use crate::interface::{Iface, IfaceClass, IfaceId, IfaceImpl, IfaceRef, ImplId};
 #[cfg(feature = "fs")]
-use crate::persistence::fs::FsStored;
+use crate::persistence::fs::{FsConfig, FsStored};
 use crate::LIB_NAME_RGB_STORAGE;
 
+/// Journal of modifications made to a map within a transaction, keeping the
+/// values which the keys had before their first modification.
+#[derive(Clone, Debug)]
+struct MapJournal<K: Ord, V>(BTreeMap<K, Option<V>>);
+
+impl<K: Ord, V> Default for MapJournal<K, V> {
+    fn default() -> Self { Self(BTreeMap::new()) }
+}
+
+impl<K: Ord + Clone, V: Clone> MapJournal<K, V> {
+    fn save(&mut self, key: &K, prev: Option<&V>) {
+        self.0.entry(key.clone()).or_insert_with(|| prev.cloned());
+    }
+
+    fn restore<const MIN: usize, const MAX: usize>(
+        self,
+        map: &mut Confined<BTreeMap<K, V>, MIN, MAX>,
+    ) {
+        for (key, prev) in self.0 {
+            match prev {
+                Some(value) => map.insert(key, value).map(|_| ()),
+                None => map.remove(&key).map(|_| ()),
+            }
+            .expect("restoring data from before the transaction can't exceed collection limits");
+        }
+    }
+}
+
 //////////
 // STASH
 //////////
 
+#[derive(Clone, Debug, Default)]
+struct StashJournal {
+    schemata: MapJournal<SchemaId, SchemaIfaces>,
+    ifaces: MapJournal<IfaceId, Iface>,
+    geneses: MapJournal<ContractId, Genesis>,
+    suppl: MapJournal<ContentRef, TinyOrdSet<Supplement>>,
+    bundles: MapJournal<BundleId, TransitionBundle>,
+    extensions: MapJournal<OpId, Extension>,
+    witnesses: MapJournal<XWitnessId, SealWitness>,
+    attachments: MapJournal<AttachId, MediumBlob>,
+    secret_seals: BTreeSet<XChain<GraphSeal>>,
+    purged_seals: BTreeSet<XChain<GraphSeal>>,
+    type_system: Option<TypeSystem>,
+    identities: MapJournal<Identity, TrustLevel>,
+    libs: MapJournal<LibId, Lib>,
+    sigs: MapJournal<ContentId, ContentSigs>,
+    archived: Option<SmallOrdSet<ContractId>>,
+    superseded_iimpls: MapJournal<ImplId, IfaceImpl>,
+    bindings: MapJournal<ContractId, TinyOrdMap<TypeName, TinyVec<ImplId>>>,
+}
+
 /// Hoard is an in-memory stash useful for WASM implementations.
 #[derive(Getters, Clone, Debug)]
 #[getter(prefix = "debug_")]
@@ -80,9 +130,25 @@ use crate::LIB_NAME_RGB_STORAGE;
 pub struct MemStash {
     #[strict_type(skip)]
     dirty: bool,
+    #[strict_type(skip)]
+    #[getter(skip)]
+    journal: Option<StashJournal>,
     #[cfg(feature = "fs")]
     #[strict_type(skip)]
     filename: Option<PathBuf>,
+    #[cfg(feature = "fs")]
+    #[strict_type(skip)]
+    #[getter(skip)]
+    fs_config: FsConfig,
+    // Archival status, superseded interface implementations and contract
+    // bindings to them are not a part of the standard RGBStorage type; the
+    // file-based storage keeps them in its versioned layout
+    #[strict_type(skip)]
+    archived: SmallOrdSet<ContractId>,
+    #[strict_type(skip)]
+    superseded_iimpls: SmallOrdMap<ImplId, IfaceImpl>,
+    #[strict_type(skip)]
+    bindings: SmallOrdMap<ContractId, TinyOrdMap<TypeName, TinyVec<ImplId>>>,
 
     schemata: TinyOrdMap<SchemaId, SchemaIfaces>,
     ifaces: TinyOrdMap<IfaceId, Iface>,
@@ -106,8 +172,14 @@ impl MemStash {
     pub fn in_memory() -> Self {
         Self {
             dirty: false,
+            journal: None,
             #[cfg(feature = "fs")]
             filename: None,
+            #[cfg(feature = "fs")]
+            fs_config: default!(),
+            archived: empty!(),
+            superseded_iimpls: empty!(),
+            bindings: empty!(),
             schemata: empty!(),
             ifaces: empty!(),
             geneses: empty!(),
@@ -123,6 +195,28 @@ impl MemStash {
             sigs: empty!(),
         }
     }
+
+    fn push_binding(
+        &mut self,
+        contract_id: ContractId,
+        iface_name: TypeName,
+        impl_id: ImplId,
+    ) -> Result<bool, SerializeError> {
+        let bindings = self.bindings.get(&contract_id);
+        let impl_ids = bindings.and_then(|bindings| bindings.get(&iface_name));
+        if impl_ids.and_then(|impl_ids| impl_ids.last()) == Some(&impl_id) {
+            return Ok(false);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.bindings.save(&contract_id, bindings);
+        }
+        let mut bindings = self.bindings.remove(&contract_id)?.unwrap_or_default();
+        let mut impl_ids = bindings.remove(&iface_name)?.unwrap_or_default();
+        impl_ids.push(impl_id)?;
+        bindings.insert(iface_name, impl_ids)?;
+        self.bindings.insert(contract_id, bindings)?;
+        Ok(true)
+    }
 }
 
 impl StoreTransaction for MemStash {
@@ -130,10 +224,14 @@ impl StoreTransaction for MemStash {
 
     fn begin_transaction(&mut self) -> Result<(), Self::TransactionErr> {
         self.dirty = true;
+        if self.journal.is_none() {
+            self.journal = Some(default!());
+        }
         Ok(())
     }
 
     fn commit_transaction(&mut self) -> Result<(), Self::TransactionErr> {
+        self.journal = None;
         #[cfg(feature = "fs")]
         if self.dirty {
             self.store()?;
@@ -141,7 +239,40 @@ impl StoreTransaction for MemStash {
         Ok(())
     }
 
-    fn rollback_transaction(&mut self) { unreachable!() }
+    fn rollback_transaction(&mut self) {
+        let Some(journal) = self.journal.take() else {
+            return;
+        };
+        journal.schemata.restore(&mut self.schemata);
+        journal.ifaces.restore(&mut self.ifaces);
+        journal.geneses.restore(&mut self.geneses);
+        journal.suppl.restore(&mut self.suppl);
+        journal.bundles.restore(&mut self.bundles);
+        journal.extensions.restore(&mut self.extensions);
+        journal.witnesses.restore(&mut self.witnesses);
+        journal.attachments.restore(&mut self.attachments);
+        for seal in journal.secret_seals {
+            self.secret_seals
+                .remove(&seal)
+                .expect("collection allows zero elements");
+        }
+        for seal in journal.purged_seals {
+            self.secret_seals
+                .push(seal)
+                .expect("restoring seals from before the transaction can't exceed limits");
+        }
+        if let Some(archived) = journal.archived {
+            self.archived = archived;
+        }
+        journal.superseded_iimpls.restore(&mut self.superseded_iimpls);
+        journal.bindings.restore(&mut self.bindings);
+        if let Some(type_system) = journal.type_system {
+            self.type_system = type_system;
+        }
+        journal.identities.restore(&mut self.identities);
+        journal.libs.restore(&mut self.libs);
+        journal.sigs.restore(&mut self.sigs);
+    }
 }
 
 impl StashProvider for MemStash {}
@@ -158,6 +289,8 @@ impl StashReadProvider for MemStash {
             .ok_or_else(|| StashInconsistency::LibAbsent(id).into())
     }
 
+    fn libs(&self) -> Result<impl Iterator<Item = &Lib>, Self::Error> { Ok(self.libs.values()) }
+
     fn ifaces(&self) -> Result<impl Iterator<Item = &Iface>, Self::Error> {
         Ok(self.ifaces.values())
     }
@@ -219,6 +352,10 @@ impl StashReadProvider for MemStash {
         Ok(self.identities.get(identity).copied().unwrap_or_default())
     }
 
+    fn identities(&self) -> Result<impl Iterator<Item = (&Identity, TrustLevel)>, Self::Error> {
+        Ok(self.identities.iter().map(|(id, trust)| (id, *trust)))
+    }
+
     fn supplement(&self, content_ref: ContentRef) -> Result<Option<&Supplement>, Self::Error> {
         Ok(self.suppl.get(&content_ref).and_then(|s| s.first()))
     }
@@ -296,6 +433,10 @@ impl StashReadProvider for MemStash {
             .ok_or(StashInconsistency::WitnessAbsent(witness_id).into())
     }
 
+    fn attachments(&self) -> Result<impl Iterator<Item = (AttachId, &MediumBlob)>, Self::Error> {
+        Ok(self.attachments.iter().map(|(id, attach)| (*id, attach)))
+    }
+
     fn taprets(&self) -> Result<impl Iterator<Item = (XWitnessId, TapretCommitment)>, Self::Error> {
         Ok(self
             .witnesses
@@ -327,6 +468,55 @@ impl StashReadProvider for MemStash {
     fn secret_seals(&self) -> Result<impl Iterator<Item = XChain<GraphSeal>>, Self::Error> {
         Ok(self.secret_seals.iter().copied())
     }
+
+    fn archived_contracts(&self) -> Result<impl Iterator<Item = ContractId>, Self::Error> {
+        Ok(self.archived.iter().copied())
+    }
+
+    fn iimpls(&self, schema_id: SchemaId) -> Result<impl Iterator<Item = &IfaceImpl>, Self::Error> {
+        let current = self
+            .schemata
+            .get(&schema_id)
+            .into_iter()
+            .flat_map(|schema_ifaces| schema_ifaces.iimpls.values());
+        let superseded = self
+            .superseded_iimpls
+            .values()
+            .filter(move |iimpl| iimpl.schema_id == schema_id);
+        Ok(current.chain(superseded))
+    }
+
+    fn iimpl(&self, impl_id: ImplId) -> Result<&IfaceImpl, StashProviderError<Self::Error>> {
+        self.superseded_iimpls
+            .get(&impl_id)
+            .or_else(|| {
+                self.schemata
+                    .values()
+                    .flat_map(|schema_ifaces| schema_ifaces.iimpls.values())
+                    .find(|iimpl| iimpl.impl_id() == impl_id)
+            })
+            .ok_or_else(|| StashInconsistency::ImplAbsent(impl_id).into())
+    }
+
+    fn iimpl_bindings(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<impl Iterator<Item = (&TypeName, ImplId)>, Self::Error> {
+        Ok(self
+            .bindings
+            .get(&contract_id)
+            .into_iter()
+            .flatten()
+            .flat_map(|(name, impl_ids)| impl_ids.iter().map(move |impl_id| (name, *impl_id))))
+    }
+
+    fn serialized_size(&self) -> Result<usize, Self::Error> {
+        // Data which are not a part of the RGBStorage type are persisted too
+        Ok(serialized_size(self) +
+            serialized_size(&self.archived) +
+            serialized_size(&self.superseded_iimpls) +
+            serialized_size(&self.bindings))
+    }
 }
 
 impl StashWriteProvider for MemStash {
@@ -335,6 +525,9 @@ impl StashWriteProvider for MemStash {
     fn replace_schema(&mut self, schema: Schema) -> Result<bool, Self::Error> {
         let schema_id = schema.schema_id();
         if !self.schemata.contains_key(&schema_id) {
+            if let Some(journal) = &mut self.journal {
+                journal.schemata.save(&schema_id, None);
+            }
             self.schemata.insert(schema_id, SchemaIfaces::new(schema))?;
             return Ok(true);
         }
@@ -344,6 +537,9 @@ impl StashWriteProvider for MemStash {
     fn replace_iface(&mut self, iface: Iface) -> Result<bool, Self::Error> {
         let iface_id = iface.iface_id();
         if !self.ifaces.contains_key(&iface_id) {
+            if let Some(journal) = &mut self.journal {
+                journal.ifaces.save(&iface_id, None);
+            }
             self.ifaces.insert(iface_id, iface)?;
             return Ok(true);
         }
@@ -351,15 +547,37 @@ impl StashWriteProvider for MemStash {
     }
 
     fn replace_iimpl(&mut self, iimpl: IfaceImpl) -> Result<bool, Self::Error> {
+        let impl_id = iimpl.impl_id();
+        if self.superseded_iimpls.contains_key(&impl_id) {
+            return Ok(false);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal
+                .schemata
+                .save(&iimpl.schema_id, self.schemata.get(&iimpl.schema_id));
+        }
         let schema_ifaces = self
             .schemata
             .get_mut(&iimpl.schema_id)
             .expect("unknown schema");
         let iface = self.ifaces.get(&iimpl.iface_id).expect("unknown interface");
         let iface_name = iface.name.clone();
-        let present = schema_ifaces.iimpls.contains_key(&iface_name);
-        schema_ifaces.iimpls.insert(iface_name, iimpl)?;
-        Ok(!present)
+        let superseded = match schema_ifaces.iimpls.get(&iface_name) {
+            Some(current) if current.impl_id() == impl_id => return Ok(false),
+            Some(current) if !iimpl.supersedes(current) => iimpl,
+            _ => match schema_ifaces.iimpls.insert(iface_name, iimpl)? {
+                Some(prev) => prev,
+                None => return Ok(true),
+            },
+        };
+        let superseded_id = superseded.impl_id();
+        if let Some(journal) = &mut self.journal {
+            journal
+                .superseded_iimpls
+                .save(&superseded_id, self.superseded_iimpls.get(&superseded_id));
+        }
+        self.superseded_iimpls.insert(superseded_id, superseded)?;
+        Ok(true)
     }
 
     fn set_trust(
@@ -367,11 +585,21 @@ impl StashWriteProvider for MemStash {
         identity: Identity,
         trust: TrustLevel,
     ) -> Result<(), confinement::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .identities
+                .save(&identity, self.identities.get(&identity));
+        }
         self.identities.insert(identity, trust)?;
         Ok(())
     }
 
     fn add_supplement(&mut self, suppl: Supplement) -> Result<(), Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .suppl
+                .save(&suppl.content_id, self.suppl.get(&suppl.content_id));
+        }
         match self.suppl.get_mut(&suppl.content_id) {
             None => {
                 self.suppl.insert(suppl.content_id, confined_bset![suppl])?;
@@ -383,24 +611,40 @@ impl StashWriteProvider for MemStash {
 
     fn replace_genesis(&mut self, genesis: Genesis) -> Result<bool, Self::Error> {
         let contract_id = genesis.contract_id();
+        if let Some(journal) = &mut self.journal {
+            journal
+                .geneses
+                .save(&contract_id, self.geneses.get(&contract_id));
+        }
         let present = self.geneses.insert(contract_id, genesis)?.is_some();
         Ok(!present)
     }
 
     fn replace_extension(&mut self, extension: Extension) -> Result<bool, Self::Error> {
         let opid = extension.id();
+        if let Some(journal) = &mut self.journal {
+            journal.extensions.save(&opid, self.extensions.get(&opid));
+        }
         let present = self.extensions.insert(opid, extension)?.is_some();
         Ok(!present)
     }
 
     fn replace_bundle(&mut self, bundle: TransitionBundle) -> Result<bool, Self::Error> {
         let bundle_id = bundle.bundle_id();
+        if let Some(journal) = &mut self.journal {
+            journal.bundles.save(&bundle_id, self.bundles.get(&bundle_id));
+        }
         let present = self.bundles.insert(bundle_id, bundle)?.is_some();
         Ok(!present)
     }
 
     fn replace_witness(&mut self, witness: SealWitness) -> Result<bool, Self::Error> {
         let witness_id = witness.witness_id();
+        if let Some(journal) = &mut self.journal {
+            journal
+                .witnesses
+                .save(&witness_id, self.witnesses.get(&witness_id));
+        }
         let present = self.witnesses.insert(witness_id, witness)?.is_some();
         Ok(!present)
     }
@@ -410,26 +654,46 @@ impl StashWriteProvider for MemStash {
         id: AttachId,
         attach: MediumBlob,
     ) -> Result<bool, Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal.attachments.save(&id, self.attachments.get(&id));
+        }
         let present = self.attachments.insert(id, attach)?.is_some();
         Ok(!present)
     }
 
     fn consume_types(&mut self, types: TypeSystem) -> Result<(), Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .type_system
+                .get_or_insert_with(|| self.type_system.clone());
+        }
         Ok(self.type_system.extend(types)?)
     }
 
     fn replace_lib(&mut self, lib: Lib) -> Result<bool, Self::Error> {
-        let present = self.libs.insert(lib.id(), lib)?.is_some();
+        let lib_id = lib.id();
+        if let Some(journal) = &mut self.journal {
+            journal.libs.save(&lib_id, self.libs.get(&lib_id));
+        }
+        let present = self.libs.insert(lib_id, lib)?.is_some();
         Ok(!present)
     }
 
     fn import_sigs<I>(&mut self, content_id: ContentId, sigs: I) -> Result<(), Self::Error>
     where I: IntoIterator<Item = (Identity, SigBlob)> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .sigs
+                .save(&content_id, self.sigs.get(&content_id));
+        }
         let sigs = sigs.into_iter().filter(|(id, _)| {
             match self.identities.get(id) {
                 Some(level) => *level,
                 None => {
                     let level = TrustLevel::default();
+                    if let Some(journal) = &mut self.journal {
+                        journal.identities.save(id, None);
+                    }
                     // We ignore if the identities are full
                     self.identities.insert(id.clone(), level).ok();
                     level
@@ -449,14 +713,176 @@ impl StashWriteProvider for MemStash {
     fn add_secret_seal(&mut self, seal: XChain<GraphSeal>) -> Result<bool, Self::Error> {
         let present = self.secret_seals.contains(&seal);
         self.secret_seals.push(seal)?;
+        if let Some(journal) = &mut self.journal {
+            if !present && !journal.purged_seals.remove(&seal) {
+                journal.secret_seals.insert(seal);
+            }
+        }
         Ok(!present)
     }
+
+    fn set_archived(
+        &mut self,
+        contract_id: ContractId,
+        archived: bool,
+    ) -> Result<bool, Self::Error> {
+        if self.archived.contains(&contract_id) == archived {
+            return Ok(false);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal
+                .archived
+                .get_or_insert_with(|| self.archived.clone());
+        }
+        if archived {
+            self.archived.push(contract_id)?;
+        } else {
+            self.archived.remove(&contract_id)?;
+        }
+        Ok(true)
+    }
+
+    fn bind_iimpl(
+        &mut self,
+        contract_id: ContractId,
+        impl_id: ImplId,
+    ) -> Result<bool, StashProviderError<Self::Error>> {
+        let iface_id = self
+            .iimpl(impl_id)
+            .map_err(|_| StashInconsistency::ImplAbsent(impl_id))?
+            .iface_id;
+        let iface_name = self
+            .ifaces
+            .get(&iface_id)
+            .ok_or(StashInconsistency::IfaceAbsent(iface_id.into()))?
+            .name
+            .clone();
+        self.push_binding(contract_id, iface_name, impl_id)
+            .map_err(StashProviderError::Connectivity)
+    }
+
+    fn remove_genesis(&mut self, contract_id: ContractId) -> Result<bool, Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .geneses
+                .save(&contract_id, self.geneses.get(&contract_id));
+            journal
+                .bindings
+                .save(&contract_id, self.bindings.get(&contract_id));
+        }
+        self.bindings.remove(&contract_id)?;
+        Ok(self.geneses.remove(&contract_id)?.is_some())
+    }
+
+    fn remove_extension(&mut self, opid: OpId) -> Result<bool, Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal.extensions.save(&opid, self.extensions.get(&opid));
+        }
+        Ok(self.extensions.remove(&opid)?.is_some())
+    }
+
+    fn remove_bundle(&mut self, bundle_id: BundleId) -> Result<bool, Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal.bundles.save(&bundle_id, self.bundles.get(&bundle_id));
+        }
+        Ok(self.bundles.remove(&bundle_id)?.is_some())
+    }
+
+    fn remove_witness(&mut self, witness_id: XWitnessId) -> Result<bool, Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .witnesses
+                .save(&witness_id, self.witnesses.get(&witness_id));
+        }
+        Ok(self.witnesses.remove(&witness_id)?.is_some())
+    }
+
+    fn remove_supplements(&mut self, content_ref: ContentRef) -> Result<bool, Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .suppl
+                .save(&content_ref, self.suppl.get(&content_ref));
+        }
+        Ok(self.suppl.remove(&content_ref)?.is_some())
+    }
+
+    fn remove_secret_seal(&mut self, seal: XChain<GraphSeal>) -> Result<bool, Self::Error> {
+        let present = self.secret_seals.remove(&seal)?;
+        if let Some(journal) = &mut self.journal {
+            if present && !journal.secret_seals.remove(&seal) {
+                journal.purged_seals.insert(seal);
+            }
+        }
+        Ok(present)
+    }
+
+    fn remove_schema(&mut self, schema_id: SchemaId) -> Result<bool, Self::Error> {
+        let superseded = self
+            .superseded_iimpls
+            .iter()
+            .filter(|(_, iimpl)| iimpl.schema_id == schema_id)
+            .map(|(impl_id, _)| *impl_id)
+            .collect::<Vec<_>>();
+        if let Some(journal) = &mut self.journal {
+            journal
+                .schemata
+                .save(&schema_id, self.schemata.get(&schema_id));
+            for impl_id in &superseded {
+                journal
+                    .superseded_iimpls
+                    .save(impl_id, self.superseded_iimpls.get(impl_id));
+            }
+        }
+        for impl_id in superseded {
+            self.superseded_iimpls.remove(&impl_id)?;
+        }
+        Ok(self.schemata.remove(&schema_id)?.is_some())
+    }
+
+    fn remove_iface(&mut self, iface_id: IfaceId) -> Result<bool, Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal.ifaces.save(&iface_id, self.ifaces.get(&iface_id));
+        }
+        Ok(self.ifaces.remove(&iface_id)?.is_some())
+    }
+
+    fn remove_lib(&mut self, lib_id: LibId) -> Result<bool, Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal.libs.save(&lib_id, self.libs.get(&lib_id));
+        }
+        Ok(self.libs.remove(&lib_id)?.is_some())
+    }
+
+    fn remove_sigs(&mut self, content_id: ContentId) -> Result<bool, Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .sigs
+                .save(&content_id, self.sigs.get(&content_id));
+        }
+        Ok(self.sigs.remove(&content_id)?.is_some())
+    }
+
+    fn replace_types(&mut self, types: TypeSystem) -> Result<(), Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .type_system
+                .get_or_insert_with(|| self.type_system.clone());
+        }
+        self.type_system = types;
+        Ok(())
+    }
 }
 
 //////////
 // STATE
 //////////
 
+#[derive(Clone, Debug, Default)]
+struct StateJournal {
+    witnesses: MapJournal<XWitnessId, WitnessOrd>,
+    contracts: MapJournal<ContractId, MemContractState>,
+}
+
 #[derive(Getters, Clone, Debug)]
 #[getter(prefix = "debug_")]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
@@ -464,9 +890,16 @@ impl StashWriteProvider for MemStash {
 pub struct MemState {
     #[strict_type(skip)]
     dirty: bool,
+    #[strict_type(skip)]
+    #[getter(skip)]
+    journal: Option<StateJournal>,
     #[cfg(feature = "fs")]
     #[strict_type(skip)]
     filename: Option<PathBuf>,
+    #[cfg(feature = "fs")]
+    #[strict_type(skip)]
+    #[getter(skip)]
+    fs_config: FsConfig,
 
     witnesses: LargeOrdMap<XWitnessId, WitnessOrd>,
     contracts: TinyOrdMap<ContractId, MemContractState>,
@@ -479,8 +912,11 @@ impl MemState {
     pub fn in_memory() -> Self {
         Self {
             dirty: false,
+            journal: None,
             #[cfg(feature = "fs")]
             filename: None,
+            #[cfg(feature = "fs")]
+            fs_config: default!(),
             witnesses: empty!(),
             contracts: empty!(),
         }
@@ -492,10 +928,14 @@ impl StoreTransaction for MemState {
 
     fn begin_transaction(&mut self) -> Result<(), Self::TransactionErr> {
         self.dirty = true;
+        if self.journal.is_none() {
+            self.journal = Some(default!());
+        }
         Ok(())
     }
 
     fn commit_transaction(&mut self) -> Result<(), Self::TransactionErr> {
+        self.journal = None;
         #[cfg(feature = "fs")]
         if self.dirty {
             self.store()?;
@@ -503,7 +943,13 @@ impl StoreTransaction for MemState {
         Ok(())
     }
 
-    fn rollback_transaction(&mut self) { unreachable!() }
+    fn rollback_transaction(&mut self) {
+        let Some(journal) = self.journal.take() else {
+            return;
+        };
+        journal.witnesses.restore(&mut self.witnesses);
+        journal.contracts.restore(&mut self.contracts);
+    }
 }
 
 impl StateProvider for MemState {}
@@ -547,6 +993,19 @@ impl StateReadProvider for MemState {
             .ok_or(StateInconsistency::AbsentValidWitness)?;
         Ok(ord.is_valid())
     }
+
+    fn witness_ord(&self, witness_id: XWitnessId) -> Result<WitnessOrd, Self::Error> {
+        self.witnesses
+            .get(&witness_id)
+            .copied()
+            .ok_or(StateInconsistency::AbsentValidWitness)
+    }
+
+    fn contract_ids(&self) -> Result<impl Iterator<Item = ContractId> + '_, Self::Error> {
+        Ok(self.contracts.keys().copied())
+    }
+
+    fn serialized_size(&self) -> Result<usize, Self::Error> { Ok(serialized_size(self)) }
 }
 
 impl StateWriteProvider for MemState {
@@ -558,8 +1017,12 @@ impl StateWriteProvider for MemState {
         schema: &Schema,
         genesis: &Genesis,
     ) -> Result<Self::ContractWrite<'_>, Self::Error> {
-        // TODO: Add begin/commit transaction
         let contract_id = genesis.contract_id();
+        if let Some(journal) = &mut self.journal {
+            journal
+                .contracts
+                .save(&contract_id, self.contracts.get(&contract_id));
+        }
         // This crazy construction is caused by a stupidity of rust borrow checker
         let contract = if self.contracts.contains_key(&contract_id) {
             if let Some(contract) = self.contracts.get_mut(&contract_id) {
@@ -577,6 +1040,11 @@ impl StateWriteProvider for MemState {
                 |witness_id: XWitnessId, ord: WitnessOrd| -> Result<(), SerializeError> {
                     // NB: We do not check the existence of the witness since we have a newer
                     // version anyway and even if it is known we have to replace it
+                    if let Some(journal) = &mut self.journal {
+                        journal
+                            .witnesses
+                            .save(&witness_id, self.witnesses.get(&witness_id));
+                    }
                     self.witnesses.insert(witness_id, ord)?;
                     Ok(())
                 },
@@ -591,7 +1059,11 @@ impl StateWriteProvider for MemState {
         &mut self,
         contract_id: ContractId,
     ) -> Result<Option<Self::ContractWrite<'_>>, Self::Error> {
-        // TODO: Add begin/commit transaction
+        if let Some(journal) = &mut self.journal {
+            journal
+                .contracts
+                .save(&contract_id, self.contracts.get(&contract_id));
+        }
         Ok(self
             .contracts
             .get_mut(&contract_id)
@@ -603,6 +1075,11 @@ impl StateWriteProvider for MemState {
                         // NB: We do not check the existence of the witness since we have a newer
                         // version anyway and even if it is known we have to replace
                         // it
+                        if let Some(journal) = &mut self.journal {
+                            journal
+                                .witnesses
+                                .save(&witness_id, self.witnesses.get(&witness_id));
+                        }
                         self.witnesses.insert(witness_id, ord)?;
                         Ok(())
                     },
@@ -615,41 +1092,126 @@ impl StateWriteProvider for MemState {
         &mut self,
         resolver: impl ResolveWitness,
         after_height: u32,
+        layer1: Option<Layer1>,
     ) -> Result<UpdateRes, Self::Error> {
         let after_height = NonZeroU32::new(after_height).unwrap_or(NonZeroU32::MIN);
         let mut succeeded = 0;
         let mut failed = map![];
-        self.begin_transaction()?;
-        let mut witnesses = LargeOrdMap::new();
-        mem::swap(&mut self.witnesses, &mut witnesses);
-        let mut witnesses = witnesses.unbox();
-        for (id, ord) in &mut witnesses {
+        let mut updated = map![];
+        // The update may be a part of a larger transaction, which must not be
+        // committed here
+        let nested = self.journal.is_some();
+        if !nested {
+            self.begin_transaction()?;
+        }
+        for (id, ord) in self.witnesses.keyed_values_mut() {
             if matches!(ord, WitnessOrd::Mined(pos) if pos.height() < after_height) {
                 continue;
             }
+            if layer1.is_some_and(|layer1| id.layer1() != layer1) {
+                continue;
+            }
             match resolver.resolve_pub_witness_ord(*id) {
-                Ok(new) => *ord = new,
+                Ok(new) => {
+                    if new != *ord {
+                        updated.insert(*id, (*ord, new));
+                    }
+                    if let Some(journal) = &mut self.journal {
+                        journal.witnesses.save(id, Some(&*ord));
+                    }
+                    *ord = new
+                }
                 Err(err) => {
                     failed.insert(*id, err.to_string());
                 }
             }
             succeeded += 1;
         }
-        let mut witnesses =
-            LargeOrdMap::try_from(witnesses).inspect_err(|_| self.rollback_transaction())?;
-        mem::swap(&mut self.witnesses, &mut witnesses);
-        self.commit_transaction()?;
-        Ok(UpdateRes { succeeded, failed })
+        if !nested {
+            self.commit_transaction()?;
+        }
+        Ok(UpdateRes {
+            succeeded,
+            failed,
+            updated,
+            changes: vec![],
+        })
+    }
+
+    fn prune(
+        &mut self,
+        contract_id: ContractId,
+        spends: impl IntoIterator<Item = (Opout, XWitnessId)>,
+        max_height: u32,
+    ) -> Result<usize, Self::Error> {
+        let spent = spends
+            .into_iter()
+            .filter(|(_, witness_id)| {
+                matches!(
+                    self.witnesses.get(witness_id),
+                    Some(WitnessOrd::Mined(pos)) if pos.height().get() <= max_height
+                )
+            })
+            .map(|(opout, _)| opout)
+            .collect::<BTreeSet<_>>();
+        if spent.is_empty() {
+            return Ok(0);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal
+                .contracts
+                .save(&contract_id, self.contracts.get(&contract_id));
+        }
+        Ok(self
+            .contracts
+            .get_mut(&contract_id)
+            .map(|contract| contract.prune(&spent))
+            .unwrap_or_default())
+    }
+
+    fn remove_contract(&mut self, contract_id: ContractId) -> Result<bool, Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .contracts
+                .save(&contract_id, self.contracts.get(&contract_id));
+        }
+        let Some(contract) = self.contracts.remove(&contract_id)? else {
+            return Ok(false);
+        };
+        let used = self
+            .contracts
+            .values()
+            .flat_map(MemContractState::witness_ids)
+            .collect::<BTreeSet<_>>();
+        for witness_id in contract.witness_ids().difference(&used) {
+            if let Some(journal) = &mut self.journal {
+                journal
+                    .witnesses
+                    .save(witness_id, self.witnesses.get(witness_id));
+            }
+            self.witnesses.remove(witness_id)?;
+        }
+        Ok(true)
     }
 }
 
-#[derive(Getters, Clone, Eq, PartialEq, Debug)]
+#[derive(Getters, Clone, Eq, Debug)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB_STORAGE)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
 pub struct MemGlobalState {
     known: LargeOrdMap<GlobalOut, DataState>,
     limit: u24,
+    /// Number of known state items per witness (with `None` standing for the
+    /// genesis), which is lazily constructed and is not persisted.
+    #[strict_type(skip)]
+    #[getter(skip)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    counts: OnceLock<BTreeMap<Option<XWitnessId>, u32>>,
+}
+
+impl PartialEq for MemGlobalState {
+    fn eq(&self, other: &Self) -> bool { self.known == other.known && self.limit == other.limit }
 }
 
 impl MemGlobalState {
@@ -657,6 +1219,41 @@ impl MemGlobalState {
         MemGlobalState {
             known: empty!(),
             limit,
+            counts: default!(),
+        }
+    }
+
+    fn witness_counts(&self) -> &BTreeMap<Option<XWitnessId>, u32> {
+        self.counts.get_or_init(|| {
+            let mut counts = BTreeMap::<_, u32>::new();
+            for out in self.known.keys() {
+                *counts.entry(out.witness_id()).or_default() += 1;
+            }
+            counts
+        })
+    }
+
+    /// Computes number of the state items which are visible under a given
+    /// witness filter, taking into account the global state limit.
+    fn size(&self, filter: &HashMap<XWitnessId, WitnessOrd>) -> u24 {
+        let size = self
+            .witness_counts()
+            .iter()
+            .filter(|(id, _)| id.map(|id| filter.contains_key(&id)).unwrap_or(true))
+            .map(|(_, count)| *count as usize)
+            .sum::<usize>()
+            .min(self.limit.to_usize());
+        u24::try_from(size as u32).expect("size is bounded by u24 limit")
+    }
+
+    fn insert(&mut self, out: GlobalOut, data: DataState) {
+        let witness_id = out.witness_id();
+        let prev = self
+            .known
+            .insert(out, data)
+            .expect("contract global state exceeded 2^32 items, which is unrealistic");
+        if let (None, Some(counts)) = (prev, self.counts.get_mut()) {
+            *counts.entry(witness_id).or_default() += 1;
         }
     }
 }
@@ -670,7 +1267,7 @@ impl MemGlobalState {
 /// validation. It does not support filtering by witness transaction validity
 /// and thus must not be used in any other cases in its explicit form. Pls see
 /// [`MemContract`] instead.
-#[derive(Getters, Clone, Eq, PartialEq, Debug)]
+#[derive(Getters, Clone, Eq, Debug)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB_STORAGE)]
 #[cfg_attr(
@@ -689,6 +1286,46 @@ pub struct MemContractState {
     fungibles: LargeOrdSet<OutputAssignment<RevealedValue>>,
     data: LargeOrdSet<OutputAssignment<RevealedData>>,
     attach: LargeOrdSet<OutputAssignment<RevealedAttach>>,
+    /// Secondary index of assignments by their outpoint, which is lazily
+    /// constructed on the first lookup and is not persisted.
+    #[strict_type(skip)]
+    #[getter(skip)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    outpoints: OnceLock<OutpointIndex>,
+}
+
+type OutpointIndex = BTreeMap<(XOutpoint, AssignmentType), BTreeSet<Opout>>;
+
+fn global_out(op: OrdOpRef, index: u16) -> GlobalOut {
+    GlobalOut {
+        opid: op.id(),
+        nonce: op.nonce(),
+        index,
+        op_witness: OpWitness::from(op),
+    }
+}
+
+fn index_assignment<State: KnownState>(
+    index: &mut OutpointIndex,
+    assignment: &OutputAssignment<State>,
+) {
+    index
+        .entry((assignment.seal.to_outpoint(), assignment.opout.ty))
+        .or_default()
+        .insert(assignment.opout);
+}
+
+impl PartialEq for MemContractState {
+    fn eq(&self, other: &Self) -> bool {
+        // The outpoint index is a derived data and is ignored
+        self.schema_id == other.schema_id &&
+            self.contract_id == other.contract_id &&
+            self.global == other.global &&
+            self.rights == other.rights &&
+            self.fungibles == other.fungibles &&
+            self.data == other.data &&
+            self.attach == other.attach
+    }
 }
 
 impl MemContractState {
@@ -707,10 +1344,50 @@ impl MemContractState {
             fungibles: empty!(),
             data: empty!(),
             attach: empty!(),
+            outpoints: default!(),
         }
     }
 
-    fn add_operation(&mut self, op: OrdOpRef) {
+    fn outpoint_index(&self) -> &OutpointIndex {
+        self.outpoints.get_or_init(|| {
+            let mut index = OutpointIndex::new();
+            self.rights.iter().for_each(|a| index_assignment(&mut index, a));
+            self.fungibles.iter().for_each(|a| index_assignment(&mut index, a));
+            self.data.iter().for_each(|a| index_assignment(&mut index, a));
+            self.attach.iter().for_each(|a| index_assignment(&mut index, a));
+            index
+        })
+    }
+
+    /// Returns ids of all witnesses which have contributed to the contract
+    /// state.
+    fn witness_ids(&self) -> BTreeSet<XWitnessId> {
+        self.global
+            .values()
+            .flat_map(|state| state.witness_counts().keys().flatten().copied())
+            .chain(self.rights.iter().filter_map(|a| a.witness))
+            .chain(self.fungibles.iter().filter_map(|a| a.witness))
+            .chain(self.data.iter().filter_map(|a| a.witness))
+            .chain(self.attach.iter().filter_map(|a| a.witness))
+            .collect()
+    }
+
+    /// Iterates over all known assignments of a given type at a given
+    /// outpoint, in the order of their opouts.
+    fn assignments_at<'a, State: KnownState>(
+        &'a self,
+        contract_state: &'a LargeOrdSet<OutputAssignment<State>>,
+        outpoint: XOutpoint,
+        ty: AssignmentType,
+    ) -> impl DoubleEndedIterator<Item = &'a OutputAssignment<State>> {
+        self.outpoint_index()
+            .get(&(outpoint, ty))
+            .into_iter()
+            .flatten()
+            .filter_map(|opout| contract_state.get(opout))
+    }
+
+    pub(super) fn add_operation(&mut self, op: OrdOpRef) {
         let opid = op.id();
 
         for (ty, state) in op.globals() {
@@ -719,50 +1396,13 @@ impl MemContractState {
                 .get_mut(ty)
                 .expect("global map must be initialized from the schema");
             for (idx, s) in state.iter().enumerate() {
-                let out = GlobalOut {
-                    opid,
-                    nonce: op.nonce(),
-                    index: idx as u16,
-                    op_witness: OpWitness::from(op),
-                };
-                map.known
-                    .insert(out, s.clone())
-                    .expect("contract global state exceeded 2^32 items, which is unrealistic");
+                map.insert(global_out(op, idx as u16), s.clone());
             }
         }
 
         // We skip removing of invalidated state for the cases of re-orgs or unmined
-        // witness transactions committing to the new state.
-        // TODO: Expose an API to prune historic state by witness txid
-        /*
-        // Remove invalidated state
-        for input in &op.inputs() {
-            if let Some(o) = self.rights.iter().find(|r| r.opout == input.prev_out) {
-                let o = o.clone(); // need this b/c of borrow checker
-                self.rights
-                    .remove(&o)
-                    .expect("collection allows zero elements");
-            }
-            if let Some(o) = self.fungibles.iter().find(|r| r.opout == input.prev_out) {
-                let o = o.clone();
-                self.fungibles
-                    .remove(&o)
-                    .expect("collection allows zero elements");
-            }
-            if let Some(o) = self.data.iter().find(|r| r.opout == input.prev_out) {
-                let o = o.clone();
-                self.data
-                    .remove(&o)
-                    .expect("collection allows zero elements");
-            }
-            if let Some(o) = self.attach.iter().find(|r| r.opout == input.prev_out) {
-                let o = o.clone();
-                self.attach
-                    .remove(&o)
-                    .expect("collection allows zero elements");
-            }
-        }
-         */
+        // witness transactions committing to the new state. Spent state is removed
+        // with `MemContractState::prune` once the spending witness is deep enough.
 
         let witness_id = op.witness_id();
         match op.assignments() {
@@ -775,6 +1415,47 @@ impl MemContractState {
         }
     }
 
+    /// Removes assignments for the provided spent outputs from the contract
+    /// state, returning the number of removed assignments.
+    ///
+    /// The caller is responsible for ensuring that the spending operations
+    /// can't be invalidated by re-orgs, otherwise the state will be lost.
+    pub fn prune(&mut self, spent: &BTreeSet<Opout>) -> usize {
+        fn process<State: KnownState>(
+            contract_state: &mut LargeOrdSet<OutputAssignment<State>>,
+            index: Option<&mut OutpointIndex>,
+            spent: &BTreeSet<Opout>,
+        ) -> usize {
+            let pruned = contract_state
+                .iter()
+                .filter(|assignment| spent.contains(&assignment.opout))
+                .cloned()
+                .collect::<Vec<_>>();
+            for assignment in &pruned {
+                contract_state
+                    .remove(assignment)
+                    .expect("collection allows zero elements");
+            }
+            if let Some(index) = index {
+                for assignment in &pruned {
+                    let key = (assignment.seal.to_outpoint(), assignment.opout.ty);
+                    if let Some(opouts) = index.get_mut(&key) {
+                        opouts.remove(&assignment.opout);
+                        if opouts.is_empty() {
+                            index.remove(&key);
+                        }
+                    }
+                }
+            }
+            pruned.len()
+        }
+
+        process(&mut self.rights, self.outpoints.get_mut(), spent) +
+            process(&mut self.fungibles, self.outpoints.get_mut(), spent) +
+            process(&mut self.data, self.outpoints.get_mut(), spent) +
+            process(&mut self.attach, self.outpoints.get_mut(), spent)
+    }
+
     fn add_assignments<Seal: ExposedSeal>(
         &mut self,
         witness_id: Option<XWitnessId>,
@@ -783,6 +1464,7 @@ impl MemContractState {
     ) {
         fn process<State: ExposedState + KnownState, Seal: ExposedSeal>(
             contract_state: &mut LargeOrdSet<OutputAssignment<State>>,
+            mut index: Option<&mut OutpointIndex>,
             assignments: &[Assign<State, Seal>],
             opid: OpId,
             ty: AssignmentType,
@@ -799,36 +1481,184 @@ impl MemContractState {
                     }
                     None => OutputAssignment::with_no_witness(seal, state, opid, ty, no as u16),
                 };
+                let opout = assigned_state.opout;
                 contract_state
                     .push(assigned_state)
                     .expect("contract state exceeded 2^32 items, which is unrealistic");
+                if let Some(index) = index.as_deref_mut() {
+                    // The set keeps the previously known assignment for the same opout, so we
+                    // index whichever is actually stored.
+                    let stored = contract_state.get(&opout).expect("assignment was just added");
+                    index_assignment(index, stored);
+                }
             }
         }
 
+        let mut index = self.outpoints.get_mut();
+
         for (ty, assignments) in assignments.iter() {
             match assignments {
                 TypedAssigns::Declarative(assignments) => {
-                    process(&mut self.rights, assignments, opid, *ty, witness_id)
+                    let index = index.as_deref_mut();
+                    process(&mut self.rights, index, assignments, opid, *ty, witness_id)
                 }
                 TypedAssigns::Fungible(assignments) => {
-                    process(&mut self.fungibles, assignments, opid, *ty, witness_id)
+                    let index = index.as_deref_mut();
+                    process(&mut self.fungibles, index, assignments, opid, *ty, witness_id)
                 }
                 TypedAssigns::Structured(assignments) => {
-                    process(&mut self.data, assignments, opid, *ty, witness_id)
+                    let index = index.as_deref_mut();
+                    process(&mut self.data, index, assignments, opid, *ty, witness_id)
                 }
                 TypedAssigns::Attachment(assignments) => {
-                    process(&mut self.attach, assignments, opid, *ty, witness_id)
+                    let index = index.as_deref_mut();
+                    process(&mut self.attach, index, assignments, opid, *ty, witness_id)
                 }
             }
         }
     }
 }
 
+/// Single item of the contract state, allowing to persist the state as a set
+/// of independent records.
+#[cfg(feature = "sqlite")]
+#[derive(Clone, Debug)]
+pub(super) enum StateItem {
+    Global(GlobalStateType, GlobalOut, DataState),
+    Rights(OutputAssignment<VoidState>),
+    Fungible(OutputAssignment<RevealedValue>),
+    Data(OutputAssignment<RevealedData>),
+    Attach(OutputAssignment<RevealedAttach>),
+}
+
+#[cfg(feature = "sqlite")]
+impl MemContractState {
+    /// Constructs empty contract state with the provided global state limits.
+    pub(super) fn with_limits(
+        schema_id: SchemaId,
+        contract_id: ContractId,
+        limits: BTreeMap<GlobalStateType, u24>,
+    ) -> Result<Self, confinement::Error> {
+        let global = Confined::try_from_iter(
+            limits
+                .into_iter()
+                .map(|(ty, limit)| (ty, MemGlobalState::new(limit))),
+        )?;
+        Ok(MemContractState {
+            schema_id,
+            contract_id,
+            global,
+            rights: empty!(),
+            fungibles: empty!(),
+            data: empty!(),
+            attach: empty!(),
+            outpoints: default!(),
+        })
+    }
+
+    pub(super) fn global_limits(&self) -> BTreeMap<GlobalStateType, u24> {
+        self.global
+            .iter()
+            .map(|(ty, state)| (*ty, state.limit))
+            .collect()
+    }
+
+    /// Adds previously persisted state item.
+    ///
+    /// # Panics
+    ///
+    /// If the global state type is not known to the contract.
+    pub(super) fn restore_item(&mut self, item: StateItem) -> Result<(), confinement::Error> {
+        match item {
+            StateItem::Global(ty, out, data) => self
+                .global
+                .get_mut(&ty)
+                .expect("global state type must be known")
+                .insert(out, data),
+            StateItem::Rights(assignment) => self.rights.push(assignment)?,
+            StateItem::Fungible(assignment) => self.fungibles.push(assignment)?,
+            StateItem::Data(assignment) => self.data.push(assignment)?,
+            StateItem::Attach(assignment) => self.attach.push(assignment)?,
+        }
+        Ok(())
+    }
+
+    /// Returns state items created by an operation which was added to the
+    /// contract state.
+    pub(super) fn operation_items(&self, op: OrdOpRef) -> Vec<StateItem> {
+        fn process<Seal: ExposedSeal>(
+            state: &MemContractState,
+            opid: OpId,
+            assignments: &Assignments<Seal>,
+            items: &mut Vec<StateItem>,
+        ) {
+            for (ty, assignments) in assignments.iter() {
+                for no in 0..assignments.len_u16() {
+                    let opout = Opout::new(opid, *ty, no);
+                    // Assignments with concealed seals are not a part of the state
+                    let item = match assignments {
+                        TypedAssigns::Declarative(_) => {
+                            state.rights.get(&opout).cloned().map(StateItem::Rights)
+                        }
+                        TypedAssigns::Fungible(_) => {
+                            state.fungibles.get(&opout).cloned().map(StateItem::Fungible)
+                        }
+                        TypedAssigns::Structured(_) => {
+                            state.data.get(&opout).cloned().map(StateItem::Data)
+                        }
+                        TypedAssigns::Attachment(_) => {
+                            state.attach.get(&opout).cloned().map(StateItem::Attach)
+                        }
+                    };
+                    items.extend(item);
+                }
+            }
+        }
+
+        let mut items = vec![];
+        for (ty, state) in op.globals() {
+            for (idx, data) in state.iter().enumerate() {
+                items.push(StateItem::Global(*ty, global_out(op, idx as u16), data.clone()));
+            }
+        }
+        let opid = op.id();
+        match op.assignments() {
+            AssignmentsRef::Genesis(assignments) => process(self, opid, assignments, &mut items),
+            AssignmentsRef::Graph(assignments) => process(self, opid, assignments, &mut items),
+        }
+        items
+    }
+
+    pub(super) fn has_global(&self, ty: GlobalStateType) -> bool { self.global.contains_key(&ty) }
+}
+
 pub struct MemContract<M: Borrow<MemContractState> = MemContractState> {
     filter: HashMap<XWitnessId, WitnessOrd>,
     unfiltered: M,
 }
 
+#[cfg(feature = "sqlite")]
+impl MemContract<MemContractState> {
+    /// Constructs contract state from its unfiltered data and the witnesses
+    /// they depend on.
+    pub(super) fn with_witnesses(
+        unfiltered: MemContractState,
+        filter: HashMap<XWitnessId, WitnessOrd>,
+    ) -> Self {
+        MemContract { filter, unfiltered }
+    }
+}
+
+impl<'a> MemContract<&'a MemContractState> {
+    /// Clones the contract state, detaching it from the state provider.
+    pub fn detach(&self) -> MemContract<MemContractState> {
+        MemContract {
+            filter: self.filter.clone(),
+            unfiltered: self.unfiltered.clone(),
+        }
+    }
+}
+
 impl<M: Borrow<MemContractState>> Debug for MemContract<M> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str("MemContractFiltered { .. }")
@@ -847,6 +1677,7 @@ impl<M: Borrow<MemContractState>> ContractStateAccess for MemContract<M> {
             iter: FilteredIter<'a>,
             last: Option<(GlobalOrd, &'a DataState)>,
             depth: u24,
+            size: u24,
             constructor: Box<dyn Fn(Src<'a>) -> FilteredIter<'a> + 'a>,
         }
         impl<'a> Iter<'a> {
@@ -858,13 +1689,7 @@ impl<M: Borrow<MemContractState>> ContractStateAccess for MemContract<M> {
         }
         impl<'a> GlobalStateIter for Iter<'a> {
             type Data = &'a DataState;
-            fn size(&mut self) -> u24 {
-                let iter = self.swap();
-                // TODO: Consuming iterator just to count items is highly inefficient, but I do
-                //       not know any other way of computing this value
-                let size = iter.count();
-                u24::try_from(size as u32).expect("iterator size must fit u24 due to `take` limit")
-            }
+            fn size(&mut self) -> u24 { self.size }
             fn prev(&mut self) -> Option<(GlobalOrd, Self::Data)> {
                 self.last = self.iter.next();
                 self.depth += u24::ONE;
@@ -922,6 +1747,7 @@ impl<M: Borrow<MemContractState>> ContractStateAccess for MemContract<M> {
             src: state.known.as_inner(),
             iter: constructor(state.known.as_inner()),
             depth: u24::ZERO,
+            size: state.size(&self.filter),
             last: None,
             constructor: Box::new(constructor),
         };
@@ -929,13 +1755,9 @@ impl<M: Borrow<MemContractState>> ContractStateAccess for MemContract<M> {
     }
 
     fn rights(&self, outpoint: XOutpoint, ty: AssignmentType) -> u32 {
-        self.unfiltered
-            .borrow()
-            .rights
-            .iter()
-            .filter(|assignment| {
-                assignment.seal.to_outpoint() == outpoint && assignment.opout.ty == ty
-            })
+        let state = self.unfiltered.borrow();
+        state
+            .assignments_at(&state.rights, outpoint, ty)
             .filter(|assignment| assignment.check_witness(&self.filter))
             .count() as u32
     }
@@ -945,13 +1767,9 @@ impl<M: Borrow<MemContractState>> ContractStateAccess for MemContract<M> {
         outpoint: XOutpoint,
         ty: AssignmentType,
     ) -> impl DoubleEndedIterator<Item = FungibleState> {
-        self.unfiltered
-            .borrow()
-            .fungibles
-            .iter()
-            .filter(move |assignment| {
-                assignment.seal.to_outpoint() == outpoint && assignment.opout.ty == ty
-            })
+        let state = self.unfiltered.borrow();
+        state
+            .assignments_at(&state.fungibles, outpoint, ty)
             .filter(|assignment| assignment.check_witness(&self.filter))
             .map(|assignment| assignment.state.value)
     }
@@ -961,13 +1779,9 @@ impl<M: Borrow<MemContractState>> ContractStateAccess for MemContract<M> {
         outpoint: XOutpoint,
         ty: AssignmentType,
     ) -> impl DoubleEndedIterator<Item = impl Borrow<DataState>> {
-        self.unfiltered
-            .borrow()
-            .data
-            .iter()
-            .filter(move |assignment| {
-                assignment.seal.to_outpoint() == outpoint && assignment.opout.ty == ty
-            })
+        let state = self.unfiltered.borrow();
+        state
+            .assignments_at(&state.data, outpoint, ty)
             .filter(|assignment| assignment.check_witness(&self.filter))
             .map(|assignment| &assignment.state.value)
     }
@@ -977,13 +1791,9 @@ impl<M: Borrow<MemContractState>> ContractStateAccess for MemContract<M> {
         outpoint: XOutpoint,
         ty: AssignmentType,
     ) -> impl DoubleEndedIterator<Item = impl Borrow<AttachState>> {
-        self.unfiltered
-            .borrow()
-            .attach
-            .iter()
-            .filter(move |assignment| {
-                assignment.seal.to_outpoint() == outpoint && assignment.opout.ty == ty
-            })
+        let state = self.unfiltered.borrow();
+        state
+            .assignments_at(&state.attach, outpoint, ty)
             .filter(|assignment| assignment.check_witness(&self.filter))
             .map(|assignment| &assignment.state.file)
     }
@@ -1157,6 +1967,16 @@ pub struct ContractIndex {
     outpoint_opouts: MediumOrdMap<XOutputSeal, MediumOrdSet<Opout>>,
 }
 
+#[derive(Clone, Debug, Default)]
+struct IndexJournal {
+    op_bundle_index: MapJournal<OpId, BundleId>,
+    bundle_contract_index: MapJournal<BundleId, ContractId>,
+    bundle_witness_index: MapJournal<BundleId, TinyOrdSet<XWitnessId>>,
+    contract_index: MapJournal<ContractId, ContractIndex>,
+    terminal_index: MapJournal<XChain<SecretSeal>, Opout>,
+    spender_index: MapJournal<Opout, TinyOrdSet<Spender>>,
+}
+
 #[derive(Getters, Clone, Debug)]
 #[getter(prefix = "debug_")]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
@@ -1164,9 +1984,21 @@ pub struct ContractIndex {
 pub struct MemIndex {
     #[strict_type(skip)]
     dirty: bool,
+    #[strict_type(skip)]
+    #[getter(skip)]
+    journal: Option<IndexJournal>,
     #[cfg(feature = "fs")]
     #[strict_type(skip)]
     filename: Option<PathBuf>,
+    #[cfg(feature = "fs")]
+    #[strict_type(skip)]
+    #[getter(skip)]
+    fs_config: FsConfig,
+    // Spenders are not a part of the serialized index layout; file-based
+    // storage keeps them after the index data. Indexes created before the
+    // spenders were introduced need to be rebuilt to get them.
+    #[strict_type(skip)]
+    spender_index: MediumOrdMap<Opout, TinyOrdSet<Spender>>,
 
     op_bundle_index: MediumOrdMap<OpId, BundleId>,
     bundle_contract_index: MediumOrdMap<BundleId, ContractId>,
@@ -1182,13 +2014,17 @@ impl MemIndex {
     pub fn in_memory() -> Self {
         Self {
             dirty: false,
+            journal: None,
             #[cfg(feature = "fs")]
             filename: None,
+            #[cfg(feature = "fs")]
+            fs_config: default!(),
             op_bundle_index: empty!(),
             bundle_contract_index: empty!(),
             bundle_witness_index: empty!(),
             contract_index: empty!(),
             terminal_index: empty!(),
+            spender_index: empty!(),
         }
     }
 }
@@ -1198,10 +2034,14 @@ impl StoreTransaction for MemIndex {
 
     fn begin_transaction(&mut self) -> Result<(), Self::TransactionErr> {
         self.dirty = true;
+        if self.journal.is_none() {
+            self.journal = Some(default!());
+        }
         Ok(())
     }
 
     fn commit_transaction(&mut self) -> Result<(), Self::TransactionErr> {
+        self.journal = None;
         #[cfg(feature = "fs")]
         if self.dirty {
             self.store()?;
@@ -1209,7 +2049,21 @@ impl StoreTransaction for MemIndex {
         Ok(())
     }
 
-    fn rollback_transaction(&mut self) { unreachable!() }
+    fn rollback_transaction(&mut self) {
+        let Some(journal) = self.journal.take() else {
+            return;
+        };
+        journal.op_bundle_index.restore(&mut self.op_bundle_index);
+        journal
+            .bundle_contract_index
+            .restore(&mut self.bundle_contract_index);
+        journal
+            .bundle_witness_index
+            .restore(&mut self.bundle_witness_index);
+        journal.contract_index.restore(&mut self.contract_index);
+        journal.terminal_index.restore(&mut self.terminal_index);
+        journal.spender_index.restore(&mut self.spender_index);
+    }
 }
 
 impl IndexProvider for MemIndex {}
@@ -1286,6 +2140,10 @@ impl IndexReadProvider for MemIndex {
             .ok_or(IndexInconsistency::BundleAbsent(opid).into())
     }
 
+    fn spenders(&self, opout: Opout) -> Result<impl Iterator<Item = Spender> + '_, Self::Error> {
+        Ok(self.spender_index.get(&opout).into_iter().flatten().copied())
+    }
+
     fn bundle_info(
         &self,
         bundle_id: BundleId,
@@ -1300,6 +2158,25 @@ impl IndexReadProvider for MemIndex {
             .ok_or(IndexInconsistency::BundleContractUnknown(bundle_id))?;
         Ok((witness_ids.iter().copied(), *contract_id))
     }
+
+    fn contract_ids(&self) -> Result<impl Iterator<Item = ContractId> + '_, Self::Error> {
+        Ok(self.contract_index.keys().copied())
+    }
+
+    fn bundle_ids(&self) -> Result<impl Iterator<Item = BundleId> + '_, Self::Error> {
+        let ids = self
+            .bundle_contract_index
+            .keys()
+            .chain(self.bundle_witness_index.keys())
+            .chain(self.op_bundle_index.values())
+            .copied()
+            .collect::<BTreeSet<_>>();
+        Ok(ids.into_iter())
+    }
+
+    fn serialized_size(&self) -> Result<usize, Self::Error> {
+        Ok(serialized_size(self) + serialized_size(&self.spender_index))
+    }
 }
 
 impl IndexWriteProvider for MemIndex {
@@ -1307,6 +2184,9 @@ impl IndexWriteProvider for MemIndex {
 
     fn register_contract(&mut self, contract_id: ContractId) -> Result<bool, Self::Error> {
         if !self.contract_index.contains_key(&contract_id) {
+            if let Some(journal) = &mut self.journal {
+                journal.contract_index.save(&contract_id, None);
+            }
             self.contract_index.insert(contract_id, empty!())?;
             Ok(true)
         } else {
@@ -1332,6 +2212,14 @@ impl IndexWriteProvider for MemIndex {
             }
             .into());
         }
+        if let Some(journal) = &mut self.journal {
+            journal
+                .bundle_witness_index
+                .save(&bundle_id, self.bundle_witness_index.get(&bundle_id));
+            journal
+                .bundle_contract_index
+                .save(&bundle_id, self.bundle_contract_index.get(&bundle_id));
+        }
         let mut set = self
             .bundle_witness_index
             .remove(&bundle_id)?
@@ -1362,10 +2250,36 @@ impl IndexWriteProvider for MemIndex {
             }
             .into());
         }
+        if let Some(journal) = &mut self.journal {
+            journal
+                .op_bundle_index
+                .save(&opid, self.op_bundle_index.get(&opid));
+        }
         let present = self.op_bundle_index.insert(opid, bundle_id)?.is_some();
         Ok(!present)
     }
 
+    fn register_spender(
+        &mut self,
+        opout: Opout,
+        spender: Spender,
+    ) -> Result<bool, IndexWriteError<Self::Error>> {
+        let spenders = self.spender_index.get(&opout);
+        if spenders.is_some_and(|spenders| spenders.contains(&spender)) {
+            return Ok(false);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.spender_index.save(&opout, spenders);
+        }
+        match self.spender_index.get_mut(&opout) {
+            Some(spenders) => spenders.push(spender)?,
+            None => {
+                self.spender_index.insert(opout, confined_bset!(spender))?;
+            }
+        }
+        Ok(true)
+    }
+
     fn index_genesis_assignments<State: ExposedState>(
         &mut self,
         contract_id: ContractId,
@@ -1373,6 +2287,11 @@ impl IndexWriteProvider for MemIndex {
         opid: OpId,
         type_id: AssignmentType,
     ) -> Result<(), IndexWriteError<Self::Error>> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .contract_index
+                .save(&contract_id, self.contract_index.get(&contract_id));
+        }
         let index = self
             .contract_index
             .get_mut(&contract_id)
@@ -1396,6 +2315,11 @@ impl IndexWriteProvider for MemIndex {
                 }
             }
             if let Assign::Confidential { seal, .. } | Assign::ConfidentialSeal { seal, .. } = a {
+                if let Some(journal) = &mut self.journal {
+                    journal
+                        .terminal_index
+                        .save(seal, self.terminal_index.get(seal));
+                }
                 self.terminal_index.insert(*seal, opout)?;
             }
         }
@@ -1410,6 +2334,11 @@ impl IndexWriteProvider for MemIndex {
         type_id: AssignmentType,
         witness_id: XWitnessId,
     ) -> Result<(), IndexWriteError<Self::Error>> {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .contract_index
+                .save(&contract_id, self.contract_index.get(&contract_id));
+        }
         let index = self
             .contract_index
             .get_mut(&contract_id)
@@ -1439,23 +2368,123 @@ impl IndexWriteProvider for MemIndex {
             if let Assign::Confidential { seal, .. } | Assign::ConfidentialSeal { seal, .. } =
                 assign
             {
+                if let Some(journal) = &mut self.journal {
+                    journal
+                        .terminal_index
+                        .save(seal, self.terminal_index.get(seal));
+                }
                 self.terminal_index.insert(*seal, opout)?;
             }
         }
         Ok(())
     }
+
+    fn remove_contract(
+        &mut self,
+        contract_id: ContractId,
+        opids: &BTreeSet<OpId>,
+    ) -> Result<bool, Self::Error> {
+        let bundle_ids = self
+            .bundle_contract_index
+            .iter()
+            .filter(|(_, id)| **id == contract_id)
+            .map(|(bundle_id, _)| *bundle_id)
+            .collect::<BTreeSet<_>>();
+        let terminals = self
+            .terminal_index
+            .iter()
+            .filter(|(_, opout)| opids.contains(&opout.op))
+            .map(|(seal, _)| *seal)
+            .collect::<BTreeSet<_>>();
+        let spent = self
+            .spender_index
+            .keys()
+            .filter(|opout| opids.contains(&opout.op))
+            .copied()
+            .collect::<BTreeSet<_>>();
+        if let Some(journal) = &mut self.journal {
+            journal
+                .contract_index
+                .save(&contract_id, self.contract_index.get(&contract_id));
+            for bundle_id in &bundle_ids {
+                journal
+                    .bundle_contract_index
+                    .save(bundle_id, self.bundle_contract_index.get(bundle_id));
+                journal
+                    .bundle_witness_index
+                    .save(bundle_id, self.bundle_witness_index.get(bundle_id));
+            }
+            for opid in opids {
+                journal
+                    .op_bundle_index
+                    .save(opid, self.op_bundle_index.get(opid));
+            }
+            for seal in &terminals {
+                journal
+                    .terminal_index
+                    .save(seal, self.terminal_index.get(seal));
+            }
+            for opout in &spent {
+                journal
+                    .spender_index
+                    .save(opout, self.spender_index.get(opout));
+            }
+        }
+        for bundle_id in &bundle_ids {
+            self.bundle_contract_index.remove(bundle_id)?;
+            self.bundle_witness_index.remove(bundle_id)?;
+        }
+        for opid in opids {
+            self.op_bundle_index.remove(opid)?;
+        }
+        for seal in &terminals {
+            self.terminal_index.remove(seal)?;
+        }
+        for opout in &spent {
+            self.spender_index.remove(opout)?;
+        }
+        Ok(self.contract_index.remove(&contract_id)?.is_some())
+    }
 }
 
 #[cfg(feature = "fs")]
 mod fs {
+    use std::fs;
+    use std::io::Cursor;
     use std::path::{Path, PathBuf};
 
-    use amplify::confinement::U32;
-    use strict_encoding::{DeserializeError, SerializeError, StrictDeserialize, StrictSerialize};
-
-    use crate::persistence::fs::FsStored;
+    use amplify::confinement::{
+        Confined, MediumOrdMap, SmallOrdMap, SmallOrdSet, TinyOrdMap, TinyVec, U32,
+    };
+    use rgb::ContractId;
+    use strict_encoding::{
+        DecodeError, DeserializeError, SerializeError, StreamReader, StreamWriter, StrictDecode,
+        StrictDeserialize, StrictEncode, StrictSerialize, TypeName,
+    };
+
+    use crate::interface::ImplId;
+    use crate::persistence::fs::{store_file, FsConfig, FsStored};
     use crate::persistence::{MemIndex, MemStash, MemState};
 
+    /// Prefix of the stash files written in a versioned layout. Files written
+    /// before the layout versioning start with the stash schemata, which in
+    /// practice never produce these bytes.
+    const STASH_MAGIC: [u8; 8] = *b"RGBSTASH";
+    /// Layout version 1 follows the stash data with the archival status of
+    /// the contracts, superseded interface implementations and contract
+    /// bindings to them. Version 2 extends the limit on the number of the
+    /// contracts with bindings from 255 to 65535.
+    const STASH_VERSION: u8 = 2;
+
+    type Bindings = TinyOrdMap<TypeName, TinyVec<ImplId>>;
+
+    /// Prefix of the index files, which are versioned in the same way as the
+    /// stash files.
+    const INDEX_MAGIC: [u8; 8] = *b"RGBINDEX";
+    /// Layout version 1 follows the index data with the transitions spending
+    /// each of the outputs.
+    const INDEX_VERSION: u8 = 1;
+
     impl FsStored for MemStash {
         fn new(filename: impl ToOwned<Owned = PathBuf>) -> Self {
             Self {
@@ -1467,7 +2496,38 @@ mod fs {
 
         fn load(path: impl ToOwned<Owned = PathBuf>) -> Result<Self, DeserializeError> {
             let path = path.to_owned();
-            let mut me = Self::strict_deserialize_from_file::<U32>(&path)?;
+            let data = fs::read(&path)?;
+            let mut cursor = Cursor::new(data.as_slice());
+            let mut me = match data.strip_prefix(&STASH_MAGIC) {
+                // Files written before the layout versioning contain only the stash data
+                None => Self::strict_read(StreamReader::new::<U32>(&mut cursor))?,
+                Some([version @ (1 | STASH_VERSION), ..]) => {
+                    cursor.set_position(STASH_MAGIC.len() as u64 + 1);
+                    let mut me = Self::strict_read(StreamReader::new::<U32>(&mut cursor))?;
+                    me.archived = SmallOrdSet::strict_read(StreamReader::new::<U32>(&mut cursor))?;
+                    me.superseded_iimpls =
+                        SmallOrdMap::strict_read(StreamReader::new::<U32>(&mut cursor))?;
+                    me.bindings = if *version == 1 {
+                        let bindings = TinyOrdMap::<ContractId, Bindings>::strict_read(
+                            StreamReader::new::<U32>(&mut cursor),
+                        )?;
+                        Confined::from_collection_unsafe(bindings.release())
+                    } else {
+                        SmallOrdMap::strict_read(StreamReader::new::<U32>(&mut cursor))?
+                    };
+                    me
+                }
+                Some(_) => {
+                    return Err(DecodeError::DataIntegrityError(format!(
+                        "stash file {} has unsupported layout version",
+                        path.display()
+                    ))
+                    .into());
+                }
+            };
+            if (cursor.position() as usize) < data.len() {
+                return Err(DeserializeError::DataNotEntirelyConsumed);
+            }
             me.set_filename(path);
             Ok(me)
         }
@@ -1483,10 +2543,23 @@ mod fs {
             prev
         }
 
+        fn fs_config(&self) -> FsConfig { self.fs_config }
+
+        fn set_fs_config(&mut self, config: FsConfig) { self.fs_config = config }
+
         fn store(&self) -> Result<(), SerializeError> {
             if self.is_dirty() {
                 if let Some(filename) = self.filename() {
-                    return self.strict_serialize_to_file::<U32>(filename);
+                    let mut data = STASH_MAGIC.to_vec();
+                    data.push(STASH_VERSION);
+                    self.strict_write(StreamWriter::new::<U32>(&mut data))?;
+                    self.archived
+                        .strict_write(StreamWriter::new::<U32>(&mut data))?;
+                    self.superseded_iimpls
+                        .strict_write(StreamWriter::new::<U32>(&mut data))?;
+                    self.bindings
+                        .strict_write(StreamWriter::new::<U32>(&mut data))?;
+                    store_file(filename, &data, self.fs_config)?;
                 }
             }
             Ok(())
@@ -1520,10 +2593,15 @@ mod fs {
             prev
         }
 
+        fn fs_config(&self) -> FsConfig { self.fs_config }
+
+        fn set_fs_config(&mut self, config: FsConfig) { self.fs_config = config }
+
         fn store(&self) -> Result<(), SerializeError> {
             if self.is_dirty() {
                 if let Some(filename) = self.filename() {
-                    return self.strict_serialize_to_file::<U32>(filename);
+                    let data = self.to_strict_serialized::<U32>()?;
+                    store_file(filename, &data, self.fs_config)?;
                 }
             }
             Ok(())
@@ -1541,7 +2619,36 @@ mod fs {
 
         fn load(path: impl ToOwned<Owned = PathBuf>) -> Result<Self, DeserializeError> {
             let path = path.to_owned();
-            let mut me = Self::strict_deserialize_from_file::<U32>(&path)?;
+            let data = fs::read(&path)?;
+            let mut cursor = Cursor::new(data.as_slice());
+            let mut me = match data.strip_prefix(&INDEX_MAGIC) {
+                Some([INDEX_VERSION, ..]) => {
+                    cursor.set_position(INDEX_MAGIC.len() as u64 + 1);
+                    let mut me = Self::strict_read(StreamReader::new::<U32>(&mut cursor))?;
+                    me.spender_index =
+                        MediumOrdMap::strict_read(StreamReader::new::<U32>(&mut cursor))?;
+                    me
+                }
+                // Files written before the layout versioning have no spenders, so
+                // the index must be rebuilt from the stash
+                None => {
+                    return Err(DecodeError::DataIntegrityError(format!(
+                        "index file {} has no spenders and must be rebuilt",
+                        path.display()
+                    ))
+                    .into());
+                }
+                Some(_) => {
+                    return Err(DecodeError::DataIntegrityError(format!(
+                        "index file {} has unsupported layout version",
+                        path.display()
+                    ))
+                    .into());
+                }
+            };
+            if (cursor.position() as usize) < data.len() {
+                return Err(DeserializeError::DataNotEntirelyConsumed);
+            }
             me.set_filename(path);
             Ok(me)
         }
@@ -1557,13 +2664,373 @@ mod fs {
             prev
         }
 
+        fn fs_config(&self) -> FsConfig { self.fs_config }
+
+        fn set_fs_config(&mut self, config: FsConfig) { self.fs_config = config }
+
         fn store(&self) -> Result<(), SerializeError> {
             if self.is_dirty() {
                 if let Some(filename) = self.filename() {
-                    return self.strict_serialize_to_file::<U32>(filename);
+                    let mut data = INDEX_MAGIC.to_vec();
+                    data.push(INDEX_VERSION);
+                    self.strict_write(StreamWriter::new::<U32>(&mut data))?;
+                    self.spender_index
+                        .strict_write(StreamWriter::new::<U32>(&mut data))?;
+                    store_file(filename, &data, self.fs_config)?;
                 }
             }
             Ok(())
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bp::{Txid, Vout};
+    use rgb::validation::WitnessResolverError;
+    use rgb::vm::{WitnessPos, XWitnessTx};
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+
+    fn assignment(opid: OpId, no: u16) -> OutputAssignment<VoidState> {
+        let mut assignment = OutputAssignment::<VoidState>::strict_dumb();
+        assignment.opout = Opout::new(opid, AssignmentType::strict_dumb(), no);
+        assignment
+    }
+
+    fn assignment_at(opid: OpId, no: u16, vout: u32) -> OutputAssignment<VoidState> {
+        let mut assignment = assignment(opid, no);
+        assignment.seal = assignment.seal.map(|mut seal| {
+            seal.vout = Vout::from_u32(vout);
+            seal
+        });
+        assignment
+    }
+
+    #[test]
+    fn outpoint_index() {
+        let genesis = Genesis::strict_dumb();
+        let opid = genesis.id();
+        let ty = AssignmentType::strict_dumb();
+        let mut state = MemContractState::new(&Schema::strict_dumb(), genesis.contract_id());
+        for no in 0..4 {
+            state
+                .rights
+                .push(assignment_at(opid, no, no as u32 % 2))
+                .unwrap();
+        }
+        let even = assignment_at(opid, 0, 0).seal.to_outpoint();
+        let odd = assignment_at(opid, 1, 1).seal.to_outpoint();
+
+        let contract = MemContract { filter: empty!(), unfiltered: &state };
+        assert_eq!(contract.rights(even, ty), 2);
+        assert_eq!(contract.rights(odd, ty), 2);
+        assert_eq!(contract.rights(even, AssignmentType::with(1)), 0);
+
+        let spent = bset![Opout::new(opid, ty, 0), Opout::new(opid, ty, 1)];
+        assert_eq!(state.prune(&spent), 2);
+        let contract = MemContract { filter: empty!(), unfiltered: &state };
+        assert_eq!(contract.rights(even, ty), 1);
+        assert_eq!(contract.rights(odd, ty), 1);
+        assert_eq!(state.outpoints.get().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn global_size() {
+        let genesis = Genesis::strict_dumb();
+        let opid = genesis.id();
+        let ty = GlobalStateType::strict_dumb();
+        let valid = XWitnessId::Bitcoin(Txid::from([1u8; 32]));
+        let invalid = XWitnessId::Bitcoin(Txid::from([2u8; 32]));
+
+        let mut global = MemGlobalState::new(u24::try_from(10u32).unwrap());
+        let witnesses = [None, Some(valid), Some(valid), Some(invalid)];
+        for (index, witness_id) in witnesses.into_iter().enumerate() {
+            let out = GlobalOut {
+                opid,
+                nonce: 0,
+                index: index as u16,
+                op_witness: witness_id.map(OpWitness::Transition).unwrap_or(OpWitness::Genesis),
+            };
+            global.insert(out, DataState::strict_dumb());
+        }
+        // Re-inserting known state must not affect the counts
+        global.insert(
+            GlobalOut {
+                opid,
+                nonce: 0,
+                index: 1,
+                op_witness: OpWitness::Transition(valid),
+            },
+            DataState::strict_dumb(),
+        );
+
+        let mut state = MemContractState::new(&Schema::strict_dumb(), genesis.contract_id());
+        state.global.insert(ty, global).unwrap();
+        let filter = map! { valid => WitnessOrd::Tentative };
+        let contract = MemContract { filter, unfiltered: &state };
+        let mut iter = contract.global(ty).unwrap();
+        assert_eq!(iter.size(), u24::try_from(3u32).unwrap());
+        assert_eq!(iter.size(), u24::try_from(3u32).unwrap());
+
+        let contract = MemContract { filter: empty!(), unfiltered: &state };
+        assert_eq!(contract.global(ty).unwrap().size(), u24::ONE);
+    }
+
+    struct OrdResolver(WitnessOrd);
+
+    impl ResolveWitness for OrdResolver {
+        fn resolve_pub_witness(
+            &self,
+            witness_id: XWitnessId,
+        ) -> Result<XWitnessTx, WitnessResolverError> {
+            Err(WitnessResolverError::Unknown(witness_id))
+        }
+
+        fn resolve_pub_witness_ord(
+            &self,
+            _: XWitnessId,
+        ) -> Result<WitnessOrd, WitnessResolverError> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn update_witnesses() {
+        let genesis = Genesis::strict_dumb();
+        let contract_id = genesis.contract_id();
+        let mut state = MemState::in_memory();
+        state
+            .register_contract(&Schema::strict_dumb(), &genesis)
+            .unwrap();
+
+        let mined = XWitnessId::Bitcoin(Txid::from([1u8; 32]));
+        let archived = XWitnessId::Bitcoin(Txid::from([2u8; 32]));
+        let pos = WitnessPos::new(100, 1_700_000_000).unwrap();
+        state.witnesses.insert(mined, WitnessOrd::Mined(pos)).unwrap();
+        state
+            .witnesses
+            .insert(archived, WitnessOrd::Archived)
+            .unwrap();
+
+        let mut reorged = assignment(genesis.id(), 0);
+        reorged.witness = Some(mined);
+        let contract = state.contracts.get_mut(&contract_id).unwrap();
+        contract.rights.push(reorged).unwrap();
+        assert_eq!(state.contract_state(contract_id).unwrap().rights_all().count(), 1);
+
+        let res = state
+            .update_witnesses(OrdResolver(WitnessOrd::Archived), 1, Some(Layer1::Liquid))
+            .unwrap();
+        assert_eq!(res.succeeded, 0);
+        assert_eq!(state.contract_state(contract_id).unwrap().rights_all().count(), 1);
+
+        let res = state
+            .update_witnesses(OrdResolver(WitnessOrd::Archived), 1, Some(Layer1::Bitcoin))
+            .unwrap();
+        assert_eq!(res.succeeded, 2);
+        assert_eq!(res.updated, map! { mined => (WitnessOrd::Mined(pos), WitnessOrd::Archived) });
+        assert_eq!(state.contract_state(contract_id).unwrap().rights_all().count(), 0);
+    }
+
+    #[test]
+    fn prune() {
+        let genesis = Genesis::strict_dumb();
+        let contract_id = genesis.contract_id();
+        let mut state = MemState::in_memory();
+        state
+            .register_contract(&Schema::strict_dumb(), &genesis)
+            .unwrap();
+
+        let mined = XWitnessId::Bitcoin(Txid::from([1u8; 32]));
+        let tentative = XWitnessId::Bitcoin(Txid::from([2u8; 32]));
+        let pos = WitnessPos::new(100, 1_700_000_000).unwrap();
+        state.witnesses.insert(mined, WitnessOrd::Mined(pos)).unwrap();
+        state
+            .witnesses
+            .insert(tentative, WitnessOrd::Tentative)
+            .unwrap();
+
+        let opid = genesis.id();
+        let contract = state.contracts.get_mut(&contract_id).unwrap();
+        contract.rights.push(assignment(opid, 0)).unwrap();
+        contract.rights.push(assignment(opid, 1)).unwrap();
+        contract.rights.push(assignment(opid, 2)).unwrap();
+        let spends = [
+            (Opout::new(opid, AssignmentType::strict_dumb(), 0), mined),
+            (Opout::new(opid, AssignmentType::strict_dumb(), 1), tentative),
+        ];
+
+        assert_eq!(state.prune(contract_id, spends, 99).unwrap(), 0);
+        assert_eq!(state.contracts.get(&contract_id).unwrap().rights.len(), 3);
+
+        assert_eq!(state.prune(contract_id, spends, 100).unwrap(), 1);
+        let rights = &state.contracts.get(&contract_id).unwrap().rights;
+        assert_eq!(rights.iter().map(|a| a.opout.no).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn archived() {
+        let contract_id = ContractId::strict_dumb();
+        let mut stash = MemStash::in_memory();
+        stash.begin_transaction().unwrap();
+        assert!(stash.set_archived(contract_id, true).unwrap());
+        stash.rollback_transaction();
+        assert_eq!(stash.archived_contracts().unwrap().count(), 0);
+
+        assert!(stash.set_archived(contract_id, true).unwrap());
+        assert!(!stash.set_archived(contract_id, true).unwrap());
+        assert_eq!(stash.archived_contracts().unwrap().collect::<Vec<_>>(), vec![contract_id]);
+
+        #[cfg(feature = "fs")]
+        {
+            let path =
+                std::env::temp_dir().join(format!("rgb-std-archived-{}.dat", std::process::id()));
+            stash.set_filename(path.clone());
+            stash.store().unwrap();
+            let loaded = MemStash::load(path.clone()).unwrap();
+            assert_eq!(loaded.debug_archived(), stash.debug_archived());
+
+            // Files written before the layout versioning have no archived contracts
+            let data = stash
+                .to_strict_serialized::<amplify::confinement::U32>()
+                .unwrap()
+                .release();
+            std::fs::write(&path, &data).unwrap();
+            assert!(MemStash::load(path.clone()).unwrap().debug_archived().is_empty());
+
+            let mut unknown = b"RGBSTASH\x03".to_vec();
+            unknown.extend(data);
+            std::fs::write(&path, unknown).unwrap();
+            assert!(MemStash::load(path.clone()).is_err());
+            std::fs::remove_file(path).ok();
+        }
+    }
+
+    #[test]
+    fn spenders() {
+        let genesis = Genesis::strict_dumb();
+        let contract_id = genesis.contract_id();
+        let opout = Opout::new(genesis.id(), AssignmentType::strict_dumb(), 0);
+        let spender = Spender {
+            opid: OpId::from([1u8; 32]),
+            witness_id: XWitnessId::Bitcoin(Txid::from([2u8; 32])),
+        };
+        let mut index = MemIndex::in_memory();
+        index.begin_transaction().unwrap();
+        assert!(index.register_spender(opout, spender).unwrap());
+        index.rollback_transaction();
+        assert_eq!(index.spenders(opout).unwrap().count(), 0);
+
+        assert!(index.register_spender(opout, spender).unwrap());
+        assert!(!index.register_spender(opout, spender).unwrap());
+        assert_eq!(index.spenders(opout).unwrap().collect::<Vec<_>>(), vec![spender]);
+
+        #[cfg(feature = "fs")]
+        {
+            let path =
+                std::env::temp_dir().join(format!("rgb-std-spenders-{}.dat", std::process::id()));
+            index.set_filename(path.clone());
+            index.store().unwrap();
+            let loaded = MemIndex::load(path.clone()).unwrap();
+            assert_eq!(loaded.debug_spender_index(), index.debug_spender_index());
+
+            // Files written before the layout versioning have no spenders
+            let data = index
+                .to_strict_serialized::<amplify::confinement::U32>()
+                .unwrap()
+                .release();
+            std::fs::write(&path, data).unwrap();
+            assert!(MemIndex::load(path.clone()).is_err());
+            std::fs::remove_file(path).ok();
+        }
+
+        assert!(!index
+            .remove_contract(contract_id, &bset![genesis.id()])
+            .unwrap());
+        assert_eq!(index.spenders(opout).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn iimpl_versions() {
+        let schema = Schema::strict_dumb();
+        let iface = Iface::strict_dumb();
+        let mut older = IfaceImpl::strict_dumb();
+        older.schema_id = schema.schema_id();
+        older.iface_id = iface.iface_id();
+        let mut newer = older.clone();
+        newer.timestamp = older.timestamp + 1;
+        let contract_id = ContractId::strict_dumb();
+
+        let mut stash = MemStash::in_memory();
+        stash.replace_schema(schema.clone()).unwrap();
+        stash.replace_iface(iface).unwrap();
+        assert!(stash.replace_iimpl(newer.clone()).unwrap());
+        assert!(stash.replace_iimpl(older.clone()).unwrap());
+        assert!(!stash.replace_iimpl(older.clone()).unwrap());
+        assert!(stash.debug_superseded_iimpls().contains_key(&older.impl_id()));
+        assert_eq!(stash.iimpls(schema.schema_id()).unwrap().count(), 2);
+        assert_eq!(stash.iimpl(older.impl_id()).unwrap(), &older);
+
+        stash.begin_transaction().unwrap();
+        assert!(stash.bind_iimpl(contract_id, older.impl_id()).unwrap());
+        stash.rollback_transaction();
+        assert_eq!(stash.iimpl_bindings(contract_id).unwrap().count(), 0);
+
+        assert!(stash.bind_iimpl(contract_id, older.impl_id()).unwrap());
+        assert!(!stash.bind_iimpl(contract_id, older.impl_id()).unwrap());
+        assert!(stash.bind_iimpl(contract_id, newer.impl_id()).unwrap());
+        let bound = stash
+            .iimpl_bindings(contract_id)
+            .unwrap()
+            .map(|(_, impl_id)| impl_id)
+            .collect::<Vec<_>>();
+        assert_eq!(bound, vec![older.impl_id(), newer.impl_id()]);
+
+        let mut unknown = newer.clone();
+        unknown.timestamp += 1;
+        assert!(matches!(
+            stash.bind_iimpl(contract_id, unknown.impl_id()),
+            Err(StashProviderError::Inconsistency(StashInconsistency::ImplAbsent(id)))
+                if id == unknown.impl_id()
+        ));
+
+        #[cfg(feature = "fs")]
+        {
+            use amplify::confinement::U32;
+            use strict_encoding::{StreamWriter, StrictEncode};
+
+            let path =
+                std::env::temp_dir().join(format!("rgb-std-iimpls-{}.dat", std::process::id()));
+            stash.set_filename(path.clone());
+            stash.store().unwrap();
+            let loaded = MemStash::load(path.clone()).unwrap();
+            assert_eq!(loaded.debug_superseded_iimpls(), stash.debug_superseded_iimpls());
+            assert_eq!(loaded.debug_bindings(), stash.debug_bindings());
+
+            // Layout version 1 keeps the bindings in a collection limited to
+            // 255 contracts
+            let bindings: TinyOrdMap<_, _> =
+                Confined::from_collection_unsafe(stash.debug_bindings().clone().release());
+            let mut data = b"RGBSTASH\x01".to_vec();
+            stash.strict_write(StreamWriter::new::<U32>(&mut data)).unwrap();
+            stash
+                .debug_archived()
+                .strict_write(StreamWriter::new::<U32>(&mut data))
+                .unwrap();
+            stash
+                .debug_superseded_iimpls()
+                .strict_write(StreamWriter::new::<U32>(&mut data))
+                .unwrap();
+            bindings
+                .strict_write(StreamWriter::new::<U32>(&mut data))
+                .unwrap();
+            std::fs::write(&path, data).unwrap();
+            let loaded = MemStash::load(path.clone()).unwrap();
+            assert_eq!(loaded.debug_superseded_iimpls(), stash.debug_superseded_iimpls());
+            assert_eq!(loaded.debug_bindings(), stash.debug_bindings());
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
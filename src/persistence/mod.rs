@@ -34,16 +34,34 @@ mod stock;
 mod stash;
 mod state;
 mod index;
+mod observer;
+mod nonblocking;
 
 mod memory;
 #[cfg(feature = "fs")]
 pub mod fs;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(test)]
+pub(crate) mod fixtures;
+
+use std::io;
+
+use amplify::confinement::U32;
+use strict_encoding::{StreamWriter, StrictEncode};
 
 pub use index::{
     Index, IndexError, IndexInconsistency, IndexProvider, IndexReadError, IndexReadProvider,
-    IndexWriteError, IndexWriteProvider,
+    IndexWriteError, IndexWriteProvider, Spender,
 };
 pub use memory::{MemContract, MemContractState, MemGlobalState, MemIndex, MemStash, MemState};
+pub use nonblocking::{
+    AsyncIndexReadProvider, AsyncStashReadProvider, AsyncStateReadProvider, AsyncStock,
+    AsyncStockError,
+};
+pub use observer::{StockEvent, StockObserver};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqlContractWriter, SqlError, SqlIndex, SqlStash, SqlState};
 pub use stash::{
     ProviderError as StashProviderError, SchemaIfaces, Stash, StashDataError, StashError,
     StashInconsistency, StashProvider, StashReadProvider, StashWriteProvider,
@@ -53,10 +71,29 @@ pub use state::{
     StateProvider, StateReadProvider, StateWriteProvider,
 };
 pub use stock::{
-    ComposeError, ConsignError, ContractIfaceError, FasciaError, InputError as StockInputError,
-    Stock, StockError, StockErrorAll, StockErrorMem, UpdateRes,
+    AllocationChange, BackupError, CheckReport, ComposeError, ConsignError, ContractIfaceError,
+    ContractStats, DisclosureError, FasciaError, GcReport, HistoryEntry, HistoryOpKind,
+    InputError as StockInputError, PurgeReport, Stock, StockError, StockErrorAll, StockErrorMem,
+    StockSnapshot, StockStats, UpdateRes,
 };
 
+/// Computes the size of the strict-serialized data.
+pub(crate) fn serialized_size(data: &impl StrictEncode) -> usize {
+    struct Counter(usize);
+    impl io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    let mut counter = Counter(0);
+    data.strict_write(StreamWriter::new::<U32>(&mut counter))
+        .expect("counter doesn't fail");
+    counter.0
+}
+
 pub trait StoreTransaction {
     type TransactionErr: std::error::Error;
 
@@ -0,0 +1,644 @@
+// RGB standard library for working with smart contracts on Bitcoin & Lightning
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Asynchronous counterparts of the read provider APIs, returning owned data
+//! such that they can be backed by networked databases or asynchronous
+//! key-value stores.
+//!
+//! Any synchronous stash and index provider which is [`Sync`] is also an
+//! asynchronous one, returning immediately ready futures. Since the contract
+//! state is borrowed from the synchronous state providers, the asynchronous
+//! state API is implemented for the providers which are able to detach it:
+//! [`MemState`] and `SqlState`.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::future::{self, Future};
+
+use aluvm::library::{Lib, LibId};
+use bp::dbc::tapret::TapretCommitment;
+use rgb::{
+    BundleId, ContractId, Extension, Genesis, GraphSeal, Identity, OpId, Opout, SchemaId,
+    TransitionBundle, XChain, XOutputSeal, XWitnessId,
+};
+use strict_types::TypeSystem;
+
+use super::{
+    ContractStateRead, IndexReadError, IndexReadProvider, MemContract, MemContractState, MemState,
+    SchemaIfaces, StashProviderError, StashReadProvider, StateInconsistency, StateReadProvider,
+};
+use crate::containers::{ContentId, ContentRef, ContentSigs, SealWitness, Supplement, TrustLevel};
+use crate::interface::{Iface, IfaceClass, IfaceImpl, IfaceRef};
+use crate::SecretSeal;
+
+pub trait AsyncStashReadProvider {
+    /// Error type which must indicate problems on data retrieval.
+    type Error: Clone + Eq + Error + Send;
+
+    fn type_system(&self) -> impl Future<Output = Result<TypeSystem, Self::Error>> + Send;
+    fn lib(
+        &self,
+        id: LibId,
+    ) -> impl Future<Output = Result<Lib, StashProviderError<Self::Error>>> + Send;
+
+    fn ifaces(&self) -> impl Future<Output = Result<Vec<Iface>, Self::Error>> + Send;
+    fn iface(
+        &self,
+        iface: IfaceRef,
+    ) -> impl Future<Output = Result<Iface, StashProviderError<Self::Error>>> + Send;
+    fn schemata(&self) -> impl Future<Output = Result<Vec<SchemaIfaces>, Self::Error>> + Send;
+    fn schema(
+        &self,
+        schema_id: SchemaId,
+    ) -> impl Future<Output = Result<SchemaIfaces, StashProviderError<Self::Error>>> + Send;
+    fn schemata_by<C: IfaceClass>(
+        &self,
+    ) -> impl Future<Output = Result<Vec<SchemaIfaces>, Self::Error>> + Send;
+    fn impl_for<C: IfaceClass + 'static>(
+        &self,
+        schema_ifaces: &SchemaIfaces,
+    ) -> impl Future<Output = Result<IfaceImpl, StashProviderError<Self::Error>>> + Send;
+    fn geneses(&self) -> impl Future<Output = Result<Vec<Genesis>, Self::Error>> + Send;
+    fn geneses_by<C: IfaceClass>(
+        &self,
+    ) -> impl Future<Output = Result<Vec<Genesis>, Self::Error>> + Send;
+    fn genesis(
+        &self,
+        contract_id: ContractId,
+    ) -> impl Future<Output = Result<Genesis, StashProviderError<Self::Error>>> + Send;
+    fn contract_schema(
+        &self,
+        contract_id: ContractId,
+    ) -> impl Future<Output = Result<SchemaIfaces, StashProviderError<Self::Error>>> + Send;
+
+    fn get_trust(
+        &self,
+        identity: &Identity,
+    ) -> impl Future<Output = Result<TrustLevel, Self::Error>> + Send;
+    fn supplement(
+        &self,
+        content_ref: ContentRef,
+    ) -> impl Future<Output = Result<Option<Supplement>, Self::Error>> + Send;
+    fn supplements(
+        &self,
+        content_ref: ContentRef,
+    ) -> impl Future<Output = Result<Vec<Supplement>, Self::Error>> + Send;
+
+    fn sigs_for(
+        &self,
+        content_id: &ContentId,
+    ) -> impl Future<Output = Result<Option<ContentSigs>, Self::Error>> + Send;
+    fn witness_ids(&self) -> impl Future<Output = Result<Vec<XWitnessId>, Self::Error>> + Send;
+    fn bundle_ids(&self) -> impl Future<Output = Result<Vec<BundleId>, Self::Error>> + Send;
+    fn bundle(
+        &self,
+        bundle_id: BundleId,
+    ) -> impl Future<Output = Result<TransitionBundle, StashProviderError<Self::Error>>> + Send;
+    fn extension_ids(&self) -> impl Future<Output = Result<Vec<OpId>, Self::Error>> + Send;
+    fn extension(
+        &self,
+        op_id: OpId,
+    ) -> impl Future<Output = Result<Extension, StashProviderError<Self::Error>>> + Send;
+    fn witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> impl Future<Output = Result<SealWitness, StashProviderError<Self::Error>>> + Send;
+
+    fn taprets(
+        &self,
+    ) -> impl Future<Output = Result<Vec<(XWitnessId, TapretCommitment)>, Self::Error>> + Send;
+    fn seal_secret(
+        &self,
+        secret: XChain<SecretSeal>,
+    ) -> impl Future<Output = Result<Option<XChain<GraphSeal>>, Self::Error>> + Send;
+    fn secret_seals(
+        &self,
+    ) -> impl Future<Output = Result<Vec<XChain<GraphSeal>>, Self::Error>> + Send;
+}
+
+pub trait AsyncStateReadProvider {
+    /// Owned contract state, which can be used after the future is resolved.
+    type ContractState: ContractStateRead + Send;
+    type Error: Clone + Eq + Error + Send;
+
+    fn contract_state(
+        &self,
+        contract_id: ContractId,
+    ) -> impl Future<Output = Result<Self::ContractState, Self::Error>> + Send;
+
+    fn is_valid_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> impl Future<Output = Result<bool, Self::Error>> + Send;
+
+    fn contract_ids(&self) -> impl Future<Output = Result<Vec<ContractId>, Self::Error>> + Send;
+}
+
+pub trait AsyncIndexReadProvider {
+    type Error: Clone + Eq + Error + Send;
+
+    fn contracts_assigning(
+        &self,
+        outputs: BTreeSet<XOutputSeal>,
+    ) -> impl Future<Output = Result<Vec<ContractId>, Self::Error>> + Send;
+
+    fn public_opouts(
+        &self,
+        contract_id: ContractId,
+    ) -> impl Future<Output = Result<BTreeSet<Opout>, IndexReadError<Self::Error>>> + Send;
+
+    fn opouts_by_outputs(
+        &self,
+        contract_id: ContractId,
+        outputs: BTreeSet<XOutputSeal>,
+    ) -> impl Future<Output = Result<BTreeSet<Opout>, IndexReadError<Self::Error>>> + Send;
+
+    fn opouts_by_terminals(
+        &self,
+        terminals: BTreeSet<XChain<SecretSeal>>,
+    ) -> impl Future<Output = Result<BTreeSet<Opout>, Self::Error>> + Send;
+
+    fn bundle_id_for_op(
+        &self,
+        opid: OpId,
+    ) -> impl Future<Output = Result<BundleId, IndexReadError<Self::Error>>> + Send;
+
+    fn bundle_info(
+        &self,
+        bundle_id: BundleId,
+    ) -> impl Future<
+        Output = Result<(Vec<XWitnessId>, ContractId), IndexReadError<Self::Error>>,
+    > + Send;
+
+    fn contract_ids(&self) -> impl Future<Output = Result<Vec<ContractId>, Self::Error>> + Send;
+
+    fn bundle_ids(&self) -> impl Future<Output = Result<Vec<BundleId>, Self::Error>> + Send;
+}
+
+impl<T: StashReadProvider + Sync> AsyncStashReadProvider for T
+where T::Error: Send
+{
+    type Error = T::Error;
+
+    fn type_system(&self) -> impl Future<Output = Result<TypeSystem, T::Error>> + Send {
+        future::ready(StashReadProvider::type_system(self).cloned())
+    }
+
+    fn lib(
+        &self,
+        id: LibId,
+    ) -> impl Future<Output = Result<Lib, StashProviderError<T::Error>>> + Send {
+        future::ready(StashReadProvider::lib(self, id).cloned())
+    }
+
+    fn ifaces(&self) -> impl Future<Output = Result<Vec<Iface>, T::Error>> + Send {
+        future::ready(StashReadProvider::ifaces(self).map(|iter| iter.cloned().collect()))
+    }
+
+    fn iface(
+        &self,
+        iface: IfaceRef,
+    ) -> impl Future<Output = Result<Iface, StashProviderError<T::Error>>> + Send {
+        future::ready(StashReadProvider::iface(self, iface).cloned())
+    }
+
+    fn schemata(&self) -> impl Future<Output = Result<Vec<SchemaIfaces>, T::Error>> + Send {
+        future::ready(StashReadProvider::schemata(self).map(|iter| iter.cloned().collect()))
+    }
+
+    fn schema(
+        &self,
+        schema_id: SchemaId,
+    ) -> impl Future<Output = Result<SchemaIfaces, StashProviderError<T::Error>>> + Send {
+        future::ready(StashReadProvider::schema(self, schema_id).cloned())
+    }
+
+    fn schemata_by<C: IfaceClass>(
+        &self,
+    ) -> impl Future<Output = Result<Vec<SchemaIfaces>, T::Error>> + Send {
+        future::ready(StashReadProvider::schemata_by::<C>(self).map(|iter| iter.cloned().collect()))
+    }
+
+    fn impl_for<C: IfaceClass + 'static>(
+        &self,
+        schema_ifaces: &SchemaIfaces,
+    ) -> impl Future<Output = Result<IfaceImpl, StashProviderError<T::Error>>> + Send {
+        future::ready(StashReadProvider::impl_for::<C>(self, schema_ifaces).cloned())
+    }
+
+    fn geneses(&self) -> impl Future<Output = Result<Vec<Genesis>, T::Error>> + Send {
+        future::ready(StashReadProvider::geneses(self).map(|iter| iter.cloned().collect()))
+    }
+
+    fn geneses_by<C: IfaceClass>(
+        &self,
+    ) -> impl Future<Output = Result<Vec<Genesis>, T::Error>> + Send {
+        future::ready(StashReadProvider::geneses_by::<C>(self).map(|iter| iter.cloned().collect()))
+    }
+
+    fn genesis(
+        &self,
+        contract_id: ContractId,
+    ) -> impl Future<Output = Result<Genesis, StashProviderError<T::Error>>> + Send {
+        future::ready(StashReadProvider::genesis(self, contract_id).cloned())
+    }
+
+    fn contract_schema(
+        &self,
+        contract_id: ContractId,
+    ) -> impl Future<Output = Result<SchemaIfaces, StashProviderError<T::Error>>> + Send {
+        future::ready(StashReadProvider::contract_schema(self, contract_id).cloned())
+    }
+
+    fn get_trust(
+        &self,
+        identity: &Identity,
+    ) -> impl Future<Output = Result<TrustLevel, T::Error>> + Send {
+        future::ready(StashReadProvider::get_trust(self, identity))
+    }
+
+    fn supplement(
+        &self,
+        content_ref: ContentRef,
+    ) -> impl Future<Output = Result<Option<Supplement>, T::Error>> + Send {
+        future::ready(StashReadProvider::supplement(self, content_ref).map(Option::<&_>::cloned))
+    }
+
+    fn supplements(
+        &self,
+        content_ref: ContentRef,
+    ) -> impl Future<Output = Result<Vec<Supplement>, T::Error>> + Send {
+        future::ready(StashReadProvider::supplements(self, content_ref).map(Iterator::collect))
+    }
+
+    fn sigs_for(
+        &self,
+        content_id: &ContentId,
+    ) -> impl Future<Output = Result<Option<ContentSigs>, T::Error>> + Send {
+        future::ready(StashReadProvider::sigs_for(self, content_id).map(Option::<&_>::cloned))
+    }
+
+    fn witness_ids(&self) -> impl Future<Output = Result<Vec<XWitnessId>, T::Error>> + Send {
+        future::ready(StashReadProvider::witness_ids(self).map(Iterator::collect))
+    }
+
+    fn bundle_ids(&self) -> impl Future<Output = Result<Vec<BundleId>, T::Error>> + Send {
+        future::ready(StashReadProvider::bundle_ids(self).map(Iterator::collect))
+    }
+
+    fn bundle(
+        &self,
+        bundle_id: BundleId,
+    ) -> impl Future<Output = Result<TransitionBundle, StashProviderError<T::Error>>> + Send {
+        future::ready(StashReadProvider::bundle(self, bundle_id).cloned())
+    }
+
+    fn extension_ids(&self) -> impl Future<Output = Result<Vec<OpId>, T::Error>> + Send {
+        future::ready(StashReadProvider::extension_ids(self).map(Iterator::collect))
+    }
+
+    fn extension(
+        &self,
+        op_id: OpId,
+    ) -> impl Future<Output = Result<Extension, StashProviderError<T::Error>>> + Send {
+        future::ready(StashReadProvider::extension(self, op_id).cloned())
+    }
+
+    fn witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> impl Future<Output = Result<SealWitness, StashProviderError<T::Error>>> + Send {
+        future::ready(StashReadProvider::witness(self, witness_id).cloned())
+    }
+
+    fn taprets(
+        &self,
+    ) -> impl Future<Output = Result<Vec<(XWitnessId, TapretCommitment)>, T::Error>> + Send {
+        future::ready(StashReadProvider::taprets(self).map(Iterator::collect))
+    }
+
+    fn seal_secret(
+        &self,
+        secret: XChain<SecretSeal>,
+    ) -> impl Future<Output = Result<Option<XChain<GraphSeal>>, T::Error>> + Send {
+        future::ready(StashReadProvider::seal_secret(self, secret))
+    }
+
+    fn secret_seals(
+        &self,
+    ) -> impl Future<Output = Result<Vec<XChain<GraphSeal>>, T::Error>> + Send {
+        future::ready(StashReadProvider::secret_seals(self).map(Iterator::collect))
+    }
+}
+
+impl AsyncStateReadProvider for MemState {
+    type ContractState = MemContract<MemContractState>;
+    type Error = StateInconsistency;
+
+    fn contract_state(
+        &self,
+        contract_id: ContractId,
+    ) -> impl Future<Output = Result<Self::ContractState, Self::Error>> + Send {
+        future::ready(StateReadProvider::contract_state(self, contract_id).map(|c| c.detach()))
+    }
+
+    fn is_valid_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> impl Future<Output = Result<bool, Self::Error>> + Send {
+        future::ready(StateReadProvider::is_valid_witness(self, witness_id))
+    }
+
+    fn contract_ids(&self) -> impl Future<Output = Result<Vec<ContractId>, Self::Error>> + Send {
+        future::ready(StateReadProvider::contract_ids(self).map(Iterator::collect))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl AsyncStateReadProvider for super::SqlState {
+    type ContractState = MemContract<MemContractState>;
+    type Error = super::SqlError;
+
+    fn contract_state(
+        &self,
+        contract_id: ContractId,
+    ) -> impl Future<Output = Result<Self::ContractState, Self::Error>> + Send {
+        future::ready(StateReadProvider::contract_state(self, contract_id))
+    }
+
+    fn is_valid_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> impl Future<Output = Result<bool, Self::Error>> + Send {
+        future::ready(StateReadProvider::is_valid_witness(self, witness_id))
+    }
+
+    fn contract_ids(&self) -> impl Future<Output = Result<Vec<ContractId>, Self::Error>> + Send {
+        future::ready(StateReadProvider::contract_ids(self).map(Iterator::collect))
+    }
+}
+
+impl<T: IndexReadProvider + Sync> AsyncIndexReadProvider for T
+where T::Error: Send
+{
+    type Error = T::Error;
+
+    fn contracts_assigning(
+        &self,
+        outputs: BTreeSet<XOutputSeal>,
+    ) -> impl Future<Output = Result<Vec<ContractId>, T::Error>> + Send {
+        future::ready(IndexReadProvider::contracts_assigning(self, outputs).map(Iterator::collect))
+    }
+
+    fn public_opouts(
+        &self,
+        contract_id: ContractId,
+    ) -> impl Future<Output = Result<BTreeSet<Opout>, IndexReadError<T::Error>>> + Send {
+        future::ready(IndexReadProvider::public_opouts(self, contract_id))
+    }
+
+    fn opouts_by_outputs(
+        &self,
+        contract_id: ContractId,
+        outputs: BTreeSet<XOutputSeal>,
+    ) -> impl Future<Output = Result<BTreeSet<Opout>, IndexReadError<T::Error>>> + Send {
+        future::ready(IndexReadProvider::opouts_by_outputs(self, contract_id, outputs))
+    }
+
+    fn opouts_by_terminals(
+        &self,
+        terminals: BTreeSet<XChain<SecretSeal>>,
+    ) -> impl Future<Output = Result<BTreeSet<Opout>, T::Error>> + Send {
+        future::ready(IndexReadProvider::opouts_by_terminals(self, terminals))
+    }
+
+    fn bundle_id_for_op(
+        &self,
+        opid: OpId,
+    ) -> impl Future<Output = Result<BundleId, IndexReadError<T::Error>>> + Send {
+        future::ready(IndexReadProvider::bundle_id_for_op(self, opid))
+    }
+
+    fn bundle_info(
+        &self,
+        bundle_id: BundleId,
+    ) -> impl Future<
+        Output = Result<(Vec<XWitnessId>, ContractId), IndexReadError<T::Error>>,
+    > + Send {
+        future::ready(
+            IndexReadProvider::bundle_info(self, bundle_id)
+                .map(|(witness_ids, contract_id)| (witness_ids.collect(), contract_id)),
+        )
+    }
+
+    fn contract_ids(&self) -> impl Future<Output = Result<Vec<ContractId>, T::Error>> + Send {
+        future::ready(IndexReadProvider::contract_ids(self).map(Iterator::collect))
+    }
+
+    fn bundle_ids(&self) -> impl Future<Output = Result<Vec<BundleId>, T::Error>> + Send {
+        future::ready(IndexReadProvider::bundle_ids(self).map(Iterator::collect))
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(inner)]
+pub enum AsyncStockError<E1: Error, E2: Error, E3: Error> {
+    Stash(StashProviderError<E1>),
+    State(E2),
+    Index(IndexReadError<E3>),
+}
+
+/// Read-only asynchronous facade over the stock providers.
+///
+/// Updates to the stock are performed with the [`super::Stock`] API, since
+/// they require consistency across all providers.
+#[derive(Debug)]
+pub struct AsyncStock<
+    S: AsyncStashReadProvider,
+    H: AsyncStateReadProvider,
+    P: AsyncIndexReadProvider,
+> {
+    stash: S,
+    state: H,
+    index: P,
+}
+
+type AsyncStockResult<T, S, H, P> = Result<
+    T,
+    AsyncStockError<
+        <S as AsyncStashReadProvider>::Error,
+        <H as AsyncStateReadProvider>::Error,
+        <P as AsyncIndexReadProvider>::Error,
+    >,
+>;
+
+impl<S: AsyncStashReadProvider, H: AsyncStateReadProvider, P: AsyncIndexReadProvider>
+    AsyncStock<S, H, P>
+{
+    pub fn with(stash_provider: S, state_provider: H, index_provider: P) -> Self {
+        AsyncStock {
+            stash: stash_provider,
+            state: state_provider,
+            index: index_provider,
+        }
+    }
+
+    pub fn as_stash_provider(&self) -> &S { &self.stash }
+    pub fn as_state_provider(&self) -> &H { &self.state }
+    pub fn as_index_provider(&self) -> &P { &self.index }
+
+    pub async fn schemata(&self) -> AsyncStockResult<Vec<SchemaIfaces>, S, H, P> {
+        self.stash
+            .schemata()
+            .await
+            .map_err(|err| AsyncStockError::Stash(StashProviderError::Connectivity(err)))
+    }
+
+    pub async fn contract_ids(&self) -> AsyncStockResult<Vec<ContractId>, S, H, P> {
+        self.state.contract_ids().await.map_err(AsyncStockError::State)
+    }
+
+    pub async fn genesis(&self, contract_id: ContractId) -> AsyncStockResult<Genesis, S, H, P> {
+        self.stash
+            .genesis(contract_id)
+            .await
+            .map_err(AsyncStockError::Stash)
+    }
+
+    pub async fn contract_schema(
+        &self,
+        contract_id: ContractId,
+    ) -> AsyncStockResult<SchemaIfaces, S, H, P> {
+        self.stash
+            .contract_schema(contract_id)
+            .await
+            .map_err(AsyncStockError::Stash)
+    }
+
+    pub async fn contract_state(
+        &self,
+        contract_id: ContractId,
+    ) -> AsyncStockResult<H::ContractState, S, H, P> {
+        self.state
+            .contract_state(contract_id)
+            .await
+            .map_err(AsyncStockError::State)
+    }
+
+    pub async fn contracts_assigning(
+        &self,
+        outputs: impl IntoIterator<Item = impl Into<XOutputSeal>>,
+    ) -> AsyncStockResult<Vec<ContractId>, S, H, P> {
+        let outputs = outputs.into_iter().map(|o| o.into()).collect();
+        self.index
+            .contracts_assigning(outputs)
+            .await
+            .map_err(|err| AsyncStockError::Index(IndexReadError::Connectivity(err)))
+    }
+
+    pub async fn witness(&self, witness_id: XWitnessId) -> AsyncStockResult<SealWitness, S, H, P> {
+        self.stash
+            .witness(witness_id)
+            .await
+            .map_err(AsyncStockError::Stash)
+    }
+
+    pub async fn is_valid_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> AsyncStockResult<bool, S, H, P> {
+        self.state
+            .is_valid_witness(witness_id)
+            .await
+            .map_err(AsyncStockError::State)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    use rgb::Schema;
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::persistence::{MemIndex, MemStash, StateWriteProvider};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    // In-memory providers return ready futures, so there is no need for an
+    // executor
+    fn resolve<F: Future + Send>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        match pin!(future).poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("in-memory providers must not block"),
+        }
+    }
+
+    #[test]
+    fn mem_providers() {
+        let genesis = Genesis::strict_dumb();
+        let contract_id = genesis.contract_id();
+        let mut state = MemState::in_memory();
+        state
+            .register_contract(&Schema::strict_dumb(), &genesis)
+            .unwrap();
+
+        let stock = AsyncStock::with(MemStash::in_memory(), state, MemIndex::in_memory());
+        assert_eq!(resolve(stock.contract_ids()).unwrap(), vec![contract_id]);
+        let contract = resolve(stock.contract_state(contract_id)).unwrap();
+        assert_eq!(contract.contract_id(), contract_id);
+        assert!(resolve(stock.genesis(contract_id)).is_err());
+        assert!(resolve(stock.schemata()).unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn sql_providers() {
+        use crate::persistence::{SqlIndex, SqlStash, SqlState, StoreTransaction};
+
+        let genesis = Genesis::strict_dumb();
+        let contract_id = genesis.contract_id();
+        let mut state = SqlState::in_memory().unwrap();
+        state.begin_transaction().unwrap();
+        state
+            .register_contract(&Schema::strict_dumb(), &genesis)
+            .unwrap();
+        state.commit_transaction().unwrap();
+
+        let stock = AsyncStock::with(
+            SqlStash::in_memory().unwrap(),
+            state,
+            SqlIndex::in_memory().unwrap(),
+        );
+        assert_eq!(resolve(stock.contract_ids()).unwrap(), vec![contract_id]);
+        let contract = resolve(stock.contract_state(contract_id)).unwrap();
+        assert_eq!(contract.contract_id(), contract_id);
+        assert!(resolve(stock.genesis(contract_id)).is_err());
+    }
+}
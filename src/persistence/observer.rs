@@ -0,0 +1,100 @@
+// RGB standard library for working with smart contracts on Bitcoin & Lightning
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::{self, Debug, Formatter};
+
+use rgb::{ContractId, GraphSeal, SchemaId, XChain, XWitnessId};
+
+use super::{GcReport, PurgeReport, UpdateRes};
+use crate::containers::{BackupId, DisclosureId};
+use crate::interface::{IfaceId, ImplId};
+
+/// Events emitted by [`super::Stock`] once the changes are committed to all of
+/// its providers.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum StockEvent {
+    /// Interfaces, schemata and interface implementations were imported from
+    /// a kit.
+    KitImported {
+        ifaces: Vec<IfaceId>,
+        schemata: Vec<SchemaId>,
+        iimpls: Vec<ImplId>,
+    },
+
+    /// New contract was imported.
+    ContractImported(ContractId),
+
+    /// Transfer under a contract was accepted.
+    TransferAccepted(ContractId),
+
+    /// Fascia of a witness transaction was consumed, updating the listed
+    /// contracts.
+    FasciaConsumed {
+        witness_id: XWitnessId,
+        contracts: Vec<ContractId>,
+    },
+
+    /// New secret seal was stored.
+    SecretSealStored(XChain<GraphSeal>),
+
+    /// Mining status of the witness transactions was updated.
+    WitnessesUpdated(UpdateRes),
+
+    /// Contract was moved to the archive (if `true`) or restored from it.
+    ContractArchived(ContractId, bool),
+
+    /// Contract was bound to a different interface implementation.
+    ContractRebound {
+        contract_id: ContractId,
+        impl_id: ImplId,
+    },
+
+    /// Contract and all the data exclusively owned by it were removed.
+    ContractPurged(PurgeReport),
+
+    /// Data unreachable from the contracts were removed from the stash.
+    GarbageCollected(GcReport),
+
+    /// Backup was imported, updating the listed contracts.
+    BackupImported {
+        backup_id: BackupId,
+        contracts: Vec<ContractId>,
+    },
+
+    /// Disclosure was accepted, revealing state under the listed contracts.
+    DisclosureAccepted {
+        disclosure_id: DisclosureId,
+        contracts: Vec<ContractId>,
+    },
+}
+
+/// Observer of the changes in [`super::Stock`], which can be registered with
+/// [`super::Stock::add_observer`].
+///
+/// Events are delivered synchronously, after the corresponding transaction is
+/// committed, so observers should not perform any lengthy operations.
+pub trait StockObserver: Send + Sync {
+    fn on_event(&self, event: &StockEvent);
+}
+
+impl Debug for dyn StockObserver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { f.write_str("StockObserver { .. }") }
+}
@@ -0,0 +1,2481 @@
+// RGB standard library for working with smart contracts on Bitcoin & Lightning
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2024 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SQLite-backed persistence providers.
+//!
+//! The providers serve all read and write requests with SQL queries, keeping
+//! data in tables per entity kind. Contract state and index entries are kept
+//! as separate rows, such that each update writes only the rows it changes and
+//! each read loads only the rows it needs. Since the stash API returns
+//! references to the stored data, the stash keeps data which it has read from
+//! the database in a cache, which is dropped on each stash modification.
+//!
+//! Stash, state and index are kept in separate database files, such that each
+//! of them can be re-created (or backed up) independently. When opened by
+//! [`Stock::open_sqlite`], the files are attached to a single connection and
+//! the providers share its transactions: data written by all of them within a
+//! stock transaction are committed to the databases atomically.
+
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use aluvm::library::{Lib, LibId};
+use amplify::confinement::{self, Confined, MediumBlob, TinyOrdMap, U32};
+use amplify::num::u24;
+use bp::dbc::tapret::TapretCommitment;
+use commit_verify::{CommitId, Conceal};
+use rgb::validation::ResolveWitness;
+use rgb::vm::{OrdOpRef, WitnessOrd};
+use rgb::{
+    Assign, AssignmentType, AttachId, BundleId, ContractId, DataState, ExposedSeal, ExposedState,
+    Extension, Genesis, GenesisSeal, GlobalStateType, GraphSeal, Identity, Layer1, OpId, Operation,
+    Opout, Schema, SchemaId, SecretSeal, Transition, TransitionBundle, XChain, XOutputSeal,
+    XWitnessId,
+};
+use rusqlite::{params, Connection, Params, Row};
+use strict_encoding::{
+    DecodeError, SerializeError, StreamReader, StreamWriter, StrictDecode, StrictEncode, TypeName,
+};
+use strict_types::TypeSystem;
+
+use super::memory::StateItem;
+use super::{
+    ContractIfaceError, ContractStateWrite, IndexInconsistency, IndexProvider, IndexReadError,
+    IndexReadProvider, IndexWriteError, IndexWriteProvider, MemContract, MemContractState,
+    SchemaIfaces, Spender, StashInconsistency, StashProvider, StashProviderError,
+    StashReadProvider, StashWriteProvider, StateInconsistency, StateProvider, StateReadProvider,
+    StateWriteProvider, Stock, StoreTransaction, UpdateRes,
+};
+use crate::containers::{
+    AnchorSet, ContentId, ContentRef, ContentSigs, SealWitness, SigBlob, Supplement, TrustLevel,
+};
+use crate::contract::GlobalOut;
+use crate::interface::{Iface, IfaceClass, IfaceId, IfaceImpl, IfaceRef, ImplId};
+
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum SqlError {
+    /// SQLite database error: {0}
+    Sqlite(String),
+
+    /// unable to encode data for storing them in the database: {0}
+    Encoding(String),
+
+    /// database contains data which can't be decoded: {0}
+    Decoding(String),
+
+    /// {0}
+    State(StateInconsistency),
+}
+
+impl From<rusqlite::Error> for SqlError {
+    fn from(err: rusqlite::Error) -> Self { SqlError::Sqlite(err.to_string()) }
+}
+
+impl From<io::Error> for SqlError {
+    fn from(err: io::Error) -> Self { SqlError::Encoding(err.to_string()) }
+}
+
+impl From<SerializeError> for SqlError {
+    fn from(err: SerializeError) -> Self { SqlError::Encoding(err.to_string()) }
+}
+
+impl From<confinement::Error> for SqlError {
+    fn from(err: confinement::Error) -> Self { SqlError::Encoding(err.to_string()) }
+}
+
+impl From<DecodeError> for SqlError {
+    fn from(err: DecodeError) -> Self { SqlError::Decoding(err.to_string()) }
+}
+
+impl From<StateInconsistency> for SqlError {
+    fn from(err: StateInconsistency) -> Self { SqlError::State(err) }
+}
+
+impl From<SqlError> for StashProviderError<SqlError> {
+    fn from(err: SqlError) -> Self { StashProviderError::Connectivity(err) }
+}
+
+impl From<SqlError> for IndexReadError<SqlError> {
+    fn from(err: SqlError) -> Self { IndexReadError::Connectivity(err) }
+}
+
+impl From<SqlError> for IndexWriteError<SqlError> {
+    fn from(err: SqlError) -> Self { IndexWriteError::Connectivity(err) }
+}
+
+/// Tables keyed by strict-encoded identifiers of the data they keep in their
+/// `key` and `value` columns.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Table {
+    // Stash
+    Schemata,
+    Ifaces,
+    Iimpls,
+    Geneses,
+    Bundles,
+    Extensions,
+    SealWitnesses,
+    Attachments,
+    SecretSeals,
+    Types,
+    Identities,
+    Libs,
+    Sigs,
+    Archived,
+
+    // State
+    Witnesses,
+
+    // Index
+    IndexedContracts,
+    OpBundles,
+    BundleContracts,
+    Terminals,
+}
+
+impl Table {
+    // Tables with no columns other than the key and value; the rest of the
+    // tables are created by the provider-specific SQL
+    const STASH: [Table; 10] = [
+        Table::Schemata,
+        Table::Bundles,
+        Table::Extensions,
+        Table::SealWitnesses,
+        Table::Attachments,
+        Table::Types,
+        Table::Identities,
+        Table::Libs,
+        Table::Sigs,
+        Table::Archived,
+    ];
+    const STATE: [Table; 1] = [Table::Witnesses];
+    const INDEX: [Table; 3] = [Table::IndexedContracts, Table::OpBundles, Table::BundleContracts];
+
+    fn name(self) -> &'static str {
+        match self {
+            Table::Schemata => "schemata",
+            Table::Ifaces => "ifaces",
+            Table::Iimpls => "iimpls",
+            Table::Geneses => "geneses",
+            Table::Bundles => "bundles",
+            Table::Extensions => "extensions",
+            Table::SealWitnesses => "seal_witnesses",
+            Table::Attachments => "attachments",
+            Table::SecretSeals => "secret_seals",
+            Table::Types => "types",
+            Table::Identities => "identities",
+            Table::Libs => "libs",
+            Table::Sigs => "sigs",
+            Table::Archived => "archived",
+            Table::Witnesses => "witnesses",
+            Table::IndexedContracts => "contracts",
+            Table::OpBundles => "op_bundles",
+            Table::BundleContracts => "bundle_contracts",
+            Table::Terminals => "terminals",
+        }
+    }
+}
+
+const STASH_TABLES: &str = "
+    CREATE TABLE IF NOT EXISTS {db}.ifaces (
+        key BLOB PRIMARY KEY,
+        name BLOB NOT NULL,
+        value BLOB NOT NULL
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS {db}.ifaces_name ON ifaces (name);
+    CREATE TABLE IF NOT EXISTS {db}.iimpls (
+        key BLOB PRIMARY KEY,
+        schema_id BLOB NOT NULL,
+        iface BLOB NOT NULL,
+        superseded INTEGER NOT NULL,
+        value BLOB NOT NULL
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS {db}.iimpls_schema ON iimpls (schema_id, iface);
+    CREATE TABLE IF NOT EXISTS {db}.geneses (
+        key BLOB PRIMARY KEY,
+        schema_id BLOB NOT NULL,
+        value BLOB NOT NULL
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS {db}.geneses_schema ON geneses (schema_id);
+    CREATE TABLE IF NOT EXISTS {db}.secret_seals (
+        key BLOB PRIMARY KEY,
+        secret BLOB NOT NULL
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS {db}.secret_seals_secret ON secret_seals (secret);
+    CREATE TABLE IF NOT EXISTS {db}.supplements (
+        content_ref BLOB NOT NULL,
+        value BLOB NOT NULL,
+        PRIMARY KEY (content_ref, value)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS {db}.bindings (
+        contract_id BLOB NOT NULL,
+        iface BLOB NOT NULL,
+        seq INTEGER NOT NULL,
+        impl_id BLOB NOT NULL,
+        PRIMARY KEY (contract_id, iface, seq)
+    ) WITHOUT ROWID;";
+
+const STATE_TABLES: &str = "
+    CREATE TABLE IF NOT EXISTS {db}.contracts (
+        id BLOB PRIMARY KEY,
+        schema_id BLOB NOT NULL,
+        limits BLOB NOT NULL
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS {db}.global_state (
+        contract_id BLOB NOT NULL,
+        ty BLOB NOT NULL,
+        out BLOB NOT NULL,
+        witness_id BLOB,
+        data BLOB NOT NULL,
+        PRIMARY KEY (contract_id, ty, out)
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS {db}.global_state_witness ON global_state (witness_id);
+    CREATE TABLE IF NOT EXISTS {db}.assignments (
+        contract_id BLOB NOT NULL,
+        opout BLOB NOT NULL,
+        kind INTEGER NOT NULL,
+        witness_id BLOB,
+        value BLOB NOT NULL,
+        PRIMARY KEY (contract_id, opout)
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS {db}.assignments_witness ON assignments (witness_id);";
+
+const INDEX_TABLES: &str = "
+    CREATE TABLE IF NOT EXISTS {db}.outpoint_opouts (
+        contract_id BLOB NOT NULL,
+        output BLOB NOT NULL,
+        opout BLOB NOT NULL,
+        PRIMARY KEY (contract_id, output, opout)
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS {db}.outpoint_opouts_output ON outpoint_opouts (output);
+    CREATE TABLE IF NOT EXISTS {db}.bundle_witnesses (
+        bundle_id BLOB NOT NULL,
+        witness_id BLOB NOT NULL,
+        PRIMARY KEY (bundle_id, witness_id)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS {db}.terminals (
+        key BLOB PRIMARY KEY,
+        opid BLOB NOT NULL,
+        value BLOB NOT NULL
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS {db}.terminals_opid ON terminals (opid);
+    CREATE TABLE IF NOT EXISTS {db}.spenders (
+        opout BLOB NOT NULL,
+        opid BLOB NOT NULL,
+        spender BLOB NOT NULL,
+        PRIMARY KEY (opout, spender)
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS {db}.spenders_opid ON spenders (opid);";
+
+fn encode(data: &impl StrictEncode) -> Result<Vec<u8>, SqlError> {
+    let mut buf = Vec::new();
+    data.strict_write(StreamWriter::new::<U32>(&mut buf))?;
+    Ok(buf)
+}
+
+fn decode<T: StrictDecode>(data: &[u8]) -> Result<T, SqlError> {
+    Ok(T::strict_read(StreamReader::new::<U32>(data))?)
+}
+
+fn column<T: StrictDecode>(row: &Row, idx: usize) -> Result<T, SqlError> {
+    decode(&row.get::<_, Vec<u8>>(idx)?)
+}
+
+#[derive(Debug)]
+struct Conn {
+    inner: Connection,
+    /// Number of providers participating in the currently open transaction.
+    depth: usize,
+    /// Counter of the rolled back transactions, allowing providers to detect
+    /// that the data they have read within a transaction may be stale.
+    epoch: u64,
+}
+
+/// Database connection, which may be shared by several providers keeping
+/// their tables in different (attached) databases.
+#[derive(Clone, Debug)]
+struct Db {
+    conn: Arc<Mutex<Conn>>,
+    schema: &'static str,
+}
+
+impl Db {
+    fn connect(conn: Connection) -> Arc<Mutex<Conn>> {
+        Arc::new(Mutex::new(Conn {
+            inner: conn,
+            depth: 0,
+            epoch: 0,
+        }))
+    }
+
+    fn with(
+        conn: Arc<Mutex<Conn>>,
+        schema: &'static str,
+        tables: &[Table],
+        sql: &str,
+    ) -> Result<Self, SqlError> {
+        let db = Db { conn, schema };
+        let mut batch = db.sql(sql);
+        for table in tables {
+            batch.push_str(&db.sql(&format!(
+                "CREATE TABLE IF NOT EXISTS {{db}}.{} (
+                    key BLOB PRIMARY KEY,
+                    value BLOB NOT NULL
+                ) WITHOUT ROWID;",
+                table.name()
+            )));
+        }
+        db.lock().inner.execute_batch(&batch)?;
+        Ok(db)
+    }
+
+    fn lock(&self) -> MutexGuard<Conn> { self.conn.lock().unwrap_or_else(PoisonError::into_inner) }
+
+    fn sql(&self, sql: &str) -> String { sql.replace("{db}", self.schema) }
+
+    /// Executes the statement, returning the number of the changed rows.
+    fn execute(&self, sql: &str, params: impl Params) -> Result<usize, SqlError> {
+        Ok(self
+            .lock()
+            .inner
+            .prepare_cached(&self.sql(sql))?
+            .execute(params)?)
+    }
+
+    fn select<T>(
+        &self,
+        sql: &str,
+        params: impl Params,
+        mut f: impl FnMut(&Row) -> Result<T, SqlError>,
+    ) -> Result<Vec<T>, SqlError> {
+        let conn = self.lock();
+        let mut stmt = conn.inner.prepare_cached(&self.sql(sql))?;
+        let mut rows = stmt.query(params)?;
+        let mut res = vec![];
+        while let Some(row) = rows.next()? {
+            res.push(f(row)?);
+        }
+        Ok(res)
+    }
+
+    fn select_first<T>(
+        &self,
+        sql: &str,
+        params: impl Params,
+        f: impl FnMut(&Row) -> Result<T, SqlError>,
+    ) -> Result<Option<T>, SqlError> {
+        Ok(self.select(sql, params, f)?.into_iter().next())
+    }
+
+    fn get<V: StrictDecode>(
+        &self,
+        table: Table,
+        key: &impl StrictEncode,
+    ) -> Result<Option<V>, SqlError> {
+        self.select_first(
+            &format!("SELECT value FROM {{db}}.{} WHERE key = ?1", table.name()),
+            params![encode(key)?],
+            |row| column(row, 0),
+        )
+    }
+
+    fn contains(&self, table: Table, key: &impl StrictEncode) -> Result<bool, SqlError> {
+        let found = self.select_first(
+            &format!("SELECT 1 FROM {{db}}.{} WHERE key = ?1", table.name()),
+            params![encode(key)?],
+            |_| Ok(()),
+        )?;
+        Ok(found.is_some())
+    }
+
+    fn keys<K: StrictDecode>(&self, table: Table) -> Result<Vec<K>, SqlError> {
+        self.select(&format!("SELECT key FROM {{db}}.{} ORDER BY key", table.name()), [], |row| {
+            column(row, 0)
+        })
+    }
+
+    fn entries<K: StrictDecode, V: StrictDecode>(
+        &self,
+        table: Table,
+    ) -> Result<Vec<(K, V)>, SqlError> {
+        self.select(
+            &format!("SELECT key, value FROM {{db}}.{} ORDER BY key", table.name()),
+            [],
+            |row| Ok((column(row, 0)?, column(row, 1)?)),
+        )
+    }
+
+    fn put(
+        &self,
+        table: Table,
+        key: &impl StrictEncode,
+        value: &impl StrictEncode,
+    ) -> Result<(), SqlError> {
+        self.execute(
+            &format!("INSERT OR REPLACE INTO {{db}}.{} (key, value) VALUES (?1, ?2)", table.name()),
+            params![encode(key)?, encode(value)?],
+        )?;
+        Ok(())
+    }
+
+    /// Puts the value under the key, returning whether the key was not known
+    /// before.
+    fn replace(
+        &self,
+        table: Table,
+        key: &impl StrictEncode,
+        value: &impl StrictEncode,
+    ) -> Result<bool, SqlError> {
+        let present = self.contains(table, key)?;
+        self.put(table, key, value)?;
+        Ok(!present)
+    }
+
+    /// Adds the key with no value, returning whether it was not known before.
+    fn put_key(&self, table: Table, key: &impl StrictEncode) -> Result<bool, SqlError> {
+        let added = self.execute(
+            &format!("INSERT OR IGNORE INTO {{db}}.{} (key, value) VALUES (?1, x'')", table.name()),
+            params![encode(key)?],
+        )?;
+        Ok(added > 0)
+    }
+
+    /// Deletes the key, returning whether it was known.
+    fn delete(&self, table: Table, key: &impl StrictEncode) -> Result<bool, SqlError> {
+        let deleted = self.execute(
+            &format!("DELETE FROM {{db}}.{} WHERE key = ?1", table.name()),
+            params![encode(key)?],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    /// Size of the database file.
+    fn size(&self) -> Result<usize, SqlError> {
+        let pragma = |name: &str| -> Result<i64, SqlError> {
+            let value = self.select_first(&format!("PRAGMA {{db}}.{name}"), [], |row| {
+                Ok(row.get::<_, i64>(0)?)
+            })?;
+            Ok(value.unwrap_or_default())
+        };
+        Ok((pragma("page_count")? * pragma("page_size")?) as usize)
+    }
+
+    /// All rows of all the tables in the database, ordered by table name and
+    /// row values.
+    #[cfg(test)]
+    fn rows(&self) -> Result<Vec<String>, SqlError> {
+        let tables = self.select(
+            "SELECT name FROM {db}.sqlite_master WHERE type = 'table' ORDER BY name",
+            [],
+            |row| Ok(row.get::<_, String>(0)?),
+        )?;
+        let mut rows = vec![];
+        for table in tables {
+            let mut values = self.select(&format!("SELECT * FROM {{db}}.{table}"), [], |row| {
+                let values = (0..row.as_ref().column_count())
+                    .map(|idx| row.get::<_, rusqlite::types::Value>(idx))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("{table}: {values:?}"))
+            })?;
+            values.sort();
+            rows.extend(values);
+        }
+        Ok(rows)
+    }
+
+    fn epoch(&self) -> u64 { self.lock().epoch }
+
+    /// Joins the shared transaction, starting it if necessary.
+    fn begin(&self) -> Result<(), SqlError> {
+        let mut conn = self.lock();
+        if conn.depth == 0 {
+            conn.inner.execute_batch("BEGIN IMMEDIATE")?;
+        }
+        conn.depth += 1;
+        Ok(())
+    }
+
+    /// Leaves the shared transaction, committing it once all the participating
+    /// providers have committed their parts.
+    fn commit(&self) -> Result<(), SqlError> {
+        let mut conn = self.lock();
+        match conn.depth {
+            0 => {}
+            1 => {
+                conn.depth = 0;
+                if let Err(err) = conn.inner.execute_batch("COMMIT") {
+                    conn.abort();
+                    return Err(err.into());
+                }
+            }
+            _ => conn.depth -= 1,
+        }
+        Ok(())
+    }
+
+    fn rollback(&self) {
+        let mut conn = self.lock();
+        if conn.depth > 0 {
+            conn.depth = 0;
+            conn.abort();
+        }
+    }
+}
+
+impl Conn {
+    fn abort(&mut self) {
+        if !self.inner.is_autocommit() {
+            // If rollback fails SQLite has already rolled back the transaction itself
+            self.inner.execute_batch("ROLLBACK").ok();
+        }
+        self.epoch += 1;
+    }
+}
+
+//////////
+// STASH
+//////////
+
+/// Data decoded from the database rows, which are kept for the lifetime of
+/// the references returned by the stash.
+///
+/// The values are tagged with the connection epoch at the moment they were
+/// read, such that the values read within a transaction which was rolled back
+/// afterwards are not used anymore.
+struct Cache<K: Ord, V>(Mutex<BTreeMap<(u64, K), Box<V>>>);
+
+impl<K: Ord, V> Default for Cache<K, V> {
+    fn default() -> Self { Cache(Mutex::new(BTreeMap::new())) }
+}
+
+impl<K: Ord, V> Debug for Cache<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { f.write_str("Cache { .. }") }
+}
+
+impl<K: Ord, V: Sync> Cache<K, V> {
+    /// Returns the cached value, loading it with `load` if it is not in the
+    /// cache yet.
+    fn get_or_load<E>(
+        &self,
+        epoch: u64,
+        key: K,
+        load: impl FnOnce(&K) -> Result<Option<V>, E>,
+    ) -> Result<Option<&V>, E> {
+        let mut map = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let value = match map.entry((epoch, key)) {
+            Entry::Occupied(entry) => entry.into_mut().as_ref() as *const V,
+            Entry::Vacant(entry) => {
+                let Some(value) = load(&entry.key().1)? else {
+                    return Ok(None);
+                };
+                entry.insert(Box::new(value)).as_ref() as *const V
+            }
+        };
+        // SAFETY: values are boxed, thus they don't move when the map is modified, and they are
+        // dropped only by `Cache::clear`, which requires exclusive access to the cache.
+        Ok(Some(unsafe { &*value }))
+    }
+
+    /// Caches the value which was already read from the database, returning
+    /// the previously cached value if there is one.
+    fn insert(&self, epoch: u64, key: K, value: V) -> &V {
+        self.get_or_load(epoch, key, |_| Ok::<_, Infallible>(Some(value)))
+            .unwrap_or_else(|err| match err {})
+            .expect("value is provided")
+    }
+
+    fn clear(&mut self) { self.0.get_mut().unwrap_or_else(PoisonError::into_inner).clear() }
+}
+
+#[derive(Debug, Default)]
+struct StashCache {
+    types: Cache<(), TypeSystem>,
+    libs: Cache<LibId, Lib>,
+    ifaces: Cache<IfaceId, Iface>,
+    schemata: Cache<SchemaId, SchemaIfaces>,
+    iimpls: Cache<ImplId, IfaceImpl>,
+    geneses: Cache<ContractId, Genesis>,
+    bundles: Cache<BundleId, TransitionBundle>,
+    extensions: Cache<OpId, Extension>,
+    witnesses: Cache<XWitnessId, SealWitness>,
+    attachments: Cache<AttachId, MediumBlob>,
+    identities: Cache<Identity, Identity>,
+    supplements: Cache<ContentRef, Supplement>,
+    sigs: Cache<ContentId, ContentSigs>,
+    iface_names: Cache<TypeName, TypeName>,
+}
+
+impl StashCache {
+    fn clear(&mut self) {
+        self.types.clear();
+        self.libs.clear();
+        self.ifaces.clear();
+        self.schemata.clear();
+        self.iimpls.clear();
+        self.geneses.clear();
+        self.bundles.clear();
+        self.extensions.clear();
+        self.witnesses.clear();
+        self.attachments.clear();
+        self.identities.clear();
+        self.supplements.clear();
+        self.sigs.clear();
+        self.iface_names.clear();
+    }
+}
+
+/// Stash persisted in a SQLite database.
+#[derive(Debug)]
+pub struct SqlStash {
+    db: Db,
+    cache: StashCache,
+    // `set_trust` can't report database errors, so we write trust levels on commit
+    trust: BTreeMap<Identity, TrustLevel>,
+}
+
+impl SqlStash {
+    /// Opens stash database at the given path, creating it if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SqlError> {
+        Self::with(Db::connect(Connection::open(path)?), "main")
+    }
+
+    /// Creates stash backed by a non-persisted in-memory database.
+    pub fn in_memory() -> Result<Self, SqlError> {
+        Self::with(Db::connect(Connection::open_in_memory()?), "main")
+    }
+
+    fn with(conn: Arc<Mutex<Conn>>, schema: &'static str) -> Result<Self, SqlError> {
+        Ok(Self {
+            db: Db::with(conn, schema, &Table::STASH, STASH_TABLES)?,
+            cache: default!(),
+            trust: empty!(),
+        })
+    }
+
+    /// Rows of all the tables kept by the provider, for checking whether its
+    /// data were modified.
+    #[cfg(test)]
+    pub(super) fn debug_rows(&self) -> Result<Vec<String>, SqlError> { self.db.rows() }
+
+    fn cached<'a, K: Ord + StrictEncode, V: StrictDecode + Sync>(
+        &'a self,
+        cache: &'a Cache<K, V>,
+        table: Table,
+        key: K,
+    ) -> Result<Option<&'a V>, SqlError> {
+        cache.get_or_load(self.db.epoch(), key, |key| self.db.get(table, key))
+    }
+
+    fn cached_all<'a, K: Ord + StrictDecode, V: StrictDecode + Sync>(
+        &'a self,
+        cache: &'a Cache<K, V>,
+        table: Table,
+    ) -> Result<Vec<&'a V>, SqlError> {
+        let epoch = self.db.epoch();
+        Ok(self
+            .db
+            .entries(table)?
+            .into_iter()
+            .map(|(key, value)| cache.insert(epoch, key, value))
+            .collect())
+    }
+
+    fn cached_schema(&self, schema_id: SchemaId) -> Result<Option<&SchemaIfaces>, SqlError> {
+        self.cache
+            .schemata
+            .get_or_load(self.db.epoch(), schema_id, |schema_id| {
+                let Some(schema) = self.db.get::<Schema>(Table::Schemata, schema_id)? else {
+                    return Ok(None);
+                };
+                let iimpls = self.db.select(
+                    "SELECT iface, value FROM {db}.iimpls WHERE schema_id = ?1 AND superseded = 0",
+                    params![encode(schema_id)?],
+                    |row| Ok((column::<TypeName>(row, 0)?, column::<IfaceImpl>(row, 1)?)),
+                )?;
+                let iimpls = Confined::try_from_iter(iimpls)?;
+                Ok(Some(SchemaIfaces { schema, iimpls }))
+            })
+    }
+
+    fn iface_name(&self, iface_id: IfaceId) -> Result<Option<TypeName>, SqlError> {
+        self.db.select_first(
+            "SELECT name FROM {db}.ifaces WHERE key = ?1",
+            params![encode(&iface_id)?],
+            |row| column(row, 0),
+        )
+    }
+
+    fn store_iimpl(
+        &self,
+        iimpl: &IfaceImpl,
+        iface: &TypeName,
+        superseded: bool,
+    ) -> Result<(), SqlError> {
+        self.db.execute(
+            "INSERT OR REPLACE INTO {db}.iimpls (key, schema_id, iface, superseded, value)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                encode(&iimpl.impl_id())?,
+                encode(&iimpl.schema_id)?,
+                encode(iface)?,
+                superseded,
+                encode(iimpl)?
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+impl StoreTransaction for SqlStash {
+    type TransactionErr = SqlError;
+
+    fn begin_transaction(&mut self) -> Result<(), Self::TransactionErr> { self.db.begin() }
+
+    fn commit_transaction(&mut self) -> Result<(), Self::TransactionErr> {
+        for (identity, trust) in &self.trust {
+            self.db.put(Table::Identities, identity, trust)?;
+        }
+        self.trust.clear();
+        self.db.commit()
+    }
+
+    fn rollback_transaction(&mut self) {
+        self.db.rollback();
+        self.trust.clear();
+        self.cache.clear();
+    }
+}
+
+impl StashProvider for SqlStash {}
+
+impl StashReadProvider for SqlStash {
+    type Error = SqlError;
+
+    fn type_system(&self) -> Result<&TypeSystem, Self::Error> {
+        let types = self.cache.types.get_or_load(self.db.epoch(), (), |_| {
+            Ok::<_, SqlError>(Some(self.db.get(Table::Types, &0u8)?.unwrap_or_default()))
+        })?;
+        Ok(types.expect("type system is always provided"))
+    }
+
+    fn lib(&self, id: LibId) -> Result<&Lib, StashProviderError<Self::Error>> {
+        self.cached(&self.cache.libs, Table::Libs, id)?
+            .ok_or_else(|| StashInconsistency::LibAbsent(id).into())
+    }
+
+    fn libs(&self) -> Result<impl Iterator<Item = &Lib>, Self::Error> {
+        Ok(self.cached_all(&self.cache.libs, Table::Libs)?.into_iter())
+    }
+
+    fn ifaces(&self) -> Result<impl Iterator<Item = &Iface>, Self::Error> {
+        Ok(self.cached_all(&self.cache.ifaces, Table::Ifaces)?.into_iter())
+    }
+
+    fn iface(&self, iface: impl Into<IfaceRef>) -> Result<&Iface, StashProviderError<Self::Error>> {
+        let iref = iface.into();
+        let iface_id = match iref {
+            IfaceRef::Name(ref name) => self.db.select_first(
+                "SELECT key FROM {db}.ifaces WHERE name = ?1 ORDER BY key LIMIT 1",
+                params![encode(name)?],
+                |row| column(row, 0),
+            )?,
+            IfaceRef::Id(id) => Some(id),
+        };
+        let iface = match iface_id {
+            Some(id) => self.cached(&self.cache.ifaces, Table::Ifaces, id)?,
+            None => None,
+        };
+        iface.ok_or_else(|| StashInconsistency::IfaceAbsent(iref).into())
+    }
+
+    fn schemata(&self) -> Result<impl Iterator<Item = &SchemaIfaces>, Self::Error> {
+        let schemata = self
+            .db
+            .keys::<SchemaId>(Table::Schemata)?
+            .into_iter()
+            .filter_map(|schema_id| self.cached_schema(schema_id).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(schemata.into_iter())
+    }
+
+    fn schema(
+        &self,
+        schema_id: SchemaId,
+    ) -> Result<&SchemaIfaces, StashProviderError<Self::Error>> {
+        self.cached_schema(schema_id)?
+            .ok_or_else(|| StashInconsistency::SchemaAbsent(schema_id).into())
+    }
+
+    fn schemata_by<C: IfaceClass>(
+        &self,
+    ) -> Result<impl Iterator<Item = &SchemaIfaces>, Self::Error> {
+        Ok(self
+            .schemata()?
+            .filter(|schema_ifaces| self.impl_for::<C>(schema_ifaces).is_ok()))
+    }
+
+    fn impl_for<'a, C: IfaceClass + 'a>(
+        &'a self,
+        schema_ifaces: &'a SchemaIfaces,
+    ) -> Result<&'a IfaceImpl, StashProviderError<Self::Error>> {
+        schema_ifaces
+            .iimpls
+            .values()
+            .find(|iimpl| C::IFACE_IDS.contains(&iimpl.iface_id))
+            .or_else(|| {
+                schema_ifaces.iimpls.keys().find_map(|id| {
+                    let iface = self.iface(id.clone()).ok()?;
+                    iface.find_abstractable_impl(schema_ifaces)
+                })
+            })
+            .ok_or_else(move || {
+                ContractIfaceError::NoAbstractImpl(
+                    C::IFACE_IDS[0],
+                    schema_ifaces.schema.schema_id(),
+                )
+                .into()
+            })
+    }
+
+    fn geneses(&self) -> Result<impl Iterator<Item = &Genesis>, Self::Error> {
+        Ok(self.cached_all(&self.cache.geneses, Table::Geneses)?.into_iter())
+    }
+
+    fn geneses_by<C: IfaceClass>(&self) -> Result<impl Iterator<Item = &Genesis>, Self::Error> {
+        let epoch = self.db.epoch();
+        let mut geneses = vec![];
+        for schema_ifaces in self.schemata_by::<C>()? {
+            let rows = self.db.select(
+                "SELECT key, value FROM {db}.geneses WHERE schema_id = ?1 ORDER BY key",
+                params![encode(&schema_ifaces.schema.schema_id())?],
+                |row| Ok((column::<ContractId>(row, 0)?, column::<Genesis>(row, 1)?)),
+            )?;
+            geneses.extend(rows.into_iter().map(|(contract_id, genesis)| {
+                self.cache.geneses.insert(epoch, contract_id, genesis)
+            }));
+        }
+        Ok(geneses.into_iter())
+    }
+
+    fn genesis(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<&Genesis, StashProviderError<Self::Error>> {
+        self.cached(&self.cache.geneses, Table::Geneses, contract_id)?
+            .ok_or_else(|| StashInconsistency::ContractAbsent(contract_id).into())
+    }
+
+    fn get_trust(&self, identity: &Identity) -> Result<TrustLevel, Self::Error> {
+        if let Some(trust) = self.trust.get(identity) {
+            return Ok(*trust);
+        }
+        Ok(self
+            .db
+            .get(Table::Identities, identity)?
+            .unwrap_or_default())
+    }
+
+    fn identities(&self) -> Result<impl Iterator<Item = (&Identity, TrustLevel)>, Self::Error> {
+        let mut identities = self
+            .db
+            .entries::<Identity, TrustLevel>(Table::Identities)?
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        identities.extend(self.trust.iter().map(|(id, trust)| (id.clone(), *trust)));
+        let epoch = self.db.epoch();
+        let identities = identities
+            .into_iter()
+            .map(|(id, trust)| (self.cache.identities.insert(epoch, id.clone(), id), trust))
+            .collect::<Vec<_>>();
+        Ok(identities.into_iter())
+    }
+
+    fn supplement(&self, content_ref: ContentRef) -> Result<Option<&Supplement>, Self::Error> {
+        self.cache
+            .supplements
+            .get_or_load(self.db.epoch(), content_ref, |content_ref| {
+                Ok(self.supplements(*content_ref)?.next())
+            })
+    }
+
+    fn supplements(
+        &self,
+        content_ref: ContentRef,
+    ) -> Result<impl Iterator<Item = Supplement>, Self::Error> {
+        let suppls = self.db.select(
+            "SELECT value FROM {db}.supplements WHERE content_ref = ?1",
+            params![encode(&content_ref)?],
+            |row| column::<Supplement>(row, 0),
+        )?;
+        Ok(suppls.into_iter().collect::<BTreeSet<_>>().into_iter())
+    }
+
+    fn sigs_for(&self, content_id: &ContentId) -> Result<Option<&ContentSigs>, Self::Error> {
+        self.cached(&self.cache.sigs, Table::Sigs, *content_id)
+    }
+
+    fn witness_ids(&self) -> Result<impl Iterator<Item = XWitnessId>, Self::Error> {
+        Ok(self.db.keys::<XWitnessId>(Table::SealWitnesses)?.into_iter())
+    }
+
+    fn bundle_ids(&self) -> Result<impl Iterator<Item = BundleId>, Self::Error> {
+        Ok(self.db.keys::<BundleId>(Table::Bundles)?.into_iter())
+    }
+
+    fn bundle(
+        &self,
+        bundle_id: BundleId,
+    ) -> Result<&TransitionBundle, StashProviderError<Self::Error>> {
+        self.cached(&self.cache.bundles, Table::Bundles, bundle_id)?
+            .ok_or_else(|| StashInconsistency::BundleAbsent(bundle_id).into())
+    }
+
+    fn extension_ids(&self) -> Result<impl Iterator<Item = OpId>, Self::Error> {
+        Ok(self.db.keys::<OpId>(Table::Extensions)?.into_iter())
+    }
+
+    fn extension(&self, op_id: OpId) -> Result<&Extension, StashProviderError<Self::Error>> {
+        self.cached(&self.cache.extensions, Table::Extensions, op_id)?
+            .ok_or_else(|| StashInconsistency::OperationAbsent(op_id).into())
+    }
+
+    fn witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<&SealWitness, StashProviderError<Self::Error>> {
+        self.cached(&self.cache.witnesses, Table::SealWitnesses, witness_id)?
+            .ok_or_else(|| StashInconsistency::WitnessAbsent(witness_id).into())
+    }
+
+    fn attachments(&self) -> Result<impl Iterator<Item = (AttachId, &MediumBlob)>, Self::Error> {
+        let epoch = self.db.epoch();
+        let attachments = self
+            .db
+            .entries::<AttachId, MediumBlob>(Table::Attachments)?
+            .into_iter()
+            .map(|(id, attach)| (id, self.cache.attachments.insert(epoch, id, attach)))
+            .collect::<Vec<_>>();
+        Ok(attachments.into_iter())
+    }
+
+    fn taprets(&self) -> Result<impl Iterator<Item = (XWitnessId, TapretCommitment)>, Self::Error> {
+        let witnesses = self
+            .db
+            .entries::<XWitnessId, SealWitness>(Table::SealWitnesses)?;
+        Ok(witnesses
+            .into_iter()
+            .filter_map(|(witness_id, witness)| match witness.anchors {
+                AnchorSet::Tapret(anchor) |
+                AnchorSet::Double {
+                    tapret: anchor,
+                    opret: _,
+                } => Some((witness_id, TapretCommitment {
+                    mpc: anchor.mpc_proof.commit_id(),
+                    nonce: anchor.dbc_proof.path_proof.nonce(),
+                })),
+                _ => None,
+            }))
+    }
+
+    fn seal_secret(
+        &self,
+        secret: XChain<SecretSeal>,
+    ) -> Result<Option<XChain<GraphSeal>>, Self::Error> {
+        self.db.select_first(
+            "SELECT key FROM {db}.secret_seals WHERE secret = ?1 LIMIT 1",
+            params![encode(&secret)?],
+            |row| column(row, 0),
+        )
+    }
+
+    fn secret_seals(&self) -> Result<impl Iterator<Item = XChain<GraphSeal>>, Self::Error> {
+        Ok(self.db.keys::<XChain<GraphSeal>>(Table::SecretSeals)?.into_iter())
+    }
+
+    fn archived_contracts(&self) -> Result<impl Iterator<Item = ContractId>, Self::Error> {
+        Ok(self.db.keys::<ContractId>(Table::Archived)?.into_iter())
+    }
+
+    fn iimpls(&self, schema_id: SchemaId) -> Result<impl Iterator<Item = &IfaceImpl>, Self::Error> {
+        let epoch = self.db.epoch();
+        // Current implementations go first, followed by the superseded ones
+        let iimpls = self
+            .db
+            .select(
+                "SELECT key, value FROM {db}.iimpls WHERE schema_id = ?1
+                 ORDER BY superseded, key",
+                params![encode(&schema_id)?],
+                |row| Ok((column::<ImplId>(row, 0)?, column::<IfaceImpl>(row, 1)?)),
+            )?
+            .into_iter()
+            .map(|(impl_id, iimpl)| self.cache.iimpls.insert(epoch, impl_id, iimpl))
+            .collect::<Vec<_>>();
+        Ok(iimpls.into_iter())
+    }
+
+    fn iimpl(&self, impl_id: ImplId) -> Result<&IfaceImpl, StashProviderError<Self::Error>> {
+        self.cached(&self.cache.iimpls, Table::Iimpls, impl_id)?
+            .ok_or_else(|| StashInconsistency::ImplAbsent(impl_id).into())
+    }
+
+    fn iimpl_bindings(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<impl Iterator<Item = (&TypeName, ImplId)>, Self::Error> {
+        let mut bindings = self.db.select(
+            "SELECT iface, seq, impl_id FROM {db}.bindings WHERE contract_id = ?1",
+            params![encode(&contract_id)?],
+            |row| {
+                let iface = column::<TypeName>(row, 0)?;
+                Ok((iface, row.get::<_, i64>(1)?, column::<ImplId>(row, 2)?))
+            },
+        )?;
+        bindings.sort_by(|(name1, seq1, _), (name2, seq2, _)| (name1, seq1).cmp(&(name2, seq2)));
+        let epoch = self.db.epoch();
+        let bindings = bindings
+            .into_iter()
+            .map(|(name, _, impl_id)| {
+                (self.cache.iface_names.insert(epoch, name.clone(), name), impl_id)
+            })
+            .collect::<Vec<_>>();
+        Ok(bindings.into_iter())
+    }
+
+    fn serialized_size(&self) -> Result<usize, Self::Error> { self.db.size() }
+}
+
+impl StashWriteProvider for SqlStash {
+    type Error = SqlError;
+
+    fn replace_schema(&mut self, schema: Schema) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        let schema_id = schema.schema_id();
+        if self.db.contains(Table::Schemata, &schema_id)? {
+            return Ok(false);
+        }
+        self.db.put(Table::Schemata, &schema_id, &schema)?;
+        Ok(true)
+    }
+
+    fn replace_iface(&mut self, iface: Iface) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        let iface_id = iface.iface_id();
+        if self.db.contains(Table::Ifaces, &iface_id)? {
+            return Ok(false);
+        }
+        self.db.execute(
+            "INSERT INTO {db}.ifaces (key, name, value) VALUES (?1, ?2, ?3)",
+            params![encode(&iface_id)?, encode(&iface.name)?, encode(&iface)?],
+        )?;
+        Ok(true)
+    }
+
+    fn replace_iimpl(&mut self, iimpl: IfaceImpl) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        let impl_id = iimpl.impl_id();
+        let superseded = self.db.select_first(
+            "SELECT superseded FROM {db}.iimpls WHERE key = ?1",
+            params![encode(&impl_id)?],
+            |row| Ok(row.get::<_, bool>(0)?),
+        )?;
+        if superseded == Some(true) {
+            return Ok(false);
+        }
+        if !self.db.contains(Table::Schemata, &iimpl.schema_id)? {
+            return Err(SqlError::Encoding(format!(
+                "implementation {impl_id} is made for unknown schema {}",
+                iimpl.schema_id
+            )));
+        }
+        let Some(iface) = self.iface_name(iimpl.iface_id)? else {
+            return Err(SqlError::Encoding(format!(
+                "implementation {impl_id} is made for unknown interface {}",
+                iimpl.iface_id
+            )));
+        };
+        let current = self.db.select_first(
+            "SELECT value FROM {db}.iimpls WHERE schema_id = ?1 AND iface = ?2 AND superseded = 0",
+            params![encode(&iimpl.schema_id)?, encode(&iface)?],
+            |row| column::<IfaceImpl>(row, 0),
+        )?;
+        match current {
+            Some(current) if current.impl_id() == impl_id => return Ok(false),
+            Some(current) if !iimpl.supersedes(&current) => self.store_iimpl(&iimpl, &iface, true)?,
+            Some(current) => {
+                self.store_iimpl(&current, &iface, true)?;
+                self.store_iimpl(&iimpl, &iface, false)?;
+            }
+            None => self.store_iimpl(&iimpl, &iface, false)?,
+        }
+        Ok(true)
+    }
+
+    fn replace_genesis(&mut self, genesis: Genesis) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        let contract_id = genesis.contract_id();
+        let present = self.db.contains(Table::Geneses, &contract_id)?;
+        self.db.execute(
+            "INSERT OR REPLACE INTO {db}.geneses (key, schema_id, value) VALUES (?1, ?2, ?3)",
+            params![encode(&contract_id)?, encode(&genesis.schema_id)?, encode(&genesis)?],
+        )?;
+        Ok(!present)
+    }
+
+    fn replace_extension(&mut self, extension: Extension) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db
+            .replace(Table::Extensions, &extension.id(), &extension)
+    }
+
+    fn replace_bundle(&mut self, bundle: TransitionBundle) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.replace(Table::Bundles, &bundle.bundle_id(), &bundle)
+    }
+
+    fn replace_witness(&mut self, witness: SealWitness) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db
+            .replace(Table::SealWitnesses, &witness.witness_id(), &witness)
+    }
+
+    fn replace_attachment(
+        &mut self,
+        id: AttachId,
+        attach: MediumBlob,
+    ) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.replace(Table::Attachments, &id, &attach)
+    }
+
+    fn replace_lib(&mut self, lib: Lib) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.replace(Table::Libs, &lib.id(), &lib)
+    }
+
+    fn consume_types(&mut self, types: TypeSystem) -> Result<(), Self::Error> {
+        self.cache.clear();
+        let mut type_system = self
+            .db
+            .get::<TypeSystem>(Table::Types, &0u8)?
+            .unwrap_or_default();
+        type_system.extend(types)?;
+        self.db.put(Table::Types, &0u8, &type_system)
+    }
+
+    fn set_trust(
+        &mut self,
+        identity: Identity,
+        trust: TrustLevel,
+    ) -> Result<(), confinement::Error> {
+        self.trust.insert(identity, trust);
+        Ok(())
+    }
+
+    fn add_supplement(&mut self, suppl: Supplement) -> Result<(), Self::Error> {
+        self.cache.clear();
+        self.db.execute(
+            "INSERT OR IGNORE INTO {db}.supplements (content_ref, value) VALUES (?1, ?2)",
+            params![encode(&suppl.content_id)?, encode(&suppl)?],
+        )?;
+        Ok(())
+    }
+
+    fn import_sigs<I>(&mut self, content_id: ContentId, sigs: I) -> Result<(), Self::Error>
+    where I: IntoIterator<Item = (Identity, SigBlob)> {
+        self.cache.clear();
+        let mut accepted = vec![];
+        for (identity, sig) in sigs {
+            let trust = match self.trust.get(&identity) {
+                Some(trust) => *trust,
+                None => match self.db.get::<TrustLevel>(Table::Identities, &identity)? {
+                    Some(trust) => trust,
+                    None => {
+                        let trust = TrustLevel::default();
+                        self.db.put(Table::Identities, &identity, &trust)?;
+                        trust
+                    }
+                },
+            };
+            if trust.should_accept() {
+                accepted.push((identity, sig));
+            }
+        }
+        let sigs = match self.db.get::<ContentSigs>(Table::Sigs, &content_id)? {
+            Some(mut sigs) => {
+                sigs.extend(accepted)?;
+                sigs
+            }
+            None => ContentSigs::from(Confined::try_from_iter(accepted)?),
+        };
+        self.db.put(Table::Sigs, &content_id, &sigs)
+    }
+
+    fn add_secret_seal(&mut self, seal: XChain<GraphSeal>) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        let added = self.db.execute(
+            "INSERT OR IGNORE INTO {db}.secret_seals (key, secret) VALUES (?1, ?2)",
+            params![encode(&seal)?, encode(&seal.conceal())?],
+        )?;
+        Ok(added > 0)
+    }
+
+    fn set_archived(
+        &mut self,
+        contract_id: ContractId,
+        archived: bool,
+    ) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        match archived {
+            true => self.db.put_key(Table::Archived, &contract_id),
+            false => self.db.delete(Table::Archived, &contract_id),
+        }
+    }
+
+    fn bind_iimpl(
+        &mut self,
+        contract_id: ContractId,
+        impl_id: ImplId,
+    ) -> Result<bool, StashProviderError<Self::Error>> {
+        self.cache.clear();
+        let iface_id = self.iimpl(impl_id)?.iface_id;
+        let iface = self
+            .iface_name(iface_id)?
+            .ok_or(StashInconsistency::IfaceAbsent(iface_id.into()))?;
+        let contract = encode(&contract_id)?;
+        let iface = encode(&iface)?;
+        let last = self.db.select_first(
+            "SELECT seq, impl_id FROM {db}.bindings WHERE contract_id = ?1 AND iface = ?2
+             ORDER BY seq DESC LIMIT 1",
+            params![contract, iface],
+            |row| Ok((row.get::<_, i64>(0)?, column::<ImplId>(row, 1)?)),
+        )?;
+        let seq = match last {
+            Some((_, last)) if last == impl_id => return Ok(false),
+            Some((seq, _)) => seq + 1,
+            None => 0,
+        };
+        self.db.execute(
+            "INSERT INTO {db}.bindings (contract_id, iface, seq, impl_id) VALUES (?1, ?2, ?3, ?4)",
+            params![contract, iface, seq, encode(&impl_id)?],
+        )?;
+        Ok(true)
+    }
+
+    fn remove_genesis(&mut self, contract_id: ContractId) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.execute(
+            "DELETE FROM {db}.bindings WHERE contract_id = ?1",
+            params![encode(&contract_id)?],
+        )?;
+        self.db.delete(Table::Geneses, &contract_id)
+    }
+
+    fn remove_extension(&mut self, opid: OpId) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.delete(Table::Extensions, &opid)
+    }
+
+    fn remove_bundle(&mut self, bundle_id: BundleId) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.delete(Table::Bundles, &bundle_id)
+    }
+
+    fn remove_witness(&mut self, witness_id: XWitnessId) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.delete(Table::SealWitnesses, &witness_id)
+    }
+
+    fn remove_supplements(&mut self, content_ref: ContentRef) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        let removed = self.db.execute(
+            "DELETE FROM {db}.supplements WHERE content_ref = ?1",
+            params![encode(&content_ref)?],
+        )?;
+        Ok(removed > 0)
+    }
+
+    fn remove_secret_seal(&mut self, seal: XChain<GraphSeal>) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.delete(Table::SecretSeals, &seal)
+    }
+
+    fn remove_schema(&mut self, schema_id: SchemaId) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.execute(
+            "DELETE FROM {db}.iimpls WHERE schema_id = ?1",
+            params![encode(&schema_id)?],
+        )?;
+        self.db.delete(Table::Schemata, &schema_id)
+    }
+
+    fn remove_iface(&mut self, iface_id: IfaceId) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.delete(Table::Ifaces, &iface_id)
+    }
+
+    fn remove_lib(&mut self, lib_id: LibId) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.delete(Table::Libs, &lib_id)
+    }
+
+    fn remove_sigs(&mut self, content_id: ContentId) -> Result<bool, Self::Error> {
+        self.cache.clear();
+        self.db.delete(Table::Sigs, &content_id)
+    }
+
+    fn replace_types(&mut self, types: TypeSystem) -> Result<(), Self::Error> {
+        self.cache.clear();
+        self.db.put(Table::Types, &0u8, &types)
+    }
+}
+
+//////////
+// STATE
+//////////
+
+/// Contract state persisted in a SQLite database.
+#[derive(Debug)]
+pub struct SqlState {
+    db: Db,
+}
+
+// Kinds of the assignments in the `assignments` table
+const ASSIGN_RIGHTS: u8 = 0;
+const ASSIGN_FUNGIBLE: u8 = 1;
+const ASSIGN_DATA: u8 = 2;
+const ASSIGN_ATTACH: u8 = 3;
+
+fn store_state_item(db: &Db, contract_id: ContractId, item: &StateItem) -> Result<(), SqlError> {
+    let (opout, kind, witness_id, value) = match item {
+        StateItem::Global(ty, out, data) => {
+            let witness_id = out.witness_id().as_ref().map(encode).transpose()?;
+            // Global state items are replaced, like in the memory state
+            db.execute(
+                "INSERT OR REPLACE INTO {db}.global_state (contract_id, ty, out, witness_id, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    encode(&contract_id)?,
+                    encode(ty)?,
+                    encode(out)?,
+                    witness_id,
+                    encode(data)?
+                ],
+            )?;
+            return Ok(());
+        }
+        StateItem::Rights(a) => (a.opout, ASSIGN_RIGHTS, a.witness, encode(a)?),
+        StateItem::Fungible(a) => (a.opout, ASSIGN_FUNGIBLE, a.witness, encode(a)?),
+        StateItem::Data(a) => (a.opout, ASSIGN_DATA, a.witness, encode(a)?),
+        StateItem::Attach(a) => (a.opout, ASSIGN_ATTACH, a.witness, encode(a)?),
+    };
+    // Previously known assignments are kept, like in the memory state
+    db.execute(
+        "INSERT OR IGNORE INTO {db}.assignments (contract_id, opout, kind, witness_id, value)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            encode(&contract_id)?,
+            encode(&opout)?,
+            kind,
+            witness_id.as_ref().map(encode).transpose()?,
+            value
+        ],
+    )?;
+    Ok(())
+}
+
+impl SqlState {
+    /// Opens state database at the given path, creating it if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SqlError> {
+        Self::with(Db::connect(Connection::open(path)?), "main")
+    }
+
+    /// Creates state backed by a non-persisted in-memory database.
+    pub fn in_memory() -> Result<Self, SqlError> {
+        Self::with(Db::connect(Connection::open_in_memory()?), "main")
+    }
+
+    fn with(conn: Arc<Mutex<Conn>>, schema: &'static str) -> Result<Self, SqlError> {
+        Ok(Self {
+            db: Db::with(conn, schema, &Table::STATE, STATE_TABLES)?,
+        })
+    }
+
+    /// Rows of all the tables kept by the provider, for checking whether its
+    /// data were modified.
+    #[cfg(test)]
+    pub(super) fn debug_rows(&self) -> Result<Vec<String>, SqlError> { self.db.rows() }
+
+    /// Constructs empty state of a known contract.
+    fn empty_state(&self, contract_id: ContractId) -> Result<Option<MemContractState>, SqlError> {
+        let row = self.db.select_first(
+            "SELECT schema_id, limits FROM {db}.contracts WHERE id = ?1",
+            params![encode(&contract_id)?],
+            |row| {
+                let schema_id = column::<SchemaId>(row, 0)?;
+                let limits = column::<TinyOrdMap<GlobalStateType, u24>>(row, 1)?;
+                Ok((schema_id, limits))
+            },
+        )?;
+        let Some((schema_id, limits)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(MemContractState::with_limits(schema_id, contract_id, limits.release())?))
+    }
+}
+
+impl StoreTransaction for SqlState {
+    type TransactionErr = SqlError;
+
+    fn begin_transaction(&mut self) -> Result<(), Self::TransactionErr> { self.db.begin() }
+
+    fn commit_transaction(&mut self) -> Result<(), Self::TransactionErr> { self.db.commit() }
+
+    fn rollback_transaction(&mut self) { self.db.rollback() }
+}
+
+impl StateProvider for SqlState {}
+
+impl StateReadProvider for SqlState {
+    type ContractRead<'a> = MemContract<MemContractState>;
+    type Error = SqlError;
+
+    fn contract_state(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<Self::ContractRead<'_>, Self::Error> {
+        let mut contract = self
+            .empty_state(contract_id)?
+            .ok_or(StateInconsistency::UnknownContract(contract_id))?;
+        let id = encode(&contract_id)?;
+
+        let globals = self.db.select(
+            "SELECT ty, out, data FROM {db}.global_state WHERE contract_id = ?1",
+            params![id],
+            |row| {
+                let ty = column::<GlobalStateType>(row, 0)?;
+                Ok((ty, column::<GlobalOut>(row, 1)?, column::<DataState>(row, 2)?))
+            },
+        )?;
+        for (ty, out, data) in globals {
+            if !contract.has_global(ty) {
+                return Err(SqlError::Decoding(format!(
+                    "global state type {ty} is unknown to the contract {contract_id}"
+                )));
+            }
+            contract.restore_item(StateItem::Global(ty, out, data))?;
+        }
+
+        let assignments = self.db.select(
+            "SELECT kind, value FROM {db}.assignments WHERE contract_id = ?1",
+            params![id],
+            |row| match row.get::<_, u8>(0)? {
+                ASSIGN_RIGHTS => Ok(StateItem::Rights(column(row, 1)?)),
+                ASSIGN_FUNGIBLE => Ok(StateItem::Fungible(column(row, 1)?)),
+                ASSIGN_DATA => Ok(StateItem::Data(column(row, 1)?)),
+                ASSIGN_ATTACH => Ok(StateItem::Attach(column(row, 1)?)),
+                kind => Err(SqlError::Decoding(format!("unknown assignment kind {kind}"))),
+            },
+        )?;
+        for item in assignments {
+            contract.restore_item(item)?;
+        }
+
+        let witnesses = self.db.select(
+            "SELECT key, value FROM {db}.witnesses WHERE key IN (
+                SELECT witness_id FROM {db}.global_state WHERE contract_id = ?1
+                UNION SELECT witness_id FROM {db}.assignments WHERE contract_id = ?1
+             )",
+            params![id],
+            |row| Ok((column::<XWitnessId>(row, 0)?, column::<WitnessOrd>(row, 1)?)),
+        )?;
+        Ok(MemContract::with_witnesses(contract, witnesses.into_iter().collect()))
+    }
+
+    fn is_valid_witness(&self, witness_id: XWitnessId) -> Result<bool, Self::Error> {
+        Ok(self.witness_ord(witness_id)?.is_valid())
+    }
+
+    fn witness_ord(&self, witness_id: XWitnessId) -> Result<WitnessOrd, Self::Error> {
+        self.db
+            .get(Table::Witnesses, &witness_id)?
+            .ok_or_else(|| StateInconsistency::AbsentValidWitness.into())
+    }
+
+    fn contract_ids(&self) -> Result<impl Iterator<Item = ContractId> + '_, Self::Error> {
+        let contract_ids = self
+            .db
+            .select("SELECT id FROM {db}.contracts ORDER BY id", [], |row| {
+                column::<ContractId>(row, 0)
+            })?;
+        Ok(contract_ids.into_iter())
+    }
+
+    fn serialized_size(&self) -> Result<usize, Self::Error> { self.db.size() }
+}
+
+impl StateWriteProvider for SqlState {
+    type ContractWrite<'a> = SqlContractWriter<'a>;
+    type Error = SqlError;
+
+    fn register_contract(
+        &mut self,
+        schema: &Schema,
+        genesis: &Genesis,
+    ) -> Result<Self::ContractWrite<'_>, Self::Error> {
+        let contract_id = genesis.contract_id();
+        let empty = MemContractState::new(schema, contract_id);
+        let limits = TinyOrdMap::try_from(empty.global_limits())?;
+        self.db.execute(
+            "INSERT OR IGNORE INTO {db}.contracts (id, schema_id, limits) VALUES (?1, ?2, ?3)",
+            params![encode(&contract_id)?, encode(&empty.schema_id())?, encode(&limits)?],
+        )?;
+        let mut writer = SqlContractWriter { db: &self.db, empty };
+        writer.add_genesis(genesis)?;
+        Ok(writer)
+    }
+
+    fn update_contract(
+        &mut self,
+        contract_id: ContractId,
+    ) -> Result<Option<Self::ContractWrite<'_>>, Self::Error> {
+        let db = &self.db;
+        Ok(self
+            .empty_state(contract_id)?
+            .map(|empty| SqlContractWriter { db, empty }))
+    }
+
+    fn update_witnesses(
+        &mut self,
+        resolver: impl ResolveWitness,
+        after_height: u32,
+        layer1: Option<Layer1>,
+    ) -> Result<UpdateRes, Self::Error> {
+        let after_height = NonZeroU32::new(after_height).unwrap_or(NonZeroU32::MIN);
+        let mut res = UpdateRes {
+            succeeded: 0,
+            failed: map![],
+            updated: map![],
+            changes: vec![],
+        };
+        // The update may be a part of a larger stock transaction, in which case
+        // the transaction is committed by the stock
+        self.begin_transaction()?;
+        let witnesses = self
+            .db
+            .entries::<XWitnessId, WitnessOrd>(Table::Witnesses)
+            .inspect_err(|_| self.rollback_transaction())?;
+        for (id, ord) in witnesses {
+            if matches!(ord, WitnessOrd::Mined(pos) if pos.height() < after_height) {
+                continue;
+            }
+            if layer1.is_some_and(|layer1| id.layer1() != layer1) {
+                continue;
+            }
+            match resolver.resolve_pub_witness_ord(id) {
+                Ok(new) if new != ord => {
+                    res.updated.insert(id, (ord, new));
+                    self.db
+                        .put(Table::Witnesses, &id, &new)
+                        .inspect_err(|_| self.rollback_transaction())?;
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    res.failed.insert(id, err.to_string());
+                }
+            }
+            res.succeeded += 1;
+        }
+        self.commit_transaction()?;
+        Ok(res)
+    }
+
+    fn prune(
+        &mut self,
+        contract_id: ContractId,
+        spends: impl IntoIterator<Item = (Opout, XWitnessId)>,
+        max_height: u32,
+    ) -> Result<usize, Self::Error> {
+        let contract = encode(&contract_id)?;
+        let mut pruned = 0;
+        for (opout, witness_id) in spends {
+            let ord = self.db.get::<WitnessOrd>(Table::Witnesses, &witness_id)?;
+            if !matches!(ord, Some(WitnessOrd::Mined(pos)) if pos.height().get() <= max_height) {
+                continue;
+            }
+            pruned += self.db.execute(
+                "DELETE FROM {db}.assignments WHERE contract_id = ?1 AND opout = ?2",
+                params![contract, encode(&opout)?],
+            )?;
+        }
+        Ok(pruned)
+    }
+
+    fn remove_contract(&mut self, contract_id: ContractId) -> Result<bool, Self::Error> {
+        let id = encode(&contract_id)?;
+        let witnesses = self.db.select(
+            "SELECT witness_id FROM {db}.global_state
+             WHERE contract_id = ?1 AND witness_id IS NOT NULL
+             UNION SELECT witness_id FROM {db}.assignments
+             WHERE contract_id = ?1 AND witness_id IS NOT NULL",
+            params![id],
+            |row| Ok(row.get::<_, Vec<u8>>(0)?),
+        )?;
+        let removed = self
+            .db
+            .execute("DELETE FROM {db}.contracts WHERE id = ?1", params![id])?;
+        if removed == 0 {
+            return Ok(false);
+        }
+        self.db
+            .execute("DELETE FROM {db}.global_state WHERE contract_id = ?1", params![id])?;
+        self.db
+            .execute("DELETE FROM {db}.assignments WHERE contract_id = ?1", params![id])?;
+        // Witnesses which are still used by other contracts are kept
+        for witness_id in witnesses {
+            self.db.execute(
+                "DELETE FROM {db}.witnesses WHERE key = ?1
+                 AND NOT EXISTS (SELECT 1 FROM {db}.global_state WHERE witness_id = ?1)
+                 AND NOT EXISTS (SELECT 1 FROM {db}.assignments WHERE witness_id = ?1)",
+                params![witness_id],
+            )?;
+        }
+        Ok(true)
+    }
+}
+
+pub struct SqlContractWriter<'db> {
+    db: &'db Db,
+    /// Empty contract state, used to compute state items defined by the
+    /// operations.
+    empty: MemContractState,
+}
+
+impl<'db> SqlContractWriter<'db> {
+    fn store_operation(&self, op: OrdOpRef) -> Result<(), SqlError> {
+        let mut state = self.empty.clone();
+        state.add_operation(op);
+        for item in state.operation_items(op) {
+            store_state_item(self.db, self.empty.contract_id(), &item)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'db> ContractStateWrite for SqlContractWriter<'db> {
+    type Error = SqlError;
+
+    fn add_genesis(&mut self, genesis: &Genesis) -> Result<(), Self::Error> {
+        self.store_operation(OrdOpRef::Genesis(genesis))
+    }
+
+    fn add_transition(
+        &mut self,
+        transition: &Transition,
+        witness_id: XWitnessId,
+        ord: WitnessOrd,
+    ) -> Result<(), Self::Error> {
+        self.db.put(Table::Witnesses, &witness_id, &ord)?;
+        self.store_operation(OrdOpRef::Transition(transition, witness_id, ord))
+    }
+
+    fn add_extension(
+        &mut self,
+        extension: &Extension,
+        witness_id: XWitnessId,
+        ord: WitnessOrd,
+    ) -> Result<(), Self::Error> {
+        self.db.put(Table::Witnesses, &witness_id, &ord)?;
+        self.store_operation(OrdOpRef::Extension(extension, witness_id, ord))
+    }
+}
+
+//////////
+// INDEX
+//////////
+
+/// Index persisted in a SQLite database.
+#[derive(Debug)]
+pub struct SqlIndex {
+    db: Db,
+}
+
+impl SqlIndex {
+    /// Opens index database at the given path, creating it if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SqlError> {
+        Self::with(Db::connect(Connection::open(path)?), "main")
+    }
+
+    /// Creates index backed by a non-persisted in-memory database.
+    pub fn in_memory() -> Result<Self, SqlError> {
+        Self::with(Db::connect(Connection::open_in_memory()?), "main")
+    }
+
+    fn with(conn: Arc<Mutex<Conn>>, schema: &'static str) -> Result<Self, SqlError> {
+        Ok(Self {
+            db: Db::with(conn, schema, &Table::INDEX, INDEX_TABLES)?,
+        })
+    }
+
+    /// Rows of all the tables kept by the provider, for checking whether its
+    /// data were modified.
+    #[cfg(test)]
+    pub(super) fn debug_rows(&self) -> Result<Vec<String>, SqlError> { self.db.rows() }
+
+    fn is_registered(&self, contract_id: ContractId) -> Result<bool, SqlError> {
+        self.db.contains(Table::IndexedContracts, &contract_id)
+    }
+
+    fn store_assignments<State: ExposedState, Seal: ExposedSeal>(
+        &self,
+        contract_id: ContractId,
+        vec: &[Assign<State, Seal>],
+        opid: OpId,
+        type_id: AssignmentType,
+        output: impl Fn(&XChain<Seal>) -> XOutputSeal,
+    ) -> Result<(), IndexWriteError<SqlError>> {
+        if !self.is_registered(contract_id)? {
+            return Err(IndexInconsistency::ContractAbsent(contract_id).into());
+        }
+        for (no, assign) in vec.iter().enumerate() {
+            let opout = Opout::new(opid, type_id, no as u16);
+            match assign {
+                Assign::ConfidentialState { seal, .. } | Assign::Revealed { seal, .. } => {
+                    self.db.execute(
+                        "INSERT OR IGNORE INTO {db}.outpoint_opouts (contract_id, output, opout)
+                         VALUES (?1, ?2, ?3)",
+                        params![encode(&contract_id)?, encode(&output(seal))?, encode(&opout)?],
+                    )?;
+                }
+                Assign::Confidential { seal, .. } | Assign::ConfidentialSeal { seal, .. } => {
+                    self.db.execute(
+                        "INSERT OR REPLACE INTO {db}.terminals (key, opid, value)
+                         VALUES (?1, ?2, ?3)",
+                        params![encode(seal)?, encode(&opid)?, encode(&opout)?],
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StoreTransaction for SqlIndex {
+    type TransactionErr = SqlError;
+
+    fn begin_transaction(&mut self) -> Result<(), Self::TransactionErr> { self.db.begin() }
+
+    fn commit_transaction(&mut self) -> Result<(), Self::TransactionErr> { self.db.commit() }
+
+    fn rollback_transaction(&mut self) { self.db.rollback() }
+}
+
+impl IndexProvider for SqlIndex {}
+
+impl IndexReadProvider for SqlIndex {
+    type Error = SqlError;
+
+    fn contracts_assigning(
+        &self,
+        outputs: BTreeSet<XOutputSeal>,
+    ) -> Result<impl Iterator<Item = ContractId> + '_, Self::Error> {
+        let mut contract_ids = BTreeSet::new();
+        for output in outputs {
+            contract_ids.extend(self.db.select(
+                "SELECT DISTINCT contract_id FROM {db}.outpoint_opouts WHERE output = ?1",
+                params![encode(&output)?],
+                |row| column::<ContractId>(row, 0),
+            )?);
+        }
+        Ok(contract_ids.into_iter())
+    }
+
+    fn public_opouts(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<BTreeSet<Opout>, IndexReadError<Self::Error>> {
+        // Public opouts are not indexed, like in the memory index
+        if !self.is_registered(contract_id)? {
+            return Err(IndexInconsistency::ContractAbsent(contract_id).into());
+        }
+        Ok(BTreeSet::new())
+    }
+
+    fn opouts_by_outputs(
+        &self,
+        contract_id: ContractId,
+        outputs: impl IntoIterator<Item = impl Into<XOutputSeal>>,
+    ) -> Result<BTreeSet<Opout>, IndexReadError<Self::Error>> {
+        if !self.is_registered(contract_id)? {
+            return Err(IndexInconsistency::ContractAbsent(contract_id).into());
+        }
+        let contract = encode(&contract_id)?;
+        let mut opouts = BTreeSet::new();
+        for output in outputs {
+            let output: XOutputSeal = output.into();
+            let set = self.db.select(
+                "SELECT opout FROM {db}.outpoint_opouts WHERE contract_id = ?1 AND output = ?2",
+                params![contract, encode(&output)?],
+                |row| column::<Opout>(row, 0),
+            )?;
+            if set.is_empty() {
+                return Err(IndexInconsistency::OutpointUnknown(output, contract_id).into());
+            }
+            opouts.extend(set);
+        }
+        Ok(opouts)
+    }
+
+    fn opouts_by_terminals(
+        &self,
+        terminals: impl IntoIterator<Item = XChain<SecretSeal>>,
+    ) -> Result<BTreeSet<Opout>, Self::Error> {
+        let mut opouts = BTreeSet::new();
+        for terminal in terminals {
+            opouts.extend(self.db.get::<Opout>(Table::Terminals, &terminal)?);
+        }
+        Ok(opouts)
+    }
+
+    fn bundle_id_for_op(&self, opid: OpId) -> Result<BundleId, IndexReadError<Self::Error>> {
+        self.db
+            .get(Table::OpBundles, &opid)?
+            .ok_or_else(|| IndexInconsistency::BundleAbsent(opid).into())
+    }
+
+    fn spenders(&self, opout: Opout) -> Result<impl Iterator<Item = Spender> + '_, Self::Error> {
+        let spenders = self.db.select(
+            "SELECT spender FROM {db}.spenders WHERE opout = ?1",
+            params![encode(&opout)?],
+            |row| column::<Spender>(row, 0),
+        )?;
+        Ok(spenders.into_iter().collect::<BTreeSet<_>>().into_iter())
+    }
+
+    fn bundle_info(
+        &self,
+        bundle_id: BundleId,
+    ) -> Result<(impl Iterator<Item = XWitnessId>, ContractId), IndexReadError<Self::Error>> {
+        let witness_ids = self
+            .db
+            .select(
+                "SELECT witness_id FROM {db}.bundle_witnesses WHERE bundle_id = ?1",
+                params![encode(&bundle_id)?],
+                |row| column::<XWitnessId>(row, 0),
+            )?
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        if witness_ids.is_empty() {
+            return Err(IndexInconsistency::BundleWitnessUnknown(bundle_id).into());
+        }
+        let contract_id = self
+            .db
+            .get::<ContractId>(Table::BundleContracts, &bundle_id)?
+            .ok_or(IndexInconsistency::BundleContractUnknown(bundle_id))?;
+        Ok((witness_ids.into_iter(), contract_id))
+    }
+
+    fn contract_ids(&self) -> Result<impl Iterator<Item = ContractId> + '_, Self::Error> {
+        Ok(self.db.keys::<ContractId>(Table::IndexedContracts)?.into_iter())
+    }
+
+    fn bundle_ids(&self) -> Result<impl Iterator<Item = BundleId> + '_, Self::Error> {
+        let mut ids = self
+            .db
+            .keys::<BundleId>(Table::BundleContracts)?
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        ids.extend(self.db.select("SELECT bundle_id FROM {db}.bundle_witnesses", [], |row| {
+            column::<BundleId>(row, 0)
+        })?);
+        ids.extend(
+            self.db
+                .select("SELECT value FROM {db}.op_bundles", [], |row| column::<BundleId>(row, 0))?,
+        );
+        Ok(ids.into_iter())
+    }
+
+    fn serialized_size(&self) -> Result<usize, Self::Error> { self.db.size() }
+}
+
+impl IndexWriteProvider for SqlIndex {
+    type Error = SqlError;
+
+    fn register_contract(&mut self, contract_id: ContractId) -> Result<bool, Self::Error> {
+        self.db.put_key(Table::IndexedContracts, &contract_id)
+    }
+
+    fn register_bundle(
+        &mut self,
+        bundle_id: BundleId,
+        witness_id: XWitnessId,
+        contract_id: ContractId,
+    ) -> Result<bool, IndexWriteError<Self::Error>> {
+        if let Some(present) = self
+            .db
+            .get::<ContractId>(Table::BundleContracts, &bundle_id)?
+            .filter(|present| *present != contract_id)
+        {
+            return Err(IndexInconsistency::DistinctBundleContract {
+                bundle_id,
+                present,
+                expected: contract_id,
+            }
+            .into());
+        }
+        self.db.execute(
+            "INSERT OR IGNORE INTO {db}.bundle_witnesses (bundle_id, witness_id) VALUES (?1, ?2)",
+            params![encode(&bundle_id)?, encode(&witness_id)?],
+        )?;
+        Ok(self
+            .db
+            .replace(Table::BundleContracts, &bundle_id, &contract_id)?)
+    }
+
+    fn register_operation(
+        &mut self,
+        opid: OpId,
+        bundle_id: BundleId,
+    ) -> Result<bool, IndexWriteError<Self::Error>> {
+        if let Some(present) = self
+            .db
+            .get::<BundleId>(Table::OpBundles, &opid)?
+            .filter(|present| *present != bundle_id)
+        {
+            return Err(IndexInconsistency::DistinctBundleOp {
+                opid,
+                present,
+                expected: bundle_id,
+            }
+            .into());
+        }
+        Ok(self.db.replace(Table::OpBundles, &opid, &bundle_id)?)
+    }
+
+    fn register_spender(
+        &mut self,
+        opout: Opout,
+        spender: Spender,
+    ) -> Result<bool, IndexWriteError<Self::Error>> {
+        let added = self.db.execute(
+            "INSERT OR IGNORE INTO {db}.spenders (opout, opid, spender) VALUES (?1, ?2, ?3)",
+            params![encode(&opout)?, encode(&opout.op)?, encode(&spender)?],
+        )?;
+        Ok(added > 0)
+    }
+
+    fn index_genesis_assignments<State: ExposedState>(
+        &mut self,
+        contract_id: ContractId,
+        vec: &[Assign<State, GenesisSeal>],
+        opid: OpId,
+        type_id: AssignmentType,
+    ) -> Result<(), IndexWriteError<Self::Error>> {
+        self.store_assignments(contract_id, vec, opid, type_id, |seal| {
+            seal.to_output_seal()
+                .expect("genesis seals always have outpoint")
+        })
+    }
+
+    fn index_transition_assignments<State: ExposedState>(
+        &mut self,
+        contract_id: ContractId,
+        vec: &[Assign<State, GraphSeal>],
+        opid: OpId,
+        type_id: AssignmentType,
+        witness_id: XWitnessId,
+    ) -> Result<(), IndexWriteError<Self::Error>> {
+        self.store_assignments(contract_id, vec, opid, type_id, |seal| {
+            seal.try_to_output_seal(witness_id).unwrap_or_else(|_| {
+                panic!(
+                    "chain mismatch between assignment vout seal ({}) and witness transaction \
+                     ({})",
+                    seal, witness_id
+                )
+            })
+        })
+    }
+
+    fn remove_contract(
+        &mut self,
+        contract_id: ContractId,
+        opids: &BTreeSet<OpId>,
+    ) -> Result<bool, Self::Error> {
+        let contract = encode(&contract_id)?;
+        self.db.execute(
+            "DELETE FROM {db}.bundle_witnesses
+             WHERE bundle_id IN (SELECT key FROM {db}.bundle_contracts WHERE value = ?1)",
+            params![contract],
+        )?;
+        self.db
+            .execute("DELETE FROM {db}.bundle_contracts WHERE value = ?1", params![contract])?;
+        for opid in opids {
+            let opid = encode(opid)?;
+            self.db
+                .execute("DELETE FROM {db}.op_bundles WHERE key = ?1", params![opid])?;
+            self.db
+                .execute("DELETE FROM {db}.terminals WHERE opid = ?1", params![opid])?;
+            self.db
+                .execute("DELETE FROM {db}.spenders WHERE opid = ?1", params![opid])?;
+        }
+        self.db
+            .execute("DELETE FROM {db}.outpoint_opouts WHERE contract_id = ?1", params![contract])?;
+        self.db.delete(Table::IndexedContracts, &contract_id)
+    }
+}
+
+/// Opens stash, state and index databases inside the provided directory,
+/// creating them if they do not exist.
+///
+/// The databases are attached to a single connection, such that the
+/// providers share their transactions.
+fn open_shared(path: impl AsRef<Path>) -> Result<(SqlStash, SqlState, SqlIndex), SqlError> {
+    let path = path.as_ref();
+    let conn = Connection::open(path.join("stash.db"))?;
+    let state = path.join("state.db");
+    let index = path.join("index.db");
+    conn.execute("ATTACH DATABASE ?1 AS state", [state.to_string_lossy()])?;
+    conn.execute("ATTACH DATABASE ?1 AS idx", [index.to_string_lossy()])?;
+    let conn = Db::connect(conn);
+    Ok((
+        SqlStash::with(conn.clone(), "main")?,
+        SqlState::with(conn.clone(), "state")?,
+        SqlIndex::with(conn, "idx")?,
+    ))
+}
+
+impl Stock<SqlStash, SqlState, SqlIndex> {
+    /// Opens stock persisted in SQLite databases inside the provided directory,
+    /// creating the databases if they do not exist.
+    ///
+    /// All the data written within a single stock operation are committed to
+    /// the databases atomically.
+    pub fn open_sqlite(path: impl AsRef<Path>) -> Result<Self, SqlError> {
+        let (stash, state, index) = open_shared(path)?;
+        Ok(Self::with(stash, state, index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::path::PathBuf;
+
+    use amplify::confinement::Confined;
+    use amplify::Wrapper;
+    use bp::dbc::Method;
+    use bp::{Txid, Vout};
+    use commit_verify::Conceal;
+    use rgb::validation::WitnessResolverError;
+    use rgb::vm::{WitnessPos, XWitnessTx};
+    use rgb::{Assignments, TypedAssigns, VoidState};
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::persistence::ContractStateRead;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rgb-std-sqlite-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn seal() -> XChain<GraphSeal> {
+        XChain::with(
+            Layer1::Bitcoin,
+            GraphSeal::new_random_vout(Method::OpretFirst, Vout::from_u32(0)),
+        )
+    }
+
+    fn genesis_rights() -> Vec<Assign<VoidState, GenesisSeal>> {
+        let revealed = (0..2u8).map(|no| Assign::Revealed {
+            seal: XChain::Bitcoin(GenesisSeal::with_blinding(
+                Method::OpretFirst,
+                Txid::from([no + 1; 32]),
+                Vout::from_u32(0),
+                no as u64,
+            )),
+            state: VoidState::default(),
+            lock: Default::default(),
+        });
+        let confidential = Assign::ConfidentialSeal {
+            seal: XChain::Bitcoin(GenesisSeal::with_blinding(
+                Method::OpretFirst,
+                Txid::from([0xFF; 32]),
+                Vout::from_u32(1),
+                0xFF,
+            ))
+            .conceal(),
+            state: VoidState::default(),
+            lock: Default::default(),
+        };
+        revealed.chain([confidential]).collect()
+    }
+
+    fn genesis() -> Genesis {
+        let rights = TypedAssigns::Declarative(Confined::from_iter_unsafe(genesis_rights()));
+        let mut genesis = Genesis::strict_dumb();
+        genesis.assignments = Assignments::from_inner(Confined::from_iter_unsafe([(
+            AssignmentType::strict_dumb(),
+            rights,
+        )]));
+        genesis
+    }
+
+    fn transition(contract_id: ContractId) -> Transition {
+        let rights = TypedAssigns::Declarative(Confined::from_iter_unsafe([Assign::Revealed {
+            seal: seal(),
+            state: VoidState::default(),
+            lock: Default::default(),
+        }]));
+        let mut transition = Transition::strict_dumb();
+        transition.contract_id = contract_id;
+        transition.assignments = Assignments::from_inner(Confined::from_iter_unsafe([(
+            AssignmentType::strict_dumb(),
+            rights,
+        )]));
+        transition
+    }
+
+    struct OrdResolver(WitnessOrd);
+
+    impl ResolveWitness for OrdResolver {
+        fn resolve_pub_witness(
+            &self,
+            witness_id: XWitnessId,
+        ) -> Result<XWitnessTx, WitnessResolverError> {
+            Err(WitnessResolverError::Unknown(witness_id))
+        }
+
+        fn resolve_pub_witness_ord(
+            &self,
+            _: XWitnessId,
+        ) -> Result<WitnessOrd, WitnessResolverError> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn secret_seal_persists() {
+        let dir = test_dir("seal");
+        let seal = seal();
+        {
+            let mut stock = Stock::open_sqlite(&dir).unwrap();
+            assert!(stock.store_secret_seal(seal).unwrap());
+        }
+        let stock = Stock::open_sqlite(&dir).unwrap();
+        let stash = stock.as_stash_provider();
+        assert_eq!(stash.seal_secret(seal.conceal()).unwrap(), Some(seal));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn rollback() {
+        let mut stash = SqlStash::in_memory().unwrap();
+        let seal1 = seal();
+        let seal2 = seal();
+
+        stash.begin_transaction().unwrap();
+        stash.add_secret_seal(seal1).unwrap();
+        stash.commit_transaction().unwrap();
+
+        stash.begin_transaction().unwrap();
+        stash.add_secret_seal(seal2).unwrap();
+        stash.rollback_transaction();
+
+        let seals = stash.secret_seals().unwrap().collect::<Vec<_>>();
+        assert_eq!(seals, vec![seal1]);
+    }
+
+    #[test]
+    fn state_persists() {
+        let dir = test_dir("state");
+        let path = dir.join("state.db");
+        let genesis = genesis();
+        let contract_id = genesis.contract_id();
+        let transition = transition(contract_id);
+        let witness_id = XWitnessId::Bitcoin(Txid::from([0xAA; 32]));
+        let rights =
+            |state: &SqlState| state.contract_state(contract_id).unwrap().rights_all().count();
+
+        let mut state = SqlState::open(&path).unwrap();
+        state.begin_transaction().unwrap();
+        let mut writer = state
+            .register_contract(&Schema::strict_dumb(), &genesis)
+            .unwrap();
+        writer
+            .add_transition(&transition, witness_id, WitnessOrd::Tentative)
+            .unwrap();
+        state.commit_transaction().unwrap();
+        assert_eq!(rights(&state), 3);
+        assert_eq!(rights(&SqlState::open(&path).unwrap()), 3);
+
+        let pos = WitnessPos::new(100, 1_700_000_000).unwrap();
+        let res = state
+            .update_witnesses(OrdResolver(WitnessOrd::Mined(pos)), 1, None)
+            .unwrap();
+        let updated = (WitnessOrd::Tentative, WitnessOrd::Mined(pos));
+        assert_eq!(res.updated, map! { witness_id => updated });
+        let mut state = SqlState::open(&path).unwrap();
+        assert_eq!(state.witness_ord(witness_id).unwrap(), WitnessOrd::Mined(pos));
+
+        let spends = [(Opout::new(genesis.id(), AssignmentType::strict_dumb(), 0), witness_id)];
+        assert_eq!(state.prune(contract_id, spends, 99).unwrap(), 0);
+        assert_eq!(state.prune(contract_id, spends, 100).unwrap(), 1);
+        assert_eq!(rights(&SqlState::open(&path).unwrap()), 2);
+
+        assert!(state.remove_contract(contract_id).unwrap());
+        assert!(!state.remove_contract(contract_id).unwrap());
+        let state = SqlState::open(&path).unwrap();
+        assert_eq!(state.contract_ids().unwrap().count(), 0);
+        assert!(state.witness_ord(witness_id).is_err());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn index_persists() {
+        let dir = test_dir("index");
+        let path = dir.join("index.db");
+        let genesis = genesis();
+        let contract_id = genesis.contract_id();
+        let opid = genesis.id();
+        let ty = AssignmentType::strict_dumb();
+        let transition = transition(contract_id);
+        let witness_id = XWitnessId::Bitcoin(Txid::from([0xAA; 32]));
+        let bundle_id = BundleId::from([0xBB; 32]);
+        let spender = Spender {
+            opid: transition.id(),
+            witness_id,
+        };
+        let outputs = genesis_rights()
+            .into_iter()
+            .filter_map(|assign| match assign {
+                Assign::Revealed { seal, .. } => seal.to_output_seal(),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let terminal = genesis_rights()
+            .into_iter()
+            .find_map(|assign| match assign {
+                Assign::ConfidentialSeal { seal, .. } => Some(seal),
+                _ => None,
+            })
+            .unwrap();
+
+        let mut index = SqlIndex::open(&path).unwrap();
+        index.begin_transaction().unwrap();
+        assert!(index.register_contract(contract_id).unwrap());
+        index
+            .index_genesis_assignments(contract_id, &genesis_rights(), opid, ty)
+            .unwrap();
+        assert!(index
+            .register_bundle(bundle_id, witness_id, contract_id)
+            .unwrap());
+        assert!(index.register_operation(transition.id(), bundle_id).unwrap());
+        let TypedAssigns::Declarative(rights) = transition.assignments.get(&ty).unwrap() else {
+            unreachable!()
+        };
+        index
+            .index_transition_assignments(contract_id, rights, transition.id(), ty, witness_id)
+            .unwrap();
+        assert!(index.register_spender(Opout::new(opid, ty, 0), spender).unwrap());
+        index.commit_transaction().unwrap();
+
+        let mut index = SqlIndex::open(&path).unwrap();
+        assert_eq!(index.contract_ids().unwrap().collect::<Vec<_>>(), vec![contract_id]);
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(
+            index.opouts_by_outputs(contract_id, outputs.clone()).unwrap(),
+            bset![Opout::new(opid, ty, 0), Opout::new(opid, ty, 1)]
+        );
+        assert_eq!(index.bundle_id_for_op(transition.id()).unwrap(), bundle_id);
+        let (witness_ids, bundle_contract) = index.bundle_info(bundle_id).unwrap();
+        assert_eq!(witness_ids.collect::<Vec<_>>(), vec![witness_id]);
+        assert_eq!(bundle_contract, contract_id);
+        assert_eq!(index.opouts_by_terminals([terminal]).unwrap(), bset![Opout::new(opid, ty, 2)]);
+        let spenders = index.spenders(Opout::new(opid, ty, 0)).unwrap();
+        assert_eq!(spenders.collect::<Vec<_>>(), vec![spender]);
+
+        index.begin_transaction().unwrap();
+        assert!(!index.register_spender(Opout::new(opid, ty, 0), spender).unwrap());
+        index.rollback_transaction();
+
+        assert!(index
+            .remove_contract(contract_id, &bset![opid, transition.id()])
+            .unwrap());
+        let index = SqlIndex::open(&path).unwrap();
+        assert_eq!(index.contract_ids().unwrap().count(), 0);
+        assert_eq!(index.bundle_ids().unwrap().count(), 0);
+        assert!(index.opouts_by_outputs(contract_id, outputs).is_err());
+        assert!(index.opouts_by_terminals([terminal]).unwrap().is_empty());
+        assert_eq!(index.spenders(Opout::new(opid, ty, 0)).unwrap().count(), 0);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn stash_persists() {
+        let dir = test_dir("stash");
+        let path = dir.join("stash.db");
+        let schema = Schema::strict_dumb();
+        let iface = Iface::strict_dumb();
+        let mut older = IfaceImpl::strict_dumb();
+        older.schema_id = schema.schema_id();
+        older.iface_id = iface.iface_id();
+        let mut newer = older.clone();
+        newer.timestamp = older.timestamp + 1;
+        let contract_id = ContractId::strict_dumb();
+        let bindings = |stash: &SqlStash| {
+            stash
+                .iimpl_bindings(contract_id)
+                .unwrap()
+                .map(|(_, impl_id)| impl_id)
+                .collect::<Vec<_>>()
+        };
+
+        let mut stash = SqlStash::open(&path).unwrap();
+        stash.replace_schema(schema.clone()).unwrap();
+        stash.replace_iface(iface.clone()).unwrap();
+        assert!(stash.replace_iimpl(newer.clone()).unwrap());
+        assert!(stash.replace_iimpl(older.clone()).unwrap());
+        assert!(!stash.replace_iimpl(older.clone()).unwrap());
+
+        let mut stash = SqlStash::open(&path).unwrap();
+        assert_eq!(stash.iimpls(schema.schema_id()).unwrap().count(), 2);
+        let schema_ifaces = stash.schema(schema.schema_id()).unwrap();
+        assert_eq!(schema_ifaces.iimpls.get(&iface.name), Some(&newer));
+
+        stash.begin_transaction().unwrap();
+        assert!(stash.bind_iimpl(contract_id, older.impl_id()).unwrap());
+        assert!(stash.set_archived(contract_id, true).unwrap());
+        stash.rollback_transaction();
+        assert!(bindings(&stash).is_empty());
+        assert_eq!(stash.archived_contracts().unwrap().count(), 0);
+
+        assert!(stash.bind_iimpl(contract_id, older.impl_id()).unwrap());
+        assert!(stash.bind_iimpl(contract_id, newer.impl_id()).unwrap());
+        assert!(!stash.bind_iimpl(contract_id, newer.impl_id()).unwrap());
+        assert!(stash.set_archived(contract_id, true).unwrap());
+
+        let mut stash = SqlStash::open(&path).unwrap();
+        assert_eq!(bindings(&stash), vec![older.impl_id(), newer.impl_id()]);
+        assert_eq!(stash.archived_contracts().unwrap().collect::<Vec<_>>(), vec![contract_id]);
+
+        assert!(stash.set_archived(contract_id, false).unwrap());
+        assert!(stash.remove_schema(schema.schema_id()).unwrap());
+        let stash = SqlStash::open(&path).unwrap();
+        assert_eq!(stash.archived_contracts().unwrap().count(), 0);
+        assert!(stash.schema(schema.schema_id()).is_err());
+        assert_eq!(stash.iimpls(schema.schema_id()).unwrap().count(), 0);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn shared_transaction() {
+        let dir = test_dir("shared");
+        let seal = seal();
+        let contract_id = ContractId::strict_dumb();
+        let (mut stash, mut state, mut index) = open_shared(&dir).unwrap();
+
+        // Index commits its part, but the stash fails afterwards, so the stock
+        // rolls back the whole transaction
+        stash.begin_transaction().unwrap();
+        state.begin_transaction().unwrap();
+        index.begin_transaction().unwrap();
+        stash.add_secret_seal(seal).unwrap();
+        index.register_contract(contract_id).unwrap();
+        index.commit_transaction().unwrap();
+        state.commit_transaction().unwrap();
+        stash.rollback_transaction();
+        state.rollback_transaction();
+        index.rollback_transaction();
+        assert_eq!(stash.secret_seals().unwrap().count(), 0);
+
+        assert_eq!(index.contract_ids().unwrap().count(), 0);
+        drop((stash, state, index));
+
+        let (stash, _, index) = open_shared(&dir).unwrap();
+        assert_eq!(stash.secret_seals().unwrap().count(), 0);
+        assert_eq!(index.contract_ids().unwrap().count(), 0);
+
+        let mut stock = Stock::with(stash, SqlState::in_memory().unwrap(), index);
+        stock.store_secret_seal(seal).unwrap();
+        drop(stock);
+        let (stash, ..) = open_shared(&dir).unwrap();
+        assert_eq!(stash.secret_seals().unwrap().collect::<Vec<_>>(), vec![seal]);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn many_contracts() {
+        let dir = test_dir("many");
+        let schema = Schema::strict_dumb();
+        let geneses = (0..300)
+            .map(|n| {
+                let mut genesis = genesis();
+                genesis.timestamp = n;
+                genesis
+            })
+            .collect::<Vec<_>>();
+        {
+            let (mut stash, mut state, mut index) = open_shared(&dir).unwrap();
+            stash.begin_transaction().unwrap();
+            state.begin_transaction().unwrap();
+            index.begin_transaction().unwrap();
+            for genesis in &geneses {
+                assert!(stash.replace_genesis(genesis.clone()).unwrap());
+                state.register_contract(&schema, genesis).unwrap();
+                assert!(index.register_contract(genesis.contract_id()).unwrap());
+            }
+            stash.commit_transaction().unwrap();
+            state.commit_transaction().unwrap();
+            index.commit_transaction().unwrap();
+        }
+
+        let (stash, state, index) = open_shared(&dir).unwrap();
+        assert_eq!(stash.geneses().unwrap().count(), 300);
+        assert_eq!(state.contract_ids().unwrap().count(), 300);
+        assert_eq!(index.contract_ids().unwrap().count(), 300);
+        let contract_id = geneses[299].contract_id();
+        assert_eq!(stash.genesis(contract_id).unwrap(), &geneses[299]);
+        assert_eq!(state.contract_state(contract_id).unwrap().rights_all().count(), 2);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn consign_unknown() {
+        let dir = test_dir("consign");
+        let mut stock = Stock::open_sqlite(&dir).unwrap();
+        let seal = seal();
+        stock.store_secret_seal(seal).unwrap();
+        let contract_id = ContractId::copy_from_slice([0xAB; 32]).unwrap();
+        assert!(stock
+            .consign::<true>(contract_id, [], [seal.conceal()])
+            .is_err());
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
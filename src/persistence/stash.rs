@@ -19,13 +19,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cmp::Reverse;
 use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::fmt::Debug;
+use std::iter;
 
 use aluvm::library::{Lib, LibId};
 use amplify::confinement;
-use amplify::confinement::{Confined, MediumBlob, TinyOrdMap};
+use amplify::confinement::{Confined, MediumBlob, SmallOrdMap, TinyOrdMap};
 use bp::dbc::anchor::MergeError;
 use bp::dbc::tapret::TapretCommitment;
 use commit_verify::mpc;
@@ -39,14 +41,16 @@ use strict_types::typesys::UnknownType;
 use strict_types::TypeSystem;
 
 use crate::containers::{
-    BundledWitness, Consignment, ConsignmentExt, ContentId, ContentRef, ContentSigs, Kit,
-    SealWitness, SigBlob, Supplement, TrustLevel,
+    Backup, BundledWitness, Consignment, ConsignmentExt, ConsignmentHeader, ConsignmentTrailer,
+    ContentId, ContentRef, ContentSigs, Kit, SealWitness, SigBlob, Supplement, TrustLevel,
 };
 use crate::interface::{
-    ContractBuilder, Iface, IfaceClass, IfaceId, IfaceImpl, IfaceRef, TransitionBuilder,
+    ContractBuilder, Iface, IfaceClass, IfaceId, IfaceImpl, IfaceRef, ImplId, TransitionBuilder,
 };
-use crate::persistence::{ContractIfaceError, StoreTransaction};
-use crate::{MergeReveal, MergeRevealError, SecretSeal, LIB_NAME_RGB_STD};
+use crate::persistence::{
+    serialized_size, ContractIfaceError, GcReport, PurgeReport, StoreTransaction,
+};
+use crate::{BundleExt, MergeReveal, MergeRevealError, SecretSeal, LIB_NAME_RGB_STD};
 
 #[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
 #[display(inner)]
@@ -93,6 +97,27 @@ impl<P: StashProvider> From<ProviderError<<P as StashReadProvider>::Error>> for
     }
 }
 
+impl<P: StashProvider> StashError<P> {
+    fn with_write_provider(err: ProviderError<<P as StashWriteProvider>::Error>) -> Self {
+        match err {
+            ProviderError::Inconsistency(e) => StashError::Inconsistency(e),
+            ProviderError::Connectivity(e) => StashError::WriteProvider(e),
+            ProviderError::Iface(e) => StashError::Data(StashDataError::NoAbstractIface(e)),
+        }
+    }
+}
+
+impl<E: Error> ProviderError<E> {
+    /// Converts the connectivity error, keeping the other errors unchanged.
+    pub fn map_connectivity<E2: Error>(self, f: impl FnOnce(E) -> E2) -> ProviderError<E2> {
+        match self {
+            ProviderError::Inconsistency(e) => ProviderError::Inconsistency(e),
+            ProviderError::Iface(e) => ProviderError::Iface(e),
+            ProviderError::Connectivity(e) => ProviderError::Connectivity(f(e)),
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
 #[display(doc_comments)]
 pub enum StashInconsistency {
@@ -111,6 +136,9 @@ pub enum StashInconsistency {
     /// interface {0::<0} is not implemented for the schema {1::<0}.
     IfaceImplAbsent(IfaceId, SchemaId),
 
+    /// interface implementation {0} is unknown.
+    ImplAbsent(ImplId),
+
     /// transition {0} is absent.
     OperationAbsent(OpId),
 
@@ -150,6 +178,17 @@ pub enum StashDataError {
     /// schema {0} doesn't implement interface {1}.
     NoIfaceImpl(SchemaId, IfaceId),
 
+    /// interface implementation {0} is made for schema {1} and can't be used
+    /// by contract {2}.
+    ImplSchemaMismatch(ImplId, SchemaId, ContractId),
+
+    /// interface implementation {0} implements interface {1}, which is not
+    /// named {2}.
+    ImplIfaceMismatch(ImplId, IfaceId, TypeName),
+
+    /// too many identities with assigned trust level.
+    TooManyIdentities,
+
     #[from]
     #[display(inner)]
     NoAbstractIface(ContractIfaceError),
@@ -230,6 +269,44 @@ impl<P: StashProvider> Stash<P> {
     ) -> Result<&'a IfaceImpl, StashError<P>> {
         Ok(self.provider.impl_for::<C>(schema_ifaces)?)
     }
+    pub(super) fn iimpls(
+        &self,
+        schema_id: SchemaId,
+    ) -> Result<impl Iterator<Item = &IfaceImpl> + '_, StashError<P>> {
+        self.provider
+            .iimpls(schema_id)
+            .map_err(StashError::ReadProvider)
+    }
+    pub(super) fn iimpl(&self, impl_id: ImplId) -> Result<&IfaceImpl, StashError<P>> {
+        Ok(self.provider.iimpl(impl_id)?)
+    }
+    pub(super) fn iimpl_bindings(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<impl Iterator<Item = (&TypeName, ImplId)> + '_, StashError<P>> {
+        self.provider
+            .iimpl_bindings(contract_id)
+            .map_err(StashError::ReadProvider)
+    }
+    /// Returns implementation which the contract is bound to for the interface
+    /// implemented by `iimpl`, or `iimpl` itself if the contract was never
+    /// bound to a specific implementation.
+    pub(super) fn bound_iimpl<'a>(
+        &'a self,
+        contract_id: ContractId,
+        iimpl: &'a IfaceImpl,
+    ) -> Result<&'a IfaceImpl, StashError<P>> {
+        let name = &self.iface(iimpl.iface_id)?.name;
+        let bound = self
+            .iimpl_bindings(contract_id)?
+            .filter(|(n, _)| *n == name)
+            .map(|(_, impl_id)| impl_id)
+            .last();
+        match bound {
+            Some(impl_id) => self.iimpl(impl_id),
+            None => Ok(iimpl),
+        }
+    }
 
     pub(super) fn geneses(&self) -> Result<impl Iterator<Item = &Genesis> + '_, StashError<P>> {
         self.provider.geneses().map_err(StashError::ReadProvider)
@@ -250,6 +327,33 @@ impl<P: StashProvider> Stash<P> {
     pub(super) fn witness(&self, witness_id: XWitnessId) -> Result<&SealWitness, StashError<P>> {
         Ok(self.provider.witness(witness_id)?)
     }
+    pub(super) fn witness_ids(
+        &self,
+    ) -> Result<impl Iterator<Item = XWitnessId> + '_, StashError<P>> {
+        self.provider
+            .witness_ids()
+            .map_err(StashError::ReadProvider)
+    }
+    pub(super) fn bundle_ids(&self) -> Result<impl Iterator<Item = BundleId> + '_, StashError<P>> {
+        self.provider
+            .bundle_ids()
+            .map_err(StashError::ReadProvider)
+    }
+    pub(super) fn extension_ids(&self) -> Result<impl Iterator<Item = OpId> + '_, StashError<P>> {
+        self.provider
+            .extension_ids()
+            .map_err(StashError::ReadProvider)
+    }
+    pub(super) fn extension(&self, opid: OpId) -> Result<&Extension, StashError<P>> {
+        Ok(self.provider.extension(opid)?)
+    }
+    pub(super) fn secret_seals(
+        &self,
+    ) -> Result<impl Iterator<Item = XChain<GraphSeal>> + '_, StashError<P>> {
+        self.provider
+            .secret_seals()
+            .map_err(StashError::ReadProvider)
+    }
 
     pub(super) fn supplements(
         &self,
@@ -278,6 +382,34 @@ impl<P: StashProvider> Stash<P> {
             .map_err(StashError::ReadProvider)
     }
 
+    pub(super) fn type_system(&self) -> Result<&TypeSystem, StashError<P>> {
+        self.provider
+            .type_system()
+            .map_err(StashError::ReadProvider)
+    }
+
+    pub(super) fn serialized_size(&self) -> Result<usize, StashError<P>> {
+        self.provider
+            .serialized_size()
+            .map_err(StashError::ReadProvider)
+    }
+
+    pub(super) fn identities(
+        &self,
+    ) -> Result<impl Iterator<Item = (&Identity, TrustLevel)> + '_, StashError<P>> {
+        self.provider
+            .identities()
+            .map_err(StashError::ReadProvider)
+    }
+
+    pub(super) fn attachments(
+        &self,
+    ) -> Result<impl Iterator<Item = (AttachId, &MediumBlob)> + '_, StashError<P>> {
+        self.provider
+            .attachments()
+            .map_err(StashError::ReadProvider)
+    }
+
     pub(super) fn extract<'a>(
         &self,
         schema: &Schema,
@@ -440,10 +572,12 @@ impl<P: StashProvider> Stash<P> {
                 .replace_iface(iface)
                 .map_err(StashError::WriteProvider)?;
         }
-        for iimpl in kit.iimpls {
-            self.provider
-                .replace_iimpl(iimpl)
-                .map_err(StashError::WriteProvider)?;
+        // Newer revisions go first, such that the older ones are kept as
+        // superseded without re-binding the contracts
+        let mut iimpls = kit.iimpls.into_iter().collect::<Vec<_>>();
+        iimpls.sort_by_key(|iimpl| Reverse((iimpl.version, iimpl.timestamp)));
+        for iimpl in iimpls {
+            self.replace_iimpl(iimpl)?;
         }
 
         // TODO: filter out non-trusted signers
@@ -462,6 +596,164 @@ impl<P: StashProvider> Stash<P> {
         Ok(())
     }
 
+    /// Adds interface implementation to the stash. If it supersedes the
+    /// implementation currently used for the same interface, contracts which
+    /// were never bound to a specific implementation get bound to the current
+    /// one, such that they keep using it until they are explicitly re-bound.
+    fn replace_iimpl(&mut self, iimpl: IfaceImpl) -> Result<bool, StashError<P>> {
+        let name = self.iface(iimpl.iface_id)?.name.clone();
+        let current = self
+            .schema(iimpl.schema_id)?
+            .iimpls
+            .get(&name)
+            .filter(|current| iimpl.supersedes(current))
+            .map(IfaceImpl::impl_id);
+        if let Some(current) = current {
+            let contract_ids = self
+                .geneses()?
+                .filter(|genesis| genesis.schema_id == iimpl.schema_id)
+                .map(Genesis::contract_id)
+                .collect::<Vec<_>>();
+            for contract_id in contract_ids {
+                if self.iimpl_bindings(contract_id)?.any(|(n, _)| n == &name) {
+                    continue;
+                }
+                self.provider
+                    .bind_iimpl(contract_id, current)
+                    .map_err(StashError::with_write_provider)?;
+            }
+        }
+        self.provider
+            .replace_iimpl(iimpl)
+            .map_err(StashError::WriteProvider)
+    }
+
+    pub(super) fn bind_iimpl(
+        &mut self,
+        contract_id: ContractId,
+        impl_id: ImplId,
+    ) -> Result<bool, StashError<P>> {
+        self.check_binding(contract_id, impl_id)?;
+        self.begin_transaction()?;
+        let changed = self
+            .provider
+            .bind_iimpl(contract_id, impl_id)
+            .inspect_err(|_| self.rollback_transaction())
+            .map_err(StashError::with_write_provider)?;
+        self.commit_transaction()?;
+        Ok(changed)
+    }
+
+    /// Checks that the interface implementation is known and is made for the
+    /// contract schema, returning the name of the implemented interface.
+    fn check_binding(
+        &self,
+        contract_id: ContractId,
+        impl_id: ImplId,
+    ) -> Result<&TypeName, StashError<P>> {
+        let schema_id = self.genesis(contract_id)?.schema_id;
+        let iimpl = self.iimpl(impl_id)?;
+        if iimpl.schema_id != schema_id {
+            return Err(
+                StashDataError::ImplSchemaMismatch(impl_id, iimpl.schema_id, contract_id).into()
+            );
+        }
+        Ok(&self.iface(iimpl.iface_id)?.name)
+    }
+
+    /// Merges backup data into the stash. Contract state and index must be
+    /// updated separately.
+    pub(super) fn consume_backup(&mut self, backup: Backup) -> Result<(), StashError<P>> {
+        // Implementations are imported before the contracts, such that the
+        // contracts from the backup are not bound to superseded ones
+        self.consume_kit(Kit {
+            version: backup.version,
+            ifaces: backup.ifaces,
+            schemata: backup.schemata,
+            iimpls: Confined::from_collection_unsafe(backup.iimpls.into_inner()),
+            supplements: none!(),
+            types: backup.types,
+            scripts: backup.scripts,
+            signatures: none!(),
+        })?;
+        for genesis in backup.geneses.into_values() {
+            let genesis = match self.genesis(genesis.contract_id()) {
+                Ok(g) => g.clone().merge_reveal(genesis)?,
+                Err(_) => genesis,
+            };
+            self.provider
+                .replace_genesis(genesis)
+                .map_err(StashError::WriteProvider)?;
+        }
+        for (opid, extension) in backup.extensions {
+            let extension = match self.provider.extension(opid) {
+                Ok(e) => e.clone().merge_reveal(extension)?,
+                Err(_) => extension,
+            };
+            self.provider
+                .replace_extension(extension)
+                .map_err(StashError::WriteProvider)?;
+        }
+        for bundle in backup.bundles.into_values() {
+            self.consume_bundle(bundle)?;
+        }
+        for witness in backup.witnesses.into_values() {
+            self.consume_witness(witness)?;
+        }
+
+        for (id, attach) in backup.attachments {
+            self.provider
+                .replace_attachment(id, attach)
+                .map_err(StashError::WriteProvider)?;
+        }
+        for seal in backup.secret_seals {
+            self.provider
+                .add_secret_seal(seal)
+                .map_err(StashError::WriteProvider)?;
+        }
+        for (identity, trust) in backup.identities {
+            self.provider
+                .set_trust(identity, trust)
+                .map_err(|_| StashDataError::TooManyIdentities)?;
+        }
+        for contract_id in backup.archived {
+            self.provider
+                .set_archived(contract_id, true)
+                .map_err(StashError::WriteProvider)?;
+        }
+        // Bindings are checked against the implementations imported from the
+        // backup or already known to the stash
+        for (contract_id, bindings) in backup.bindings {
+            for (name, impl_ids) in bindings {
+                for impl_id in impl_ids {
+                    let iface_id = self.iimpl(impl_id)?.iface_id;
+                    if self.check_binding(contract_id, impl_id)? != &name {
+                        let err = StashDataError::ImplIfaceMismatch(impl_id, iface_id, name);
+                        return Err(err.into());
+                    }
+                    self.provider
+                        .bind_iimpl(contract_id, impl_id)
+                        .map_err(StashError::with_write_provider)?;
+                }
+            }
+        }
+
+        for suppl in backup.supplements {
+            self.provider
+                .add_supplement(suppl)
+                .map_err(StashError::WriteProvider)?;
+        }
+        // Unlike kits, backups carry signatures which were already accepted by
+        // the stash, so failure to import them is an error
+        for (content_id, sigs) in backup.signatures {
+            self.provider
+                .import_sigs(content_id, sigs)
+                .map_err(StashError::WriteProvider)?;
+        }
+
+        Ok(())
+    }
+
     pub(super) fn resolve_secrets<const TRANSFER: bool>(
         &self,
         mut consignment: Consignment<TRANSFER>,
@@ -474,21 +766,113 @@ impl<P: StashProvider> Stash<P> {
         Ok(consignment)
     }
 
+    /// Reveals the seals of the terminal bundles known to the stash inside a
+    /// bundled witness read from a consignment stream.
+    pub(super) fn resolve_terminal_secrets(
+        &self,
+        bundled_witness: &mut BundledWitness,
+        terminals: &SmallOrdMap<BundleId, XChain<SecretSeal>>,
+    ) -> Result<(), StashError<P>> {
+        for bundle in bundled_witness.anchored_bundles.bundles_mut() {
+            let Some(secret) = terminals.get(&bundle.bundle_id()) else {
+                continue;
+            };
+            if let Some(seal) = self
+                .provider
+                .seal_secret(*secret)
+                .map_err(StashError::ReadProvider)?
+            {
+                bundle.reveal_seal(seal);
+            }
+        }
+        Ok(())
+    }
+
     pub(super) fn consume_consignment<const TRANSFER: bool>(
         &mut self,
         consignment: Consignment<TRANSFER>,
     ) -> Result<(), StashError<P>> {
         let contract_id = consignment.contract_id();
+        let Consignment {
+            version,
+            transfer,
+            terminals,
+            genesis,
+            extensions,
+            bundles,
+            schema,
+            ifaces,
+            supplements,
+            types,
+            scripts,
+            attachments,
+            signatures,
+        } = consignment;
+
+        self.consume_consignment_data(
+            ConsignmentHeader {
+                version,
+                transfer,
+                terminals,
+                genesis,
+                extensions,
+            },
+            ConsignmentTrailer {
+                schema,
+                ifaces,
+                supplements,
+                types,
+                scripts,
+                attachments,
+                signatures,
+            },
+        )?;
+        for bw in bundles {
+            self.consume_bundled_witness(contract_id, bw)?;
+        }
+        Ok(())
+    }
+
+    /// Consumes all consignment data except the bundles, which are consumed
+    /// separately with [`Self::consume_bundled_witness`].
+    pub(super) fn consume_consignment_data(
+        &mut self,
+        header: ConsignmentHeader,
+        trailer: ConsignmentTrailer,
+    ) -> Result<(), StashError<P>> {
+        let (ifaces, iimpls): (BTreeSet<_>, BTreeSet<_>) = trailer
+            .ifaces
+            .into_inner()
+            .into_iter()
+            .fold((bset!(), bset!()), |(mut keys, mut values), (k, v)| {
+                keys.insert(k);
+                values.insert(v);
+                (keys, values)
+            });
+
+        // Implementations are imported before the genesis, such that a new
+        // contract is not bound to the implementations it supersedes
+        self.consume_kit(Kit {
+            version: header.version,
+            ifaces: Confined::from_collection_unsafe(ifaces),
+            schemata: tiny_bset![trailer.schema],
+            iimpls: Confined::from_collection_unsafe(iimpls),
+            supplements: trailer.supplements,
+            types: trailer.types,
+            scripts: Confined::from_collection_unsafe(trailer.scripts.into_inner()),
+            signatures: trailer.signatures,
+        })?;
 
+        let contract_id = header.genesis.contract_id();
         let genesis = match self.genesis(contract_id) {
-            Ok(g) => g.clone().merge_reveal(consignment.genesis)?,
-            Err(_) => consignment.genesis,
+            Ok(g) => g.clone().merge_reveal(header.genesis)?,
+            Err(_) => header.genesis,
         };
         self.provider
             .replace_genesis(genesis)
             .map_err(StashError::WriteProvider)?;
 
-        for extension in consignment.extensions {
+        for extension in header.extensions {
             let opid = extension.id();
             let extension = match self.provider.extension(opid) {
                 Ok(e) => e.clone().merge_reveal(extension)?,
@@ -499,39 +883,16 @@ impl<P: StashProvider> Stash<P> {
                 .map_err(StashError::WriteProvider)?;
         }
 
-        for bw in consignment.bundles {
-            self.consume_bundled_witness(contract_id, bw)?;
-        }
-
-        for (id, attach) in consignment.attachments {
+        for (id, attach) in trailer.attachments {
             self.provider
                 .replace_attachment(id, attach)
                 .map_err(StashError::WriteProvider)?;
         }
 
-        let (ifaces, iimpls): (BTreeSet<_>, BTreeSet<_>) = consignment
-            .ifaces
-            .into_inner()
-            .into_iter()
-            .fold((bset!(), bset!()), |(mut keys, mut values), (k, v)| {
-                keys.insert(k);
-                values.insert(v);
-                (keys, values)
-            });
-
-        self.consume_kit(Kit {
-            version: consignment.version,
-            ifaces: Confined::from_collection_unsafe(ifaces),
-            schemata: tiny_bset![consignment.schema],
-            iimpls: Confined::from_collection_unsafe(iimpls),
-            supplements: consignment.supplements,
-            types: consignment.types,
-            scripts: Confined::from_collection_unsafe(consignment.scripts.into_inner()),
-            signatures: consignment.signatures,
-        })
+        Ok(())
     }
 
-    fn consume_bundled_witness(
+    pub(super) fn consume_bundled_witness(
         &mut self,
         contract_id: ContractId,
         bundled_witness: BundledWitness,
@@ -599,6 +960,170 @@ impl<P: StashProvider> Stash<P> {
         self.commit_transaction()?;
         Ok(seal)
     }
+
+    pub(super) fn archived_contracts(
+        &self,
+    ) -> Result<impl Iterator<Item = ContractId> + '_, StashError<P>> {
+        self.provider
+            .archived_contracts()
+            .map_err(StashError::ReadProvider)
+    }
+
+    pub(super) fn set_archived(
+        &mut self,
+        contract_id: ContractId,
+        archived: bool,
+    ) -> Result<bool, StashError<P>> {
+        self.begin_transaction()?;
+        let changed = self
+            .provider
+            .set_archived(contract_id, archived)
+            .inspect_err(|_| self.rollback_transaction())
+            .map_err(StashError::WriteProvider)?;
+        self.commit_transaction()?;
+        Ok(changed)
+    }
+
+    /// Collects schemata, interfaces, libraries and types which are not
+    /// reachable from any of the contract geneses. Returns them together with
+    /// the type system reduced to the reachable types.
+    pub(super) fn gc_report(&self) -> Result<(GcReport, TypeSystem), StashError<P>> {
+        let mut schema_ids = BTreeSet::new();
+        for genesis in self.geneses()? {
+            schema_ids.insert(genesis.schema_id);
+        }
+
+        let mut iface_ids = BTreeSet::new();
+        let mut lib_ids = BTreeSet::new();
+        let mut sem_ids = BTreeSet::new();
+        for schema_id in &schema_ids {
+            let schema_ifaces = self.schema(*schema_id)?;
+            sem_ids.extend(schema_ifaces.schema.types());
+            lib_ids.extend(schema_ifaces.schema.libs());
+            // Parent interfaces are reachable as well, since they are used to
+            // look up implementations for the interface classes. Interfaces of
+            // the superseded implementations are kept for the bound contracts.
+            let mut queue = self
+                .iimpls(*schema_id)?
+                .map(|iimpl| iimpl.iface_id)
+                .collect::<Vec<_>>();
+            while let Some(iface_id) = queue.pop() {
+                if !iface_ids.insert(iface_id) {
+                    continue;
+                }
+                if let Ok(iface) = self.provider.iface(iface_id) {
+                    sem_ids.extend(iface.types());
+                    queue.extend(iface.inherits.iter().copied());
+                }
+            }
+        }
+        let type_system = self.type_system()?;
+        let types = type_system.extract(sem_ids)?;
+
+        let mut report = GcReport {
+            types: serialized_size(type_system).saturating_sub(serialized_size(&types)),
+            ..default!()
+        };
+        for schema_ifaces in self.schemata()? {
+            let schema_id = schema_ifaces.schema.schema_id();
+            if !schema_ids.contains(&schema_id) {
+                report.schemata.insert(schema_id, serialized_size(schema_ifaces));
+                report
+                    .iimpls
+                    .extend(self.iimpls(schema_id)?.map(IfaceImpl::impl_id));
+            }
+        }
+        for iface in self.ifaces()? {
+            let iface_id = iface.iface_id();
+            if !iface_ids.contains(&iface_id) {
+                report.ifaces.insert(iface_id, serialized_size(iface));
+            }
+        }
+        for lib in self.provider.libs().map_err(StashError::ReadProvider)? {
+            let lib_id = lib.id();
+            if !lib_ids.contains(&lib_id) {
+                report.libs.insert(lib_id, serialized_size(lib));
+            }
+        }
+        for content_ref in report.content_refs() {
+            for suppl in self.supplements(content_ref)? {
+                report
+                    .supplements
+                    .insert(suppl.suppl_id(), serialized_size(&suppl));
+            }
+        }
+
+        Ok((report, types))
+    }
+
+    pub(super) fn collect_garbage(
+        &mut self,
+        report: &GcReport,
+        types: TypeSystem,
+    ) -> Result<(), StashError<P>> {
+        for schema_id in report.schemata.keys() {
+            self.provider
+                .remove_schema(*schema_id)
+                .map_err(StashError::WriteProvider)?;
+        }
+        for iface_id in report.ifaces.keys() {
+            self.provider
+                .remove_iface(*iface_id)
+                .map_err(StashError::WriteProvider)?;
+        }
+        for lib_id in report.libs.keys() {
+            self.provider
+                .remove_lib(*lib_id)
+                .map_err(StashError::WriteProvider)?;
+        }
+        for content_ref in report.content_refs() {
+            self.provider
+                .remove_supplements(content_ref)
+                .and_then(|_| self.provider.remove_sigs(content_ref.into()))
+                .map_err(StashError::WriteProvider)?;
+        }
+        for suppl_id in report.supplements.keys() {
+            self.provider
+                .remove_sigs(ContentId::Suppl(*suppl_id))
+                .map_err(StashError::WriteProvider)?;
+        }
+        if report.types > 0 {
+            self.provider
+                .replace_types(types)
+                .map_err(StashError::WriteProvider)?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn purge(&mut self, report: &PurgeReport) -> Result<(), StashError<P>> {
+        let contract_id = report.contract_id;
+        self.provider
+            .set_archived(contract_id, false)
+            .and_then(|_| self.provider.remove_genesis(contract_id))
+            .and_then(|_| self.provider.remove_supplements(ContentRef::Genesis(contract_id)))
+            .map_err(StashError::WriteProvider)?;
+        for opid in &report.extensions {
+            self.provider
+                .remove_extension(*opid)
+                .map_err(StashError::WriteProvider)?;
+        }
+        for bundle_id in &report.bundles {
+            self.provider
+                .remove_bundle(*bundle_id)
+                .map_err(StashError::WriteProvider)?;
+        }
+        for witness_id in &report.witnesses {
+            self.provider
+                .remove_witness(*witness_id)
+                .map_err(StashError::WriteProvider)?;
+        }
+        for seal in &report.secret_seals {
+            self.provider
+                .remove_secret_seal(*seal)
+                .map_err(StashError::WriteProvider)?;
+        }
+        Ok(())
+    }
 }
 
 impl<P: StashProvider> StoreTransaction for Stash<P> {
@@ -627,6 +1152,7 @@ pub trait StashReadProvider {
 
     fn type_system(&self) -> Result<&TypeSystem, Self::Error>;
     fn lib(&self, id: LibId) -> Result<&Lib, ProviderError<Self::Error>>;
+    fn libs(&self) -> Result<impl Iterator<Item = &Lib>, Self::Error>;
 
     fn ifaces(&self) -> Result<impl Iterator<Item = &Iface>, Self::Error>;
     fn iface(&self, iface: impl Into<IfaceRef>) -> Result<&Iface, ProviderError<Self::Error>>;
@@ -652,6 +1178,8 @@ pub trait StashReadProvider {
     }
 
     fn get_trust(&self, identity: &Identity) -> Result<TrustLevel, Self::Error>;
+    /// Lists identities with explicitly assigned trust levels.
+    fn identities(&self) -> Result<impl Iterator<Item = (&Identity, TrustLevel)>, Self::Error>;
     fn supplement(&self, content_ref: ContentRef) -> Result<Option<&Supplement>, Self::Error>;
     fn supplements(
         &self,
@@ -665,6 +1193,7 @@ pub trait StashReadProvider {
     fn extension_ids(&self) -> Result<impl Iterator<Item = OpId>, Self::Error>;
     fn extension(&self, op_id: OpId) -> Result<&Extension, ProviderError<Self::Error>>;
     fn witness(&self, witness_id: XWitnessId) -> Result<&SealWitness, ProviderError<Self::Error>>;
+    fn attachments(&self) -> Result<impl Iterator<Item = (AttachId, &MediumBlob)>, Self::Error>;
 
     fn taprets(&self) -> Result<impl Iterator<Item = (XWitnessId, TapretCommitment)>, Self::Error>;
     fn seal_secret(
@@ -672,6 +1201,30 @@ pub trait StashReadProvider {
         secret: XChain<SecretSeal>,
     ) -> Result<Option<XChain<GraphSeal>>, Self::Error>;
     fn secret_seals(&self) -> Result<impl Iterator<Item = XChain<GraphSeal>>, Self::Error>;
+
+    /// Lists contracts which are archived, i.e. kept in the stash but hidden
+    /// from the contract listings. Providers which don't support archival
+    /// list no contracts.
+    fn archived_contracts(&self) -> Result<impl Iterator<Item = ContractId>, Self::Error> {
+        Ok(iter::empty())
+    }
+
+    /// Lists all known implementations of interfaces for the schema, including
+    /// the ones superseded by newer revisions.
+    fn iimpls(&self, schema_id: SchemaId) -> Result<impl Iterator<Item = &IfaceImpl>, Self::Error>;
+    /// Returns known interface implementation, which may be superseded.
+    fn iimpl(&self, impl_id: ImplId) -> Result<&IfaceImpl, ProviderError<Self::Error>>;
+    /// Lists implementations which the contract was bound to, grouped by the
+    /// interface name in the order of binding. The last implementation for
+    /// each of the interface names is the one currently used by the contract.
+    fn iimpl_bindings(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<impl Iterator<Item = (&TypeName, ImplId)>, Self::Error>;
+
+    /// Size of the provider data in their strict-serialized form. Providers
+    /// which can't estimate the size of their data report zero.
+    fn serialized_size(&self) -> Result<usize, Self::Error> { Ok(0) }
 }
 
 pub trait StashWriteProvider: StoreTransaction<TransactionErr = Self::Error> {
@@ -679,6 +1232,10 @@ pub trait StashWriteProvider: StoreTransaction<TransactionErr = Self::Error> {
 
     fn replace_schema(&mut self, schema: Schema) -> Result<bool, Self::Error>;
     fn replace_iface(&mut self, iface: Iface) -> Result<bool, Self::Error>;
+    /// Adds interface implementation. If the schema already has an
+    /// implementation for the interface with the same name, the older of them
+    /// is kept as a superseded revision. Returns whether the implementation
+    /// was not known before.
     fn replace_iimpl(&mut self, iimpl: IfaceImpl) -> Result<bool, Self::Error>;
     fn replace_genesis(&mut self, genesis: Genesis) -> Result<bool, Self::Error>;
     fn replace_extension(&mut self, extension: Extension) -> Result<bool, Self::Error>;
@@ -699,4 +1256,39 @@ pub trait StashWriteProvider: StoreTransaction<TransactionErr = Self::Error> {
     where I: IntoIterator<Item = (Identity, SigBlob)>;
 
     fn add_secret_seal(&mut self, seal: XChain<GraphSeal>) -> Result<bool, Self::Error>;
+
+    /// Marks contract as archived or restores it from the archive. Returns
+    /// whether the archival status has changed.
+    fn set_archived(&mut self, contract_id: ContractId, archived: bool)
+    -> Result<bool, Self::Error>;
+
+    /// Binds contract to the known interface implementation, such that it is
+    /// used by the contract instead of the previously bound one or the one
+    /// provided by the schema. Returns `false` if the contract is already
+    /// bound to the implementation. Fails with
+    /// [`StashInconsistency::ImplAbsent`] if the implementation is not known
+    /// to the stash.
+    fn bind_iimpl(
+        &mut self,
+        contract_id: ContractId,
+        impl_id: ImplId,
+    ) -> Result<bool, ProviderError<Self::Error>>;
+
+    /// Removes contract genesis together with the contract bindings to the
+    /// interface implementations.
+    fn remove_genesis(&mut self, contract_id: ContractId) -> Result<bool, Self::Error>;
+    fn remove_extension(&mut self, opid: OpId) -> Result<bool, Self::Error>;
+    fn remove_bundle(&mut self, bundle_id: BundleId) -> Result<bool, Self::Error>;
+    fn remove_witness(&mut self, witness_id: XWitnessId) -> Result<bool, Self::Error>;
+    fn remove_supplements(&mut self, content_ref: ContentRef) -> Result<bool, Self::Error>;
+    fn remove_secret_seal(&mut self, seal: XChain<GraphSeal>) -> Result<bool, Self::Error>;
+    /// Removes schema together with all its interface implementations,
+    /// including the superseded ones.
+    fn remove_schema(&mut self, schema_id: SchemaId) -> Result<bool, Self::Error>;
+    fn remove_iface(&mut self, iface_id: IfaceId) -> Result<bool, Self::Error>;
+    fn remove_lib(&mut self, lib_id: LibId) -> Result<bool, Self::Error>;
+    fn remove_sigs(&mut self, content_id: ContentId) -> Result<bool, Self::Error>;
+    /// Replaces the whole type system, unlike [`Self::consume_types`], which
+    /// extends it.
+    fn replace_types(&mut self, types: TypeSystem) -> Result<(), Self::Error>;
 }
@@ -29,9 +29,9 @@ use invoice::Amount;
 use rgb::validation::{ResolveWitness, WitnessResolverError};
 use rgb::vm::{ContractStateAccess, WitnessOrd};
 use rgb::{
-    AssetTag, AttachState, BlindingFactor, ContractId, DataState, Extension, Genesis, Operation,
-    RevealedAttach, RevealedData, RevealedValue, Schema, SchemaId, Transition, TransitionBundle,
-    VoidState, XWitnessId,
+    AssetTag, AttachState, BlindingFactor, ContractId, DataState, Extension, Genesis, Layer1,
+    Operation, Opout, RevealedAttach, RevealedData, RevealedValue, Schema, SchemaId, Transition,
+    TransitionBundle, VoidState, XWitnessId,
 };
 
 use crate::containers::{ConsignmentExt, ToWitnessId};
@@ -125,6 +125,12 @@ impl<P: StateProvider> State<P> {
             .map_err(StateError::ReadProvider)
     }
 
+    pub(super) fn serialized_size(&self) -> Result<usize, StateError<P>> {
+        self.provider
+            .serialized_size()
+            .map_err(StateError::ReadProvider)
+    }
+
     pub fn select_valid_witness(
         &self,
         witness_ids: impl IntoIterator<Item = impl Borrow<XWitnessId>>,
@@ -142,6 +148,48 @@ impl<P: StateProvider> State<P> {
         Err(StateError::Inconsistency(StateInconsistency::AbsentValidWitness))
     }
 
+    pub(super) fn witness_ord(&self, witness_id: XWitnessId) -> Result<WitnessOrd, StateError<P>> {
+        self.provider
+            .witness_ord(witness_id)
+            .map_err(StateError::ReadProvider)
+    }
+
+    pub(super) fn contract_ids(
+        &self,
+    ) -> Result<impl Iterator<Item = ContractId> + '_, StateError<P>> {
+        self.provider
+            .contract_ids()
+            .map_err(StateError::ReadProvider)
+    }
+
+    pub(super) fn register_contract(
+        &mut self,
+        schema: &Schema,
+        genesis: &Genesis,
+    ) -> Result<(), StateError<P>> {
+        self.provider
+            .register_contract(schema, genesis)
+            .map_err(StateError::WriteProvider)?;
+        Ok(())
+    }
+
+    pub(super) fn update_from_extension(
+        &mut self,
+        extension: &Extension,
+        witness_id: XWitnessId,
+        ord: WitnessOrd,
+    ) -> Result<(), StateError<P>> {
+        let contract_id = extension.contract_id;
+        let mut updater = self
+            .as_provider_mut()
+            .update_contract(contract_id)
+            .map_err(StateError::WriteProvider)?
+            .ok_or(StateInconsistency::UnknownContract(contract_id))?;
+        updater
+            .add_extension(extension, witness_id, ord)
+            .map_err(StateError::WriteProvider)
+    }
+
     pub fn update_from_bundle<R: ResolveWitness>(
         &mut self,
         contract_id: ContractId,
@@ -229,9 +277,30 @@ impl<P: StateProvider> State<P> {
         &mut self,
         resolver: impl ResolveWitness,
         after_height: u32,
+        layer1: Option<Layer1>,
     ) -> Result<UpdateRes, StateError<P>> {
         self.provider
-            .update_witnesses(resolver, after_height)
+            .update_witnesses(resolver, after_height, layer1)
+            .map_err(StateError::WriteProvider)
+    }
+
+    pub(super) fn prune(
+        &mut self,
+        contract_id: ContractId,
+        spends: impl IntoIterator<Item = (Opout, XWitnessId)>,
+        max_height: u32,
+    ) -> Result<usize, StateError<P>> {
+        self.provider
+            .prune(contract_id, spends, max_height)
+            .map_err(StateError::WriteProvider)
+    }
+
+    pub(super) fn remove_contract(
+        &mut self,
+        contract_id: ContractId,
+    ) -> Result<bool, StateError<P>> {
+        self.provider
+            .remove_contract(contract_id)
             .map_err(StateError::WriteProvider)
     }
 }
@@ -267,6 +336,14 @@ pub trait StateReadProvider {
     ) -> Result<Self::ContractRead<'_>, Self::Error>;
 
     fn is_valid_witness(&self, witness_id: XWitnessId) -> Result<bool, Self::Error>;
+
+    fn witness_ord(&self, witness_id: XWitnessId) -> Result<WitnessOrd, Self::Error>;
+
+    fn contract_ids(&self) -> Result<impl Iterator<Item = ContractId> + '_, Self::Error>;
+
+    /// Size of the provider data in their strict-serialized form. Providers
+    /// which can't estimate the size of their data report zero.
+    fn serialized_size(&self) -> Result<usize, Self::Error> { Ok(0) }
 }
 
 pub trait StateWriteProvider: StoreTransaction<TransactionErr = Self::Error> {
@@ -285,11 +362,36 @@ pub trait StateWriteProvider: StoreTransaction<TransactionErr = Self::Error> {
         contract_id: ContractId,
     ) -> Result<Option<Self::ContractWrite<'_>>, Self::Error>;
 
+    /// Re-resolves witnesses, except those mined before `after_height`. If
+    /// `layer1` is provided, witnesses from other layers 1 are left untouched
+    /// and are not passed to the resolver.
     fn update_witnesses(
         &mut self,
         resolver: impl ResolveWitness,
         after_height: u32,
+        layer1: Option<Layer1>,
     ) -> Result<UpdateRes, Self::Error>;
+
+    /// Removes from the contract state assignments spent by the provided
+    /// witnesses, if these witnesses are mined at or below `max_height`.
+    ///
+    /// Returns the number of removed assignments. Providers which don't
+    /// support pruning keep the whole state, which is the default.
+    fn prune(
+        &mut self,
+        contract_id: ContractId,
+        spends: impl IntoIterator<Item = (Opout, XWitnessId)>,
+        max_height: u32,
+    ) -> Result<usize, Self::Error> {
+        let _ = (contract_id, spends, max_height);
+        Ok(0)
+    }
+
+    /// Removes the contract state together with the witnesses which are not
+    /// used by any other contract.
+    ///
+    /// Returns whether the contract was known.
+    fn remove_contract(&mut self, contract_id: ContractId) -> Result<bool, Self::Error>;
 }
 
 pub trait ContractStateRead: ContractStateAccess {
@@ -24,41 +24,51 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::Infallible;
 use std::error::Error;
 use std::fmt::Debug;
+use std::io::Read;
+use std::mem;
+use std::ops::Deref;
+use std::sync::Arc;
 
-use amplify::confinement::{Confined, U24};
+use aluvm::library::LibId;
+use amplify::confinement::{self, Confined, TinyOrdMap, TinyVec, U24};
 use amplify::Wrapper;
 use bp::dbc::Method;
 use bp::seals::txout::CloseMethod;
-use bp::Vout;
+use bp::{Tx, Vout};
 use chrono::Utc;
+use commit_verify::Conceal;
 use invoice::{Amount, Beneficiary, InvoiceState, NonFungible, RgbInvoice};
 use rgb::validation::{DbcProof, EAnchor, ResolveWitness, WitnessResolverError};
+use rgb::vm::{ContractStateAccess, WitnessOrd};
 use rgb::{
-    validation, AssignmentType, BlindingFactor, BundleId, ContractId, DataState, GraphSeal,
-    Identity, OpId, Operation, Opout, SchemaId, SecretSeal, Transition, TxoSeal, XChain, XOutpoint,
-    XOutputSeal, XWitnessId,
+    validation, AssignmentType, Assignments, BlindingFactor, BundleId, ContractId, DataState,
+    ExposedSeal, GraphSeal, Identity, Layer1, OpId, Operation, Opout, Schema, SchemaId,
+    SecretSeal, Transition, TransitionType, TxoSeal, XChain, XOutpoint, XOutputSeal, XWitnessId,
 };
-use strict_encoding::FieldName;
+use strict_encoding::{FieldName, TypeName};
 
 use super::{
     ContractStateRead, Index, IndexError, IndexInconsistency, IndexProvider, IndexReadProvider,
-    IndexWriteProvider, MemIndex, MemStash, MemState, PersistedState, SchemaIfaces, Stash,
+    IndexWriteProvider, MemIndex, MemStash, MemState, PersistedState, SchemaIfaces, Spender, Stash,
     StashDataError, StashError, StashInconsistency, StashProvider, StashReadProvider,
     StashWriteProvider, State, StateError, StateInconsistency, StateProvider, StateReadProvider,
-    StateWriteProvider, StoreTransaction,
+    StateWriteProvider, StockEvent, StockObserver, StoreTransaction,
 };
 use crate::containers::{
-    AnchorSet, AnchoredBundles, Batch, BuilderSeal, BundledWitness, Consignment, ContainerVer,
-    ContentId, ContentRef, Contract, Fascia, Kit, SealWitness, SupplItem, SupplSub, Transfer,
-    TransitionDichotomy, TransitionInfo, TransitionInfoError, ValidConsignment, ValidContract,
-    ValidKit, ValidTransfer, VelocityHint, SUPPL_ANNOT_VELOCITY,
+    AnchorSet, AnchoredBundles, Backup, Batch, BuilderSeal, BundledWitness, Consignment,
+    ConsignmentHeader, ConsignmentTrailer, ContainerVer, ContentId, ContentRef, Contract,
+    ContractReader, Disclosure, Fascia, Kit, SealWitness, StreamError, SupplId, SupplItem,
+    SupplSub, Transfer, TransitionDichotomy, TransitionInfo, TransitionInfoError,
+    ValidConsignment, ValidContract, ValidKit, ValidTransfer, VelocityHint, SUPPL_ANNOT_VELOCITY,
 };
 use crate::info::{ContractInfo, IfaceInfo, SchemaInfo};
 use crate::interface::{
-    BuilderError, ContractBuilder, ContractIface, Iface, IfaceClass, IfaceId, IfaceRef,
-    IfaceWrapper, TransitionBuilder,
+    BuilderError, ContractBuilder, ContractIface, Iface, IfaceClass, IfaceId, IfaceImpl, IfaceRef,
+    IfaceWrapper, ImplId, OutpointFilter, TransitionBuilder,
 };
-use crate::{MergeRevealError, RevealError};
+#[cfg(feature = "fs")]
+use crate::persistence::fs::StockDir;
+use crate::{BundleExt, MergeRevealError, RevealError, TypedAssignsExt};
 
 pub type ContractAssignments = HashMap<XOutputSeal, HashMap<Opout, PersistedState>>;
 
@@ -108,6 +118,11 @@ pub enum StockError<
 
     /// witness {0} can't be resolved: {1}
     WitnessUnresolved(XWitnessId, WitnessResolverError),
+
+    #[cfg(feature = "fs")]
+    #[display(doc_comments)]
+    /// unable to persist stock data on disk: {0}
+    Persistence(String),
 }
 
 impl<S: StashProvider, H: StateProvider, P: IndexProvider, E: Error> From<StashError<S>>
@@ -274,6 +289,57 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> From<ContractIfaceErr
     fn from(err: ContractIfaceError) -> Self { Self::InvalidInput(err) }
 }
 
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum BackupError {
+    /// unable to construct backup: {0}
+    #[from]
+    Confinement(confinement::Error),
+}
+
+impl<S: StashProvider, H: StateProvider, P: IndexProvider> From<BackupError>
+    for StockError<S, H, P, BackupError>
+{
+    fn from(err: BackupError) -> Self { Self::InvalidInput(err) }
+}
+
+impl<S: StashProvider, H: StateProvider, P: IndexProvider> From<confinement::Error>
+    for StockError<S, H, P, BackupError>
+{
+    fn from(err: confinement::Error) -> Self { Self::InvalidInput(err.into()) }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum DisclosureError {
+    /// disclosure contains state transitions under unknown contract {0}.
+    UnknownContract(ContractId),
+
+    /// bundle {1} for contract {0} contains state transitions which are not a
+    /// part of the bundle, don't match their ids or belong to another
+    /// contract.
+    InvalidBundle(ContractId, BundleId),
+
+    /// witness transaction doesn't commit to the bundle {1} under contract
+    /// {0}.
+    InvalidAnchor(ContractId, BundleId),
+
+    /// state transition {1} doesn't match the schema of contract {0}.
+    SchemaMismatch(ContractId, OpId),
+}
+
+impl<S: StashProvider, H: StateProvider, P: IndexProvider> From<DisclosureError>
+    for StockError<S, H, P, DisclosureError>
+{
+    fn from(err: DisclosureError) -> Self { Self::InvalidInput(err) }
+}
+
+impl<S: StashProvider, H: StateProvider, P: IndexProvider> From<StreamError>
+    for StockError<S, H, P, StreamError>
+{
+    fn from(err: StreamError) -> Self { Self::InvalidInput(err) }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
 #[display(inner)]
 pub enum InputError {
@@ -285,6 +351,10 @@ pub enum InputError {
     Fascia(FasciaError),
     #[from]
     ContractIface(ContractIfaceError),
+    #[from]
+    Backup(BackupError),
+    #[from]
+    Disclosure(DisclosureError),
 }
 
 macro_rules! stock_err_conv {
@@ -307,6 +377,8 @@ macro_rules! stock_err_conv {
                     StockError::StateInconsistency(e) => StockError::StateInconsistency(e),
                     StockError::IndexInconsistency(e) => StockError::IndexInconsistency(e),
                     StockError::WitnessUnresolved(id, e) => StockError::WitnessUnresolved(id, e),
+                    #[cfg(feature = "fs")]
+                    StockError::Persistence(e) => StockError::Persistence(e),
                 }
             }
         }
@@ -328,16 +400,26 @@ impl From<Infallible> for FasciaError {
 impl From<Infallible> for ContractIfaceError {
     fn from(_: Infallible) -> Self { unreachable!() }
 }
+impl From<Infallible> for BackupError {
+    fn from(_: Infallible) -> Self { unreachable!() }
+}
+impl From<Infallible> for DisclosureError {
+    fn from(_: Infallible) -> Self { unreachable!() }
+}
 
 stock_err_conv!(Infallible, ComposeError);
 stock_err_conv!(Infallible, ConsignError);
 stock_err_conv!(Infallible, FasciaError);
 stock_err_conv!(Infallible, ContractIfaceError);
+stock_err_conv!(Infallible, BackupError);
+stock_err_conv!(Infallible, DisclosureError);
 stock_err_conv!(Infallible, InputError);
 stock_err_conv!(ComposeError, InputError);
 stock_err_conv!(ConsignError, InputError);
 stock_err_conv!(FasciaError, InputError);
 stock_err_conv!(ContractIfaceError, InputError);
+stock_err_conv!(BackupError, InputError);
+stock_err_conv!(DisclosureError, InputError);
 
 pub type StockErrorMem<E = Infallible> = StockError<MemStash, MemState, MemIndex, E>;
 pub type StockErrorAll<S = MemStash, H = MemState, P = MemIndex> = StockError<S, H, P, InputError>;
@@ -351,6 +433,9 @@ pub struct Stock<
     stash: Stash<S>,
     state: State<H>,
     index: Index<P>,
+    #[cfg(feature = "fs")]
+    dir: Option<StockDir>,
+    observers: Vec<Arc<dyn StockObserver>>,
 }
 
 impl<S: StashProvider, H: StateProvider, P: IndexProvider> Default for Stock<S, H, P>
@@ -364,10 +449,32 @@ where
             stash: default!(),
             state: default!(),
             index: default!(),
+            #[cfg(feature = "fs")]
+            dir: None,
+            observers: vec![],
         }
     }
 }
 
+/// Read-only copy of the [`Stock`] data, created with [`Stock::snapshot`].
+///
+/// Once created, the snapshot is cheap to clone and can be shared across
+/// threads, serving reads while the stock it was created from is being
+/// updated. The updates are not visible to the snapshot; a new snapshot must
+/// be created to see them.
+#[derive(Clone, Debug)]
+pub struct StockSnapshot<
+    S: StashProvider = MemStash,
+    H: StateProvider = MemState,
+    P: IndexProvider = MemIndex,
+>(Arc<Stock<S, H, P>>);
+
+impl<S: StashProvider, H: StateProvider, P: IndexProvider> Deref for StockSnapshot<S, H, P> {
+    type Target = Stock<S, H, P>;
+
+    fn deref(&self) -> &Self::Target { &self.0 }
+}
+
 impl Stock {
     #[inline]
     pub fn in_memory() -> Self {
@@ -379,10 +486,26 @@ impl Stock {
 mod fs {
     use std::path::PathBuf;
 
+    use std::fs::File;
+    use std::io;
+
     use strict_encoding::{DeserializeError, SerializeError};
 
     use super::*;
-    use crate::persistence::fs::FsStored;
+    use crate::persistence::fs::{FsConfig, FsStored, SyncPolicy};
+
+    /// Loads a part of the stock which can be re-computed from the stash.
+    ///
+    /// Returns `None` if the file is missing or its data can't be decoded. I/O
+    /// errors accessing an existing file (like missing permissions) are
+    /// returned, since they don't mean that the data are lost.
+    fn load_recoverable<T: FsStored>(path: PathBuf) -> Result<Option<T>, DeserializeError> {
+        match File::open(&path) {
+            Ok(_) => Ok(T::load(path).ok()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
 
     impl<S: StashProvider, H: StateProvider, I: IndexProvider> Stock<S, H, I>
     where
@@ -391,35 +514,72 @@ mod fs {
         I: FsStored,
     {
         pub fn new(path: impl ToOwned<Owned = PathBuf>) -> Self {
-            let mut filename = path.to_owned();
-            filename.push("stash.dat");
-            let stash = S::new(filename);
-
-            let mut filename = path.to_owned();
-            filename.push("state.dat");
-            let state = H::new(filename);
-
-            let mut filename = path.to_owned();
-            filename.push("index.dat");
-            let index = I::new(filename);
-
-            Stock::with(stash, state, index)
+            let dir = StockDir::new(path, default!());
+            let stash = S::new(dir.stash_filename());
+            let state = H::new(dir.state_filename());
+            let index = I::new(dir.index_filename());
+
+            let mut stock = Stock::with(stash, state, index);
+            stock.set_sync_policy(dir.sync_policy());
+            stock.dir = Some(dir);
+            stock
         }
 
+        /// Loads stock from the directory at `path`.
+        ///
+        /// If the previous write of the stock was interrupted, the stock is
+        /// either brought to the state of this write (if it has reached its
+        /// commit point), or the write is discarded, such that the loaded
+        /// stock always represents a consistent version of its data.
         pub fn load(path: impl ToOwned<Owned = PathBuf>) -> Result<Self, DeserializeError> {
-            let mut filename = path.to_owned();
-            filename.push("stash.dat");
-            let stash = S::load(filename)?;
-
-            let mut filename = path.to_owned();
-            filename.push("state.dat");
-            let state = H::load(filename)?;
-
-            let mut filename = path.to_owned();
-            filename.push("index.dat");
-            let index = I::load(filename)?;
+            let dir = StockDir::open(path, default!())?;
+            let stash = S::load(dir.stash_filename())?;
+            let state = H::load(dir.state_filename())?;
+            let index = I::load(dir.index_filename())?;
+
+            let mut stock = Stock::with(stash, state, index);
+            stock.set_sync_policy(dir.sync_policy());
+            stock.dir = Some(dir);
+            Ok(stock)
+        }
 
-            Ok(Stock::with(stash, state, index))
+        /// Loads stock from the directory at `path`, re-computing state and
+        /// index from the stash if their data are lost or can't be read. This
+        /// includes index files written before the index layout versioning,
+        /// which miss the transitions spending the indexed outputs.
+        pub fn load_recovering(
+            path: impl ToOwned<Owned = PathBuf>,
+            resolver: impl ResolveWitness,
+        ) -> Result<Self, StockError<S, H, I>> {
+            let persistence = |err: DeserializeError| StockError::Persistence(err.to_string());
+            let dir = StockDir::open(path, default!()).map_err(persistence)?;
+            let stash = S::load(dir.stash_filename()).map_err(persistence)?;
+            let state = load_recoverable::<H>(dir.state_filename()).map_err(persistence)?;
+            let index = load_recoverable::<I>(dir.index_filename()).map_err(persistence)?;
+            let (rebuild_state, rebuild_index) = (state.is_none(), index.is_none());
+
+            let mut stock = Stock::with(
+                stash,
+                state.unwrap_or_else(|| H::new(dir.state_filename())),
+                index.unwrap_or_else(|| I::new(dir.index_filename())),
+            );
+            stock.set_sync_policy(dir.sync_policy());
+            stock.dir = Some(dir);
+
+            match (rebuild_state, rebuild_index) {
+                (true, true) => stock.store_transaction(|stash, state, index| {
+                    Self::replay_index(stash, index, |_| true)?;
+                    Self::replay_state(stash, state, resolver, |_| true)
+                })?,
+                (true, false) => stock.store_transaction(|stash, state, _| {
+                    Self::replay_state(stash, state, resolver, |_| true)
+                })?,
+                (false, true) => stock.store_transaction(|stash, _, index| {
+                    Self::replay_index(stash, index, |_| true)
+                })?,
+                (false, false) => {}
+            }
+            Ok(stock)
         }
 
         pub fn is_dirty(&self) -> bool {
@@ -429,24 +589,55 @@ mod fs {
         }
 
         pub fn set_path(&mut self, path: impl ToOwned<Owned = PathBuf>) {
-            let mut filename = path.to_owned();
-            filename.push("stash.dat");
-            self.stash.as_provider_mut().set_filename(filename);
+            let dir = match self.dir.take() {
+                Some(mut dir) => {
+                    dir.set_path(path);
+                    dir
+                }
+                None => StockDir::new(path, default!()),
+            };
+            self.stash
+                .as_provider_mut()
+                .set_filename(dir.stash_filename());
+            self.state
+                .as_provider_mut()
+                .set_filename(dir.state_filename());
+            self.index
+                .as_provider_mut()
+                .set_filename(dir.index_filename());
+            let sync = dir.sync_policy();
+            self.dir = Some(dir);
+            self.set_sync_policy(sync);
+        }
+
+        /// Returns generation of the stock data last published to the disk.
+        pub fn generation(&self) -> Option<u64> { self.dir.as_ref().map(StockDir::generation) }
 
-            let mut filename = path.to_owned();
-            filename.push("state.dat");
-            self.state.as_provider_mut().set_filename(filename);
+        pub fn sync_policy(&self) -> SyncPolicy {
+            self.dir
+                .as_ref()
+                .map(StockDir::sync_policy)
+                .unwrap_or_default()
+        }
 
-            let mut filename = path.to_owned();
-            filename.push("index.dat");
-            self.index.as_provider_mut().set_filename(filename);
+        pub fn set_sync_policy(&mut self, sync: SyncPolicy) {
+            let config = FsConfig { sync, staged: true };
+            self.stash.as_provider_mut().set_fs_config(config);
+            self.state.as_provider_mut().set_fs_config(config);
+            self.index.as_provider_mut().set_fs_config(config);
+            if let Some(dir) = &mut self.dir {
+                dir.set_sync_policy(sync);
+            }
         }
 
-        pub fn store(&self) -> Result<(), SerializeError> {
+        pub fn store(&mut self) -> Result<(), SerializeError> {
             self.as_stash_provider().store()?;
             self.as_state_provider().store()?;
             self.as_index_provider().store()?;
 
+            if let Some(dir) = &mut self.dir {
+                dir.publish()?;
+            }
             Ok(())
         }
     }
@@ -458,9 +649,58 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
             stash: Stash::new(stash_provider),
             state: State::new(state_provider),
             index: Index::new(index_provider),
+            #[cfg(feature = "fs")]
+            dir: None,
+            observers: vec![],
         }
     }
 
+    /// Registers an observer receiving [`StockEvent`]s for the changes
+    /// committed to the stock.
+    pub fn add_observer(&mut self, observer: Arc<dyn StockObserver>) {
+        self.observers.push(observer)
+    }
+
+    /// Unregisters a previously added observer, returning whether it was
+    /// registered.
+    pub fn remove_observer(&mut self, observer: &Arc<dyn StockObserver>) -> bool {
+        let len = self.observers.len();
+        self.observers.retain(|o| !Arc::ptr_eq(o, observer));
+        self.observers.len() != len
+    }
+
+    fn notify(&self, event: StockEvent) {
+        for observer in &self.observers {
+            observer.on_event(&event);
+        }
+    }
+
+    /// Creates a read-only snapshot of the stock, which can be queried from
+    /// multiple threads independently from the further stock updates.
+    ///
+    /// The snapshot is a full copy of the provider data: it doesn't share
+    /// any data with the stock, and its creation takes time and memory
+    /// proportional to the stock size. Thus, it should be refreshed after a
+    /// series of updates (like consignment imports) and not after each read.
+    ///
+    /// Only providers which can be cloned, like the in-memory ones, support
+    /// snapshots. Database-backed providers should serve concurrent reads
+    /// with separate provider instances opened on the same database instead.
+    pub fn snapshot(&self) -> StockSnapshot<S, H, P>
+    where
+        S: Clone,
+        H: Clone,
+        P: Clone,
+    {
+        let mut stock = self.clone();
+        #[cfg(feature = "fs")]
+        {
+            stock.dir = None;
+        }
+        stock.observers.clear();
+        StockSnapshot(Arc::new(stock))
+    }
+
     #[doc(hidden)]
     pub fn as_stash_provider(&self) -> &S { self.stash.as_provider() }
     #[doc(hidden)]
@@ -493,12 +733,30 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         Ok(self.stash.schema(schema_id)?)
     }
 
+    /// Lists all known contracts, except archived ones.
     pub fn contracts(
         &self,
     ) -> Result<impl Iterator<Item = ContractInfo> + '_, StockError<S, H, P>> {
-        Ok(self.stash.geneses()?.map(ContractInfo::with))
+        let archived = self.stash.archived_contracts()?.collect::<BTreeSet<_>>();
+        Ok(self
+            .stash
+            .geneses()?
+            .filter(move |genesis| !archived.contains(&genesis.contract_id()))
+            .map(ContractInfo::with))
+    }
+
+    /// Lists all known contracts which may have their state assigned to seals
+    /// on the provided layer 1, except archived ones.
+    pub fn contracts_on(
+        &self,
+        layer1: Layer1,
+    ) -> Result<impl Iterator<Item = ContractInfo> + '_, StockError<S, H, P>> {
+        Ok(self
+            .contracts()?
+            .filter(move |info| info.has_layer1(layer1)))
     }
 
+    /// Lists contracts implementing interface class `C`, except archived ones.
     #[allow(clippy::multiple_bound_locations)]
     pub fn contracts_by<'a, C: IfaceClass + 'a>(
         &'a self,
@@ -508,7 +766,11 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         > + 'a,
         StockError<S, H, P>,
     > {
-        Ok(self.stash.geneses_by::<C>()?.filter_map(|genesis| {
+        let archived = self.stash.archived_contracts()?.collect::<BTreeSet<_>>();
+        Ok(self.stash.geneses_by::<C>()?.filter_map(move |genesis| {
+            if archived.contains(&genesis.contract_id()) {
+                return None;
+            }
             self.contract_iface_class::<C>(genesis.contract_id())
                 .as_ref()
                 .map(<C::Wrapper<H::ContractRead<'_>> as IfaceWrapper<H::ContractRead<'_>>>::info)
@@ -517,7 +779,7 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
     }
 
     /// Iterates over ids of all contract assigning state to the provided set of
-    /// output seals.
+    /// output seals. Archived contracts are skipped.
     pub fn contracts_assigning(
         &self,
         outputs: impl IntoIterator<Item = impl Into<XOutputSeal>>,
@@ -526,7 +788,54 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
             .into_iter()
             .map(|o| o.into())
             .collect::<BTreeSet<_>>();
-        Ok(self.index.contracts_assigning(outputs)?)
+        let archived = self.stash.archived_contracts()?.collect::<BTreeSet<_>>();
+        Ok(self
+            .index
+            .contracts_assigning(outputs)?
+            .filter(move |id| !archived.contains(id)))
+    }
+
+    /// Iterates over ids of all contract assigning state to the provided set of
+    /// output seals, taking into account only seals on the provided layer 1.
+    /// Archived contracts are skipped.
+    pub fn contracts_assigning_on(
+        &self,
+        layer1: Layer1,
+        outputs: impl IntoIterator<Item = impl Into<XOutputSeal>>,
+    ) -> Result<impl Iterator<Item = ContractId> + '_, StockError<S, H, P>> {
+        let outputs = outputs
+            .into_iter()
+            .map(|o| o.into())
+            .filter(|output: &XOutputSeal| output.layer1() == layer1)
+            .collect::<BTreeSet<_>>();
+        self.contracts_assigning(outputs)
+    }
+
+    /// Returns the operation spending the provided output, if the output was
+    /// spent by a transition from one of the indexed bundles.
+    ///
+    /// If several transitions spend the same output (for instance, after a
+    /// witness transaction was replaced), the one which has the best-ranked
+    /// valid witness is returned. Spenders with all witnesses being invalid
+    /// (archived) are ignored.
+    pub fn spent_by(&self, opout: Opout) -> Result<Option<Spender>, StockError<S, H, P>> {
+        let mut best = None::<(WitnessOrd, Spender)>;
+        for spender in self.index.spenders(opout)? {
+            let ord = self.state.witness_ord(spender.witness_id)?;
+            if !ord.is_valid() {
+                continue;
+            }
+            if best.map_or(true, |(best_ord, _)| ord < best_ord) {
+                best = Some((ord, spender));
+            }
+        }
+        Ok(best.map(|(_, spender)| spender))
+    }
+
+    /// Checks whether the provided output was spent by a transition with a
+    /// valid witness. See [`Self::spent_by`] for the details.
+    pub fn is_spent(&self, opout: Opout) -> Result<bool, StockError<S, H, P>> {
+        self.spent_by(opout).map(|spender| spender.is_some())
     }
 
     #[allow(clippy::type_complexity)]
@@ -557,6 +866,7 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
     ) -> Result<C::Wrapper<H::ContractRead<'_>>, StockError<S, H, P, ContractIfaceError>> {
         let (schema_ifaces, state, info) = self.contract_raw(contract_id)?;
         let iimpl = self.stash.impl_for::<C>(schema_ifaces)?;
+        let iimpl = self.stash.bound_iimpl(contract_id, iimpl)?;
 
         let iface = self.stash.iface(iimpl.iface_id)?;
         let (types, _) = self.stash.extract(&schema_ifaces.schema, [iface])?;
@@ -583,6 +893,12 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         let iimpl = iface.find_abstractable_impl(schema_ifaces).ok_or_else(|| {
             ContractIfaceError::NoAbstractImpl(iface_id, schema_ifaces.schema.schema_id())
         })?;
+        let iimpl = self.stash.bound_iimpl(contract_id, iimpl)?;
+        let iface = if iimpl.iface_id == iface_id {
+            iface
+        } else {
+            self.stash.iface(iimpl.iface_id)?
+        };
 
         let (types, _) = self.stash.extract(&schema_ifaces.schema, [iface])?;
 
@@ -595,6 +911,61 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         })
     }
 
+    /// Lists known revisions of the interface implementation for the schema,
+    /// from the oldest to the newest one.
+    pub fn iimpl_versions(
+        &self,
+        schema_id: SchemaId,
+        iface: impl Into<IfaceRef>,
+    ) -> Result<Vec<&IfaceImpl>, StockError<S, H, P>> {
+        let name = &self.stash.iface(iface)?.name;
+        let mut iimpls = vec![];
+        for iimpl in self.stash.iimpls(schema_id)? {
+            if &self.stash.iface(iimpl.iface_id)?.name == name {
+                iimpls.push(iimpl);
+            }
+        }
+        iimpls.sort_by_key(|iimpl| (iimpl.version, iimpl.timestamp));
+        Ok(iimpls)
+    }
+
+    /// Lists implementations of the interface which the contract was bound
+    /// to, from the first one to the one currently used. An empty list means
+    /// that the contract uses the implementation provided by its schema.
+    pub fn contract_bindings(
+        &self,
+        contract_id: ContractId,
+        iface: impl Into<IfaceRef>,
+    ) -> Result<Vec<ImplId>, StockError<S, H, P>> {
+        let name = &self.stash.iface(iface)?.name;
+        Ok(self
+            .stash
+            .iimpl_bindings(contract_id)?
+            .filter(|(n, _)| *n == name)
+            .map(|(_, impl_id)| impl_id)
+            .collect())
+    }
+
+    /// Re-binds contract to a known revision of an interface implementation
+    /// for the contract schema, which will be used for the contract instead
+    /// of the current one. Returns `false` if the contract is already bound
+    /// to the implementation.
+    pub fn rebind_contract(
+        &mut self,
+        contract_id: ContractId,
+        impl_id: ImplId,
+    ) -> Result<bool, StockError<S, H, P>> {
+        let changed = self.stash.bind_iimpl(contract_id, impl_id)?;
+        self.publish()?;
+        if changed {
+            self.notify(StockEvent::ContractRebound {
+                contract_id,
+                impl_id,
+            });
+        }
+        Ok(changed)
+    }
+
     pub fn contract_assignments_for(
         &self,
         contract_id: ContractId,
@@ -653,6 +1024,26 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         Ok(res)
     }
 
+    /// Returns contract assignments like [`Self::contract_assignments_for`],
+    /// taking into account only outpoints on the provided layer 1. If the
+    /// contract doesn't support the layer 1, its state is not accessed.
+    pub fn contract_assignments_on(
+        &self,
+        layer1: Layer1,
+        contract_id: ContractId,
+        outpoints: impl IntoIterator<Item = impl Into<XOutpoint>>,
+    ) -> Result<ContractAssignments, StockError<S, H, P>> {
+        if !ContractInfo::with(self.stash.genesis(contract_id)?).has_layer1(layer1) {
+            return Ok(empty!());
+        }
+        let outpoints = outpoints
+            .into_iter()
+            .map(|o| o.into())
+            .filter(|outpoint: &XOutpoint| outpoint.layer1() == layer1)
+            .collect::<Vec<_>>();
+        self.contract_assignments_for(contract_id, outpoints)
+    }
+
     pub fn contract_builder(
         &self,
         issuer: impl Into<Identity>,
@@ -763,7 +1154,7 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         let mut bundled_witnesses = BTreeMap::<BundleId, BundledWitness>::new();
         let mut transitions = BTreeMap::<OpId, Transition>::new();
         let mut terminals = BTreeMap::<BundleId, XChain<SecretSeal>>::new();
-        for opout in opouts {
+        for &opout in &opouts {
             if opout.op == contract_id {
                 continue; // we skip genesis since it will be present anywhere
             }
@@ -810,7 +1201,21 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
                 .reveal_transition(transition.clone())?;
         }
 
-        let genesis = self.stash.genesis(contract_id)?.clone();
+        // 3. Conceal all transitions and seals which are not on the path from
+        //    genesis to the requested outputs and terminals. The state is kept
+        //    since it is required for the validation.
+        let opids = transitions.keys().copied().collect::<BTreeSet<_>>();
+        let mut revealed = opouts;
+        revealed.extend(
+            transitions
+                .values()
+                .flat_map(|transition| &transition.inputs)
+                .map(|input| input.prev_out),
+        );
+        conceal_bundles(bundled_witnesses.values_mut(), &opids, &revealed);
+
+        let mut genesis = self.stash.genesis(contract_id)?.clone();
+        conceal_seals(genesis.id(), &mut genesis.assignments, &revealed);
         // Get schema signature by schema id
         self.stash
             .sigs_for(&ContentId::Schema(genesis.schema_id))?
@@ -822,10 +1227,11 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
             .supplement(ContentRef::Schema(genesis.schema_id))?
             .map(|schema_suppl| supplements.insert(schema_suppl.clone()));
 
-        let schema_ifaces = self.stash.schema(genesis.schema_id)?.clone();
+        let schema_ifaces = self.stash.schema(genesis.schema_id)?;
         let mut ifaces = BTreeMap::new();
-        for (iface_id, iimpl) in schema_ifaces.iimpls {
-            let iface = self.stash.iface(iface_id)?;
+        for iimpl in schema_ifaces.iimpls.values() {
+            let iimpl = self.stash.bound_iimpl(contract_id, iimpl)?.clone();
+            let iface = self.stash.iface(iimpl.iface_id)?;
             // Get iface and iimpl signatures by iface id and iimpl id
             self.stash
                 .sigs_for(&ContentId::Iface(iface.iface_id()))?
@@ -874,14 +1280,13 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
             Confined::try_from(supplements).map_err(|_| ConsignError::TooManySupplements)?;
         let signatures =
             Confined::try_from(signatures).map_err(|_| ConsignError::TooManySignatures)?;
-        // TODO: Conceal everything we do not need
         // TODO: Add known sigs to the consignment
 
         Ok(Consignment {
             version: ContainerVer::V2,
             transfer: TRANSFER,
 
-            schema: schema_ifaces.schema,
+            schema: schema_ifaces.schema.clone(),
             ifaces,
             genesis,
             terminals,
@@ -1149,7 +1554,12 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         // Enumerate state
         let mut spent_state =
             HashMap::<ContractId, HashMap<XOutputSeal, HashMap<Opout, PersistedState>>>::new();
-        for id in self.contracts_assigning(prev_outputs.iter().copied())? {
+        // Archived contracts must be included as well, since otherwise their state
+        // would be burned
+        for id in self
+            .index
+            .contracts_assigning(prev_outputs.iter().copied().collect())?
+        {
             // Skip current contract
             if id == contract_id {
                 continue;
@@ -1250,12 +1660,16 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         self.state.begin_transaction()?;
         self.stash
             .begin_transaction()
-            .inspect_err(|_| self.stash.rollback_transaction())?;
+            .inspect_err(|_| self.state.rollback_transaction())?;
         self.index.begin_transaction().inspect_err(|_| {
             self.state.rollback_transaction();
             self.stash.rollback_transaction();
         })?;
-        f(&mut self.stash, &mut self.state, &mut self.index)?;
+        f(&mut self.stash, &mut self.state, &mut self.index).inspect_err(|_| {
+            self.state.rollback_transaction();
+            self.stash.rollback_transaction();
+            self.index.rollback_transaction();
+        })?;
         self.index
             .commit_transaction()
             .map_err(StockError::from)
@@ -1265,14 +1679,34 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
                 self.state.rollback_transaction();
                 self.stash.rollback_transaction();
                 self.index.rollback_transaction();
-            })
+            })?;
+        self.publish()
+    }
+
+    /// Publishes data written by the providers during their transaction
+    /// commits, if the stock is persisted in a directory.
+    fn publish<E: Error>(&mut self) -> Result<(), StockError<S, H, P, E>> {
+        #[cfg(feature = "fs")]
+        if let Some(dir) = &mut self.dir {
+            dir.publish().map_err(|err| StockError::Persistence(err.to_string()))?;
+        }
+        Ok(())
     }
 
     pub fn import_kit(&mut self, kit: ValidKit) -> Result<validation::Status, StockError<S, H, P>> {
         let (kit, status) = kit.split();
+        let event = StockEvent::KitImported {
+            ifaces: kit.ifaces.iter().map(|iface| iface.iface_id()).collect(),
+            schemata: kit.schemata.iter().map(|schema| schema.schema_id()).collect(),
+            iimpls: kit.iimpls.iter().map(|iimpl| iimpl.impl_id()).collect(),
+        };
         self.stash.begin_transaction()?;
-        self.stash.consume_kit(kit)?;
+        self.stash
+            .consume_kit(kit)
+            .inspect_err(|_| self.stash.rollback_transaction())?;
         self.stash.commit_transaction()?;
+        self.publish()?;
+        self.notify(event);
         Ok(status)
     }
 
@@ -1298,6 +1732,7 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         resolver: R,
     ) -> Result<validation::Status, StockError<S, H, P>> {
         let (mut consignment, status) = consignment.split();
+        let contract_id = consignment.contract_id();
 
         consignment = self.stash.resolve_secrets(consignment)?;
         self.store_transaction(move |stash, state, index| {
@@ -1307,9 +1742,104 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
             Ok(())
         })?;
 
+        self.notify(match TRANSFER {
+            true => StockEvent::TransferAccepted(contract_id),
+            false => StockEvent::ContractImported(contract_id),
+        });
         Ok(status)
     }
 
+    /// Consumes a contract consignment read from a stream into the stash,
+    /// index and state, keeping in memory only the bundle which is being
+    /// consumed.
+    ///
+    /// The consignment contract must be already known to the stock. Besides
+    /// the checks performed by the reader, witness transactions are resolved
+    /// and checked to commit to the bundles, and state transitions are checked
+    /// against the contract schema. Scripts and state rules are not checked,
+    /// thus only contract consignments coming from a trusted source, like own
+    /// backups, can be consumed; transfers must be validated and accepted with
+    /// [`Self::accept_transfer`]. All consignment data are consumed in a single
+    /// transaction, which is rolled back if any of the bundles is invalid.
+    ///
+    /// # Returns
+    ///
+    /// Consignment data preceding and following the bundles.
+    pub fn consume_stream<Rd: Read, R: ResolveWitness>(
+        &mut self,
+        mut reader: ContractReader<Rd>,
+        resolver: R,
+    ) -> Result<(ConsignmentHeader, ConsignmentTrailer), StockError<S, H, P, StreamError>> {
+        let contract_id = reader.contract_id();
+        let mut consignment = None;
+        self.store_transaction(|stash, state, index| {
+            let Ok(genesis) = stash.genesis(contract_id) else {
+                return Err(StreamError::UnknownContract(contract_id).into());
+            };
+            let schema = stash.schema(genesis.schema_id)?.schema.clone();
+            let terminals = reader.header().terminals.clone();
+            let mut extension_ids = BTreeSet::new();
+            for extension in &reader.header().extensions {
+                extension_ids.insert(extension.id());
+                index.index_extension(contract_id, extension)?;
+            }
+
+            let mut ordered_extensions = BTreeMap::new();
+            for bw in reader.by_ref() {
+                let mut bw = bw?;
+                let witness_id = bw.witness_id();
+                let witness = resolve_witness_tx(&resolver, witness_id)
+                    .map_err(|err| StockError::WitnessUnresolved(witness_id, err))?;
+                bw.anchored_bundles
+                    .verify(contract_id, &witness)
+                    .map_err(StreamError::InvalidAnchor)?;
+                if let Some(opid) = schema_mismatch(&schema, &bw) {
+                    return Err(StreamError::SchemaMismatch(opid).into());
+                }
+
+                stash.resolve_terminal_secrets(&mut bw, &terminals)?;
+                for bundle in bw.bundles() {
+                    index.index_bundle(contract_id, bundle, witness_id)?;
+                    state.update_from_bundle(contract_id, bundle, witness_id, &resolver)?;
+
+                    // Extensions are ordered by the latest witness spending them
+                    for input in bundle.known_transitions.values().flat_map(|t| &t.inputs) {
+                        let id = input.prev_out.op;
+                        if !extension_ids.contains(&id) {
+                            continue;
+                        }
+                        let witness_ord = resolver
+                            .resolve_pub_witness_ord(witness_id)
+                            .map_err(|e| StockError::WitnessUnresolved(witness_id, e))?;
+                        match ordered_extensions.entry(id) {
+                            Entry::Vacant(entry) => {
+                                entry.insert((witness_id, witness_ord));
+                            }
+                            Entry::Occupied(mut entry) if entry.get().1 < witness_ord => {
+                                entry.insert((witness_id, witness_ord));
+                            }
+                            Entry::Occupied(_) => {}
+                        }
+                    }
+                }
+                stash.consume_bundled_witness(contract_id, bw)?;
+            }
+
+            let (header, trailer) = reader.finish()?;
+            for extension in &header.extensions {
+                if let Some((witness_id, witness_ord)) = ordered_extensions.get(&extension.id()) {
+                    state.update_from_extension(extension, *witness_id, *witness_ord)?;
+                }
+            }
+            stash.consume_consignment_data(header.clone(), trailer.clone())?;
+            consignment = Some((header, trailer));
+            Ok(())
+        })?;
+
+        self.notify(StockEvent::ContractImported(contract_id));
+        Ok(consignment.expect("set by the committed transaction"))
+    }
+
     /// Imports fascia into the stash, index and inventory.
     ///
     /// Part of the transfer workflow. Called once PSBT is completed and an RGB
@@ -1323,8 +1853,9 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         fascia: Fascia,
         resolver: R,
     ) -> Result<(), StockError<S, H, P, FasciaError>> {
+        let witness_id = fascia.witness_id();
+        let contracts = fascia.bundles.keys().copied().collect();
         self.store_transaction(move |stash, state, index| {
-            let witness_id = fascia.witness_id();
             stash
                 .consume_witness(SealWitness::new(fascia.witness.clone(), fascia.anchor.clone()))?;
 
@@ -1344,9 +1875,136 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
                 stash.consume_bundle(bundle)?;
             }
             Ok(())
+        })?;
+        self.notify(StockEvent::FasciaConsumed {
+            witness_id,
+            contracts,
+        });
+        Ok(())
+    }
+
+    /// Composes a disclosure of the state assigned to the provided outputs
+    /// under all non-archived contracts known to the stock.
+    ///
+    /// The disclosure contains only the state transitions assigning the state
+    /// to the outputs; all other transitions in their bundles, as well as the
+    /// seals of the assignments to other outputs, are concealed. State
+    /// assigned by genesis and state extensions is not disclosed, since it is
+    /// known to anybody having the contract.
+    pub fn disclose(
+        &self,
+        outputs: impl AsRef<[XOutputSeal]>,
+    ) -> Result<Disclosure, StockError<S, H, P, ConsignError>> {
+        let outputs = outputs.as_ref();
+        let contract_ids = self
+            .contracts_assigning(outputs.iter().copied())?
+            .collect::<Vec<_>>();
+
+        let mut bundles = BTreeMap::new();
+        for contract_id in contract_ids {
+            let opouts = self
+                .index
+                .opouts_by_outputs(contract_id, outputs.iter().copied())?;
+            let mut opids = BTreeSet::new();
+            let mut bundled_witnesses = BTreeMap::<BundleId, BundledWitness>::new();
+            for opout in &opouts {
+                if opout.op == contract_id || self.stash.extension(opout.op).is_ok() {
+                    continue;
+                }
+                let transition = self.transition(opout.op)?;
+                let bundle_id = self.index.bundle_id_for_op(transition.id())?;
+                if let Entry::Vacant(entry) = bundled_witnesses.entry(bundle_id) {
+                    entry.insert(self.bundled_witness(bundle_id)?);
+                }
+                opids.insert(opout.op);
+            }
+            if opids.is_empty() {
+                continue;
+            }
+            conceal_bundles(bundled_witnesses.values_mut(), &opids, &opouts);
+
+            let mut witnesses = BTreeMap::<XWitnessId, BundledWitness>::new();
+            for bw in bundled_witnesses.into_values() {
+                let witness_id = bw.witness_id();
+                let bw = match witnesses.remove(&witness_id) {
+                    Some(prev) => prev.merge_reveal(bw)?,
+                    None => bw,
+                };
+                witnesses.insert(witness_id, bw);
+            }
+            let witnesses = Confined::try_from_iter(witnesses.into_values())
+                .map_err(|_| ConsignError::TooManyBundles)?;
+            bundles.insert(contract_id, witnesses);
+        }
+        let bundles = Confined::try_from(bundles).map_err(|_| ConsignError::TooManyBundles)?;
+
+        Ok(Disclosure {
+            version: ContainerVer::V2,
+            bundles,
         })
     }
 
+    /// Accepts a disclosure, revealing the state of the transitions under the
+    /// contracts known to the stock.
+    ///
+    /// Witness transactions of the disclosed bundles are resolved and checked
+    /// to commit to the bundles, and the transitions are checked against the
+    /// contract schema. Scripts are not run, thus disclosures must be accepted
+    /// only from trusted parties.
+    pub fn accept_disclosure<R: ResolveWitness>(
+        &mut self,
+        disclosure: Disclosure,
+        resolver: R,
+    ) -> Result<(), StockError<S, H, P, DisclosureError>> {
+        let disclosure_id = disclosure.disclosure_id();
+        let contracts = disclosure.contract_ids().collect();
+        self.store_transaction(move |stash, state, index| {
+            for (contract_id, bundled_witnesses) in disclosure.bundles {
+                let Ok(genesis) = stash.genesis(contract_id) else {
+                    return Err(DisclosureError::UnknownContract(contract_id).into());
+                };
+                let schema = stash.schema(genesis.schema_id)?.schema.clone();
+                for bw in bundled_witnesses {
+                    let witness_id = bw.witness_id();
+                    let witness = resolve_witness_tx(&resolver, witness_id)
+                        .map_err(|err| StockError::WitnessUnresolved(witness_id, err))?;
+                    for bundle in bw.bundles() {
+                        let opids = bundle.input_map.values().copied().collect::<BTreeSet<_>>();
+                        if bundle.known_transitions.iter().any(|(opid, transition)| {
+                            !opids.contains(opid) ||
+                                transition.id() != *opid ||
+                                transition.contract_id != contract_id
+                        }) {
+                            let bundle_id = bundle.bundle_id();
+                            return Err(
+                                DisclosureError::InvalidBundle(contract_id, bundle_id).into(),
+                            );
+                        }
+                    }
+                    if let Some(opid) = schema_mismatch(&schema, &bw) {
+                        return Err(DisclosureError::SchemaMismatch(contract_id, opid).into());
+                    }
+                    bw.anchored_bundles
+                        .verify(contract_id, &witness)
+                        .map_err(|bundle_id| {
+                            DisclosureError::InvalidAnchor(contract_id, bundle_id)
+                        })?;
+                    for bundle in bw.bundles() {
+                        index.index_bundle(contract_id, bundle, witness_id)?;
+                        state.update_from_bundle(contract_id, bundle, witness_id, &resolver)?;
+                    }
+                    stash.consume_bundled_witness(contract_id, bw)?;
+                }
+            }
+            Ok(())
+        })?;
+        self.notify(StockEvent::DisclosureAccepted {
+            disclosure_id,
+            contracts,
+        });
+        Ok(())
+    }
+
     fn transition(&self, opid: OpId) -> Result<&Transition, StockError<S, H, P, ConsignError>> {
         let bundle_id = self.index.bundle_id_for_op(opid)?;
         let bundle = self.stash.bundle(bundle_id)?;
@@ -1398,40 +2056,2535 @@ impl<S: StashProvider, H: StateProvider, P: IndexProvider> Stock<S, H, P> {
         &mut self,
         seal: XChain<GraphSeal>,
     ) -> Result<bool, StockError<S, H, P>> {
-        Ok(self.stash.store_secret_seal(seal)?)
+        let res = self.stash.store_secret_seal(seal)?;
+        self.publish()?;
+        if res {
+            self.notify(StockEvent::SecretSealStored(seal));
+        }
+        Ok(res)
     }
 
+    /// Updates mining status of the witness transactions, except the ones
+    /// mined before `after_height`.
+    ///
+    /// Allocations created by the witnesses which became archived (for
+    /// instance, due to a re-org or a double-spend) are excluded from the
+    /// contract state, and are returned back once the witness gets mined or
+    /// re-broadcasted. All allocations affected by the witness status
+    /// changes are reported in [`UpdateRes::changes`].
     pub fn update_witnesses(
         &mut self,
         resolver: impl ResolveWitness,
         after_height: u32,
     ) -> Result<UpdateRes, StockError<S, H, P>> {
-        Ok(self.state.update_witnesses(resolver, after_height)?)
+        self.update_witnesses_inner(resolver, after_height, None)
     }
-}
 
-#[derive(Clone, Eq, PartialEq, Debug)]
-pub struct UpdateRes {
-    pub succeeded: usize,
-    pub failed: HashMap<XWitnessId, String>,
-}
+    /// Updates witnesses like [`Self::update_witnesses`], but only for the
+    /// witnesses on the provided layer 1. Witnesses from other layers 1 are
+    /// not passed to the resolver and keep their current status.
+    pub fn update_witnesses_on(
+        &mut self,
+        layer1: Layer1,
+        resolver: impl ResolveWitness,
+        after_height: u32,
+    ) -> Result<UpdateRes, StockError<S, H, P>> {
+        self.update_witnesses_inner(resolver, after_height, Some(layer1))
+    }
 
-#[cfg(test)]
-mod test {
-    use std::str::FromStr;
+    fn update_witnesses_inner(
+        &mut self,
+        resolver: impl ResolveWitness,
+        after_height: u32,
+        layer1: Option<Layer1>,
+    ) -> Result<UpdateRes, StockError<S, H, P>> {
+        let mut res = None;
+        // Allocation changes are collected before the updated witnesses are
+        // committed, such that a failure to read the stash doesn't leave the
+        // changes unreported.
+        self.store_transaction(|stash, state, _| {
+            let mut update = state.update_witnesses(resolver, after_height, layer1)?;
+            update.changes = Self::allocation_changes(stash, &update.updated)?;
+            res = Some(update);
+            Ok(())
+        })?;
+        let res = res.expect("transaction always produces the update result");
+        self.notify(StockEvent::WitnessesUpdated(res.clone()));
+        Ok(res)
+    }
 
-    use baid64::FromBaid64Str;
-    use commit_verify::{Conceal, DigestExt, Sha256};
-    use strict_encoding::TypeName;
+    fn allocation_changes(
+        stash: &Stash<S>,
+        updated: &HashMap<XWitnessId, (WitnessOrd, WitnessOrd)>,
+    ) -> Result<Vec<AllocationChange>, StockError<S, H, P>> {
+        let bundle_ids = stash.bundle_ids()?.collect::<BTreeSet<_>>();
+        let mut changes = vec![];
+        for (witness_id, (old, new)) in updated {
+            let witness = stash.witness(*witness_id)?;
+            for (contract_id, bundle_id) in witness.anchors.known_bundles() {
+                // Witness may anchor bundles of other contracts which are not
+                // known to the stash
+                if !bundle_ids.contains(&bundle_id) {
+                    continue;
+                }
+                let bundle = stash.bundle(bundle_id)?;
+                for (opid, transition) in bundle.known_transitions.iter() {
+                    for (ty, assignments) in transition.assignments.iter() {
+                        for no in 0..assignments.len_u16() {
+                            // Allocations with concealed seals are not a part of the state
+                            if !matches!(assignments.revealed_seal_at(no), Ok(Some(_))) {
+                                continue;
+                            }
+                            changes.push(AllocationChange {
+                                contract_id,
+                                opout: Opout::new(*opid, *ty, no),
+                                witness_id: *witness_id,
+                                old: *old,
+                                new: *new,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        changes.sort_by_key(|change| (change.contract_id, change.opout));
+        Ok(changes)
+    }
 
-    use super::*;
-    use crate::containers::ConsignmentExt;
+    /// Prunes historic contract state, removing assignments which were spent
+    /// by witnesses mined at least `depth` blocks below the `tip_height`.
+    ///
+    /// The `depth` must be large enough to make re-orgs of the spending
+    /// witnesses impossible, since pruned state can be recovered only by
+    /// re-computing the whole state from the stash with
+    /// [`Self::rebuild_state`]. State which is not pruned is not affected.
+    ///
+    /// Returns the number of removed assignments.
+    pub fn prune_state(
+        &mut self,
+        tip_height: u32,
+        depth: u32,
+    ) -> Result<usize, StockError<S, H, P>> {
+        let max_height = tip_height.saturating_sub(depth);
+        let mut spends = BTreeMap::<ContractId, Vec<(Opout, XWitnessId)>>::new();
+        for bundle_id in self.stash.bundle_ids()? {
+            let bundle = self.stash.bundle(bundle_id)?;
+            let (witness_ids, contract_id) = self.index.bundle_info(bundle_id)?;
+            let witness_ids = witness_ids.collect::<Vec<_>>();
+            let contract_spends = spends.entry(contract_id).or_default();
+            for input in bundle.known_transitions.values().flat_map(|t| &t.inputs) {
+                contract_spends.extend(witness_ids.iter().map(|id| (input.prev_out, *id)));
+            }
+        }
 
-    #[test]
-    fn test_consign() {
-        let mut stock = Stock::in_memory();
-        let seal = XChain::with(
-            rgbcore::Layer1::Bitcoin,
+        let mut pruned = 0;
+        self.store_transaction(|_, state, _| {
+            for (contract_id, spends) in spends {
+                pruned += state.prune(contract_id, spends, max_height)?;
+            }
+            Ok(())
+        })?;
+        Ok(pruned)
+    }
+
+    /// Moves contract to the archive, hiding it from the contract listings
+    /// while keeping all of its data. Returns `false` if the contract was
+    /// already archived.
+    pub fn archive_contract(
+        &mut self,
+        contract_id: ContractId,
+    ) -> Result<bool, StockError<S, H, P>> {
+        self.set_archived(contract_id, true)
+    }
+
+    /// Restores previously archived contract. Returns `false` if the contract
+    /// was not archived.
+    pub fn unarchive_contract(
+        &mut self,
+        contract_id: ContractId,
+    ) -> Result<bool, StockError<S, H, P>> {
+        self.set_archived(contract_id, false)
+    }
+
+    fn set_archived(
+        &mut self,
+        contract_id: ContractId,
+        archived: bool,
+    ) -> Result<bool, StockError<S, H, P>> {
+        self.stash.genesis(contract_id)?;
+        let changed = self.stash.set_archived(contract_id, archived)?;
+        self.publish()?;
+        if changed {
+            self.notify(StockEvent::ContractArchived(contract_id, archived));
+        }
+        Ok(changed)
+    }
+
+    pub fn archived_contracts(
+        &self,
+    ) -> Result<impl Iterator<Item = ContractId> + '_, StockError<S, H, P>> {
+        Ok(self.stash.archived_contracts()?)
+    }
+
+    /// Removes contract together with all of its operations and the data which
+    /// are not used by any other contract: bundles, witnesses, secret seals,
+    /// supplements, state and index entries. Schemata, interfaces and their
+    /// implementations are kept.
+    ///
+    /// With `dry_run` set, nothing is removed, and the returned report lists
+    /// what would have been removed.
+    pub fn purge_contract(
+        &mut self,
+        contract_id: ContractId,
+        dry_run: bool,
+    ) -> Result<PurgeReport, StockError<S, H, P>> {
+        let (report, opids) = self.purge_report(contract_id)?;
+        if dry_run {
+            return Ok(report);
+        }
+
+        self.store_transaction(|stash, state, index| {
+            stash.purge(&report)?;
+            state.remove_contract(contract_id)?;
+            index.remove_contract(contract_id, &opids)?;
+            Ok(())
+        })?;
+        self.notify(StockEvent::ContractPurged(report.clone()));
+        Ok(report)
+    }
+
+    /// Collects data exclusively owned by the contract, together with the ids
+    /// of all the contract operations.
+    fn purge_report(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<(PurgeReport, BTreeSet<OpId>), StockError<S, H, P>> {
+        let genesis = self.stash.genesis(contract_id)?;
+        let mut report = PurgeReport {
+            contract_id,
+            bundles: empty!(),
+            extensions: empty!(),
+            witnesses: empty!(),
+            secret_seals: empty!(),
+            supplements: self
+                .stash
+                .supplements(ContentRef::Genesis(contract_id))?
+                .map(|suppl| suppl.suppl_id())
+                .collect(),
+        };
+
+        for opid in self.stash.extension_ids()? {
+            if self.stash.extension(opid)?.contract_id == contract_id {
+                report.extensions.insert(opid);
+            }
+        }
+        for witness_id in self.stash.witness_ids()? {
+            let witness = self.stash.witness(witness_id)?;
+            // A witness is owned by the contract only if it anchors some of
+            // the contract bundles and none of the bundles of other contracts.
+            let (mut own, mut foreign) = (false, false);
+            for (id, bundle_id) in witness.anchors.known_bundles() {
+                if id == contract_id {
+                    report.bundles.insert(bundle_id);
+                    own = true;
+                } else {
+                    foreign = true;
+                }
+            }
+            if own && !foreign {
+                report.witnesses.insert(witness_id);
+            }
+        }
+
+        let mut opids = bset![genesis.id()];
+        opids.extend(&report.extensions);
+        for bundle_id in self.stash.bundle_ids()? {
+            if report.bundles.contains(&bundle_id) {
+                opids.extend(self.stash.bundle(bundle_id)?.known_transitions.keys());
+            }
+        }
+        report.secret_seals = self
+            .seals_used_by(&opids, &report.bundles)?
+            .into_iter()
+            .filter(|(_, exclusive)| *exclusive)
+            .map(|(seal, _)| seal)
+            .collect();
+
+        Ok((report, opids))
+    }
+
+    /// Removes schemata, interfaces, their implementations, AluVM libraries and
+    /// types which are not reachable from any of the contracts known to the
+    /// stash, together with their supplements and signatures. This includes
+    /// the data imported from kits which are not used by any contract yet.
+    ///
+    /// With `dry_run` set, nothing is removed, and the returned report lists
+    /// what would have been removed.
+    pub fn collect_garbage(&mut self, dry_run: bool) -> Result<GcReport, StockError<S, H, P>> {
+        let (report, types) = self.stash.gc_report()?;
+        if dry_run || report.is_empty() {
+            return Ok(report);
+        }
+
+        self.stash.begin_transaction()?;
+        self.stash
+            .collect_garbage(&report, types)
+            .inspect_err(|_| self.stash.rollback_transaction())?;
+        self.stash.commit_transaction()?;
+        self.publish()?;
+        self.notify(StockEvent::GarbageCollected(report.clone()));
+        Ok(report)
+    }
+
+    /// Collects secret seals used by the provided operations from the provided
+    /// bundles, either as a revealed or as a concealed seal. Each seal is
+    /// mapped to a flag telling whether it is used by these operations
+    /// exclusively.
+    fn seals_used_by(
+        &self,
+        opids: &BTreeSet<OpId>,
+        bundles: &BTreeSet<BundleId>,
+    ) -> Result<BTreeMap<XChain<GraphSeal>, bool>, StockError<S, H, P>> {
+        let mut own_seals = BTreeSet::new();
+        let mut other_seals = BTreeSet::new();
+        for bundle_id in self.stash.bundle_ids()? {
+            let bundle = self.stash.bundle(bundle_id)?;
+            let seals = if bundles.contains(&bundle_id) {
+                &mut own_seals
+            } else {
+                &mut other_seals
+            };
+            for transition in bundle.known_transitions.values() {
+                for (_, assignments) in transition.assignments.iter() {
+                    for no in 0..assignments.len_u16() {
+                        if let Ok(Some(seal)) = assignments.revealed_seal_at(no) {
+                            seals.insert(seal);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut used = BTreeMap::new();
+        for seal in self.stash.secret_seals()? {
+            let opouts = self.index.opouts_by_terminals([seal.conceal()])?;
+            let own = own_seals.contains(&seal) ||
+                opouts.iter().any(|opout| opids.contains(&opout.op));
+            let other = other_seals.contains(&seal) ||
+                opouts.iter().any(|opout| !opids.contains(&opout.op));
+            if own {
+                used.insert(seal, !other);
+            }
+        }
+        Ok(used)
+    }
+
+    /// Exports stash data of all contracts, together with all schemata,
+    /// interfaces, their implementations and other stash data, into a backup.
+    ///
+    /// The backup doesn't depend on the persistence providers and can be
+    /// imported with [`Self::import_backup`] into a stock using any of them.
+    pub fn export_backup(&self) -> Result<Backup, StockError<S, H, P, BackupError>> {
+        let contracts = self
+            .stash
+            .geneses()?
+            .map(|genesis| genesis.contract_id())
+            .collect();
+        self.backup(contracts, true)
+    }
+
+    /// Exports stash data of the provided contracts into a backup.
+    ///
+    /// Unlike [`Self::export_backup`], the backup contains only schemata,
+    /// interfaces and implementations used by these contracts, and only secret
+    /// seals used by the contract operations. Data containers are not a part
+    /// of such backup.
+    pub fn export_contracts_backup(
+        &self,
+        contracts: impl IntoIterator<Item = ContractId>,
+    ) -> Result<Backup, StockError<S, H, P, BackupError>> {
+        self.backup(contracts.into_iter().collect(), false)
+    }
+
+    fn backup(
+        &self,
+        contracts: BTreeSet<ContractId>,
+        full: bool,
+    ) -> Result<Backup, StockError<S, H, P, BackupError>> {
+        let mut backup = Backup::default();
+        let mut content = Vec::<ContentRef>::new();
+
+        let mut schema_ids = BTreeSet::new();
+        let mut opids = BTreeSet::new();
+        for contract_id in &contracts {
+            let genesis = self.stash.genesis(*contract_id)?;
+            schema_ids.insert(genesis.schema_id);
+            opids.insert(genesis.id());
+            content.push(ContentRef::Genesis(*contract_id));
+            backup.geneses.insert(*contract_id, genesis.clone())?;
+        }
+
+        let schemata = if full {
+            self.stash.schemata()?.collect::<Vec<_>>()
+        } else {
+            schema_ids
+                .into_iter()
+                .map(|id| self.stash.schema(id))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let mut iface_ids = BTreeSet::new();
+        for schema_ifaces in schemata {
+            let schema = &schema_ifaces.schema;
+            let mut ifaces = Vec::with_capacity(schema_ifaces.iimpls.len());
+            for iimpl in self.stash.iimpls(schema.schema_id())? {
+                ifaces.push(self.stash.iface(iimpl.iface_id)?);
+                iface_ids.insert(iimpl.iface_id);
+                content.push(ContentRef::IfaceImpl(iimpl.impl_id()));
+                backup.iimpls.push(iimpl.clone())?;
+            }
+            let (types, scripts) = self.stash.extract(schema, ifaces)?;
+            if !full {
+                backup.types.extend(types)?;
+            }
+            backup.scripts.extend(scripts.into_values())?;
+            content.push(ContentRef::Schema(schema.schema_id()));
+            backup.schemata.push(schema.clone())?;
+        }
+        if full {
+            iface_ids.extend(self.stash.ifaces()?.map(Iface::iface_id));
+            backup.types = self.stash.type_system()?.clone();
+            backup.attachments = Confined::try_from_iter(
+                self.stash
+                    .attachments()?
+                    .map(|(id, attach)| (id, attach.clone())),
+            )?;
+        }
+        for iface_id in iface_ids {
+            content.push(ContentRef::Iface(iface_id));
+            backup.ifaces.push(self.stash.iface(iface_id)?.clone())?;
+        }
+
+        for opid in self.stash.extension_ids()? {
+            let extension = self.stash.extension(opid)?;
+            if contracts.contains(&extension.contract_id) {
+                opids.insert(opid);
+                backup.extensions.insert(opid, extension.clone())?;
+            }
+        }
+        let bundle_ids = self.stash.bundle_ids()?.collect::<BTreeSet<_>>();
+        for witness_id in self.stash.witness_ids()? {
+            let witness = self.stash.witness(witness_id)?;
+            let mut used = false;
+            for (contract_id, bundle_id) in witness.anchors.known_bundles() {
+                if !contracts.contains(&contract_id) || !bundle_ids.contains(&bundle_id) {
+                    continue;
+                }
+                let bundle = self.stash.bundle(bundle_id)?;
+                opids.extend(bundle.known_transitions.keys());
+                backup.bundles.insert(bundle_id, bundle.clone())?;
+                used = true;
+            }
+            if used {
+                backup.witnesses.insert(witness_id, witness.clone())?;
+            }
+        }
+
+        backup.secret_seals = if full {
+            Confined::try_from_iter(self.stash.secret_seals()?)?
+        } else {
+            let bundles = backup.bundles.keys().copied().collect();
+            Confined::try_from_iter(self.seals_used_by(&opids, &bundles)?.into_keys())?
+        };
+
+        for content_ref in content {
+            for suppl in self.stash.supplements(content_ref)? {
+                let content_id = ContentId::Suppl(suppl.suppl_id());
+                if let Some(sigs) = self.stash.sigs_for(&content_id)? {
+                    backup.signatures.insert(content_id, sigs.clone())?;
+                }
+                backup.supplements.push(suppl)?;
+            }
+            let content_id = ContentId::from(content_ref);
+            if let Some(sigs) = self.stash.sigs_for(&content_id)? {
+                backup.signatures.insert(content_id, sigs.clone())?;
+            }
+        }
+
+        backup.identities = Confined::try_from_iter(
+            self.stash
+                .identities()?
+                .map(|(identity, trust)| (identity.clone(), trust)),
+        )?;
+        backup.archived = Confined::try_from_iter(
+            self.stash
+                .archived_contracts()?
+                .filter(|id| contracts.contains(id)),
+        )?;
+        for contract_id in &contracts {
+            let mut bindings: TinyOrdMap<TypeName, TinyVec<ImplId>> = empty!();
+            for (name, impl_id) in self.stash.iimpl_bindings(*contract_id)? {
+                let mut impl_ids = bindings.remove(name)?.unwrap_or_default();
+                impl_ids.push(impl_id)?;
+                bindings.insert(name.clone(), impl_ids)?;
+            }
+            if !bindings.is_empty() {
+                backup.bindings.insert(*contract_id, bindings)?;
+            }
+        }
+
+        Ok(backup)
+    }
+
+    /// Imports backup produced by [`Self::export_backup`] or
+    /// [`Self::export_contracts_backup`], merging its data with the data
+    /// already present in the stash.
+    ///
+    /// Contract state and index are re-computed for all contracts from the
+    /// backup. Returns ids of these contracts.
+    pub fn import_backup(
+        &mut self,
+        backup: Backup,
+        resolver: impl ResolveWitness,
+    ) -> Result<BTreeSet<ContractId>, StockError<S, H, P>> {
+        let backup_id = backup.backup_id();
+        let contracts = backup.contract_ids().collect::<BTreeSet<_>>();
+
+        // Operations of the already known contracts, which must be removed
+        // from the index before the contract data are replayed
+        let mut known = BTreeMap::new();
+        for contract_id in &contracts {
+            if self.stash.genesis(*contract_id).is_ok() {
+                let (_, opids) = self.purge_report(*contract_id)?;
+                known.insert(*contract_id, opids);
+            }
+        }
+
+        self.store_transaction(|stash, state, index| {
+            for (contract_id, opids) in &known {
+                state.remove_contract(*contract_id)?;
+                index.remove_contract(*contract_id, opids)?;
+            }
+            stash.consume_backup(backup)?;
+            Self::replay_index(stash, index, |id| contracts.contains(&id))?;
+            Self::replay_state(stash, state, resolver, |id| contracts.contains(&id))
+        })?;
+
+        self.notify(StockEvent::BackupImported {
+            backup_id,
+            contracts: contracts.iter().copied().collect(),
+        });
+        Ok(contracts)
+    }
+
+    /// Lists operations of the contract, ordered by their witnesses (see
+    /// [`HistoryEntry::witness`]), starting with the genesis. Operations with
+    /// no witness are put at the end. Transitions anchored only to archived
+    /// witnesses were reverted and are not listed.
+    ///
+    /// Transitions which assign all their state to the outputs matching the
+    /// `filter` (normally, the wallet outputs) are tagged as own change.
+    ///
+    /// Returns at most `limit` operations, skipping the first `offset` of
+    /// them.
+    pub fn history(
+        &self,
+        contract_id: ContractId,
+        filter: impl OutpointFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<HistoryEntry>, StockError<S, H, P>> {
+        let genesis = self.stash.genesis(contract_id)?;
+        let genesis_id = genesis.id();
+
+        let mut extensions = BTreeMap::new();
+        for opid in self.stash.extension_ids()? {
+            let extension = self.stash.extension(opid)?;
+            if extension.contract_id == contract_id {
+                extensions.insert(opid, extension);
+            }
+        }
+
+        // Bundle witnesses are taken from the index, such that the witnesses
+        // of other contracts are not loaded
+        let mut transitions = BTreeMap::new();
+        for bundle_id in self.index.bundle_ids()? {
+            let (witness_ids, id) = self.index.bundle_info(bundle_id)?;
+            if id != contract_id {
+                continue;
+            }
+            let mut witness = None;
+            for witness_id in witness_ids {
+                let witness_ord = self.state.witness_ord(witness_id)?;
+                if witness_ord.is_valid() &&
+                    witness.map_or(true, |best| (witness_ord, witness_id) < best)
+                {
+                    witness = Some((witness_ord, witness_id));
+                }
+            }
+            let Some((witness_ord, witness_id)) = witness else {
+                continue;
+            };
+            let bundle = self.stash.bundle(bundle_id)?;
+            for (opid, transition) in bundle.known_transitions.iter() {
+                transitions.insert(*opid, (transition, witness_id, witness_ord));
+            }
+        }
+
+        // Extensions are ordered by the latest witness spending them
+        let mut extension_witnesses = BTreeMap::<_, (XWitnessId, WitnessOrd)>::new();
+        for &(transition, witness_id, witness_ord) in transitions.values() {
+            for input in &transition.inputs {
+                let prev_op = input.prev_out.op;
+                if !extensions.contains_key(&prev_op) {
+                    continue;
+                }
+                match extension_witnesses.entry(prev_op) {
+                    Entry::Vacant(entry) => {
+                        entry.insert((witness_id, witness_ord));
+                    }
+                    Entry::Occupied(mut entry) if entry.get().1 < witness_ord => {
+                        entry.insert((witness_id, witness_ord));
+                    }
+                    Entry::Occupied(_) => {}
+                }
+            }
+        }
+
+        let mut ops = vec![(genesis_id, None)];
+        ops.extend(transitions.iter().map(|(opid, &(_, id, ord))| (*opid, Some((id, ord)))));
+        ops.extend(extensions.keys().map(|opid| (*opid, extension_witnesses.get(opid).copied())));
+        ops.sort_by_key(|(opid, witness)| {
+            let ord = witness.map(|(id, ord)| (ord, id));
+            (*opid != genesis_id, ord.is_none(), ord, *opid)
+        });
+
+        // History entries are constructed only for the requested page, with
+        // the output seals used to detect the payers of the operations
+        let op_entry = |opid: OpId| {
+            if opid == genesis_id {
+                let (mut entry, seals) = HistoryEntry::with(
+                    opid,
+                    HistoryOpKind::Genesis,
+                    &genesis.assignments,
+                    |seal| seal.to_output_seal(),
+                );
+                entry.timestamp = Some(genesis.timestamp);
+                Some((entry, seals, vec![]))
+            } else if let Some(extension) = extensions.get(&opid) {
+                let (entry, seals) = HistoryEntry::with(
+                    opid,
+                    HistoryOpKind::Extension,
+                    &extension.assignments,
+                    |seal| seal.to_output_seal(),
+                );
+                Some((entry, seals, vec![]))
+            } else {
+                let &(transition, witness_id, _) = transitions.get(&opid)?;
+                let kind = if transition.transition_type == TransitionType::BLANK {
+                    HistoryOpKind::Blank
+                } else {
+                    HistoryOpKind::Transition
+                };
+                let (entry, seals) =
+                    HistoryEntry::with(opid, kind, &transition.assignments, |seal| {
+                        seal.try_to_output_seal(witness_id).ok()
+                    });
+                let inputs = transition
+                    .inputs
+                    .iter()
+                    .map(|input| input.prev_out)
+                    .collect::<Vec<_>>();
+                Some((entry, seals, inputs))
+            }
+        };
+
+        let mut history = vec![];
+        for (opid, witness) in ops.into_iter().skip(offset).take(limit) {
+            let Some((mut entry, _, inputs)) = op_entry(opid) else {
+                continue;
+            };
+            entry.witness = witness;
+            entry.payers = inputs
+                .into_iter()
+                .filter_map(|opout| op_entry(opout.op)?.1.get(&opout).copied())
+                .collect();
+            if let Some((_, WitnessOrd::Mined(pos))) = entry.witness {
+                entry.timestamp = Some(pos.timestamp());
+            }
+            entry.own_change = entry.is_transition() &&
+                entry.concealed == 0 &&
+                !entry.beneficiaries.is_empty() &&
+                entry
+                    .beneficiaries
+                    .iter()
+                    .all(|seal| filter.include_outpoint(*seal));
+            history.push(entry);
+        }
+        Ok(history)
+    }
+
+    /// Collects statistics on the data kept by the stock for each of the
+    /// contracts (including the archived ones), together with the size of
+    /// the data kept by each of the providers.
+    pub fn stats(&self) -> Result<StockStats, StockError<S, H, P>> {
+        let bundle_ids = self.stash.bundle_ids()?.collect::<BTreeSet<_>>();
+        let mut contract_opids = BTreeMap::<_, BTreeSet<_>>::new();
+        let mut contract_bundles = BTreeMap::<_, BTreeSet<_>>::new();
+        let mut contract_witnesses = BTreeMap::<_, BTreeSet<_>>::new();
+        for opid in self.stash.extension_ids()? {
+            let contract_id = self.stash.extension(opid)?.contract_id;
+            contract_opids.entry(contract_id).or_default().insert(opid);
+        }
+        for witness_id in self.stash.witness_ids()? {
+            let witness = self.stash.witness(witness_id)?;
+            for (contract_id, bundle_id) in witness.anchors.known_bundles() {
+                contract_witnesses
+                    .entry(contract_id)
+                    .or_default()
+                    .insert(witness_id);
+                // Anchors may commit to bundles which are not known to the stash
+                if !bundle_ids.contains(&bundle_id) {
+                    continue;
+                }
+                contract_bundles
+                    .entry(contract_id)
+                    .or_default()
+                    .insert(bundle_id);
+            }
+        }
+
+        let mut stats = StockStats {
+            stash_size: self.stash.serialized_size()?,
+            state_size: self.state.serialized_size()?,
+            index_size: self.index.serialized_size()?,
+            ..default!()
+        };
+        let mut op_contracts = BTreeMap::new();
+        let mut bundle_contracts = BTreeMap::new();
+        for genesis in self.stash.geneses()? {
+            let contract_id = genesis.contract_id();
+            let bundles = contract_bundles.remove(&contract_id).unwrap_or_default();
+            let mut opids = contract_opids.remove(&contract_id).unwrap_or_default();
+            let extensions = opids.len();
+            let mut transitions = 0;
+            for bundle_id in &bundles {
+                let known = &self.stash.bundle(*bundle_id)?.known_transitions;
+                transitions += known.len();
+                opids.extend(known.keys());
+                bundle_contracts.insert(*bundle_id, contract_id);
+            }
+            opids.insert(genesis.id());
+            op_contracts.extend(opids.into_iter().map(|opid| (opid, contract_id)));
+
+            let schema = &self.stash.schema(genesis.schema_id)?.schema;
+            let contract = self.state.contract_state(contract_id)?;
+            let global = schema
+                .global_types
+                .keys()
+                .filter_map(|ty| contract.global(*ty).ok())
+                .map(Iterator::count)
+                .sum();
+            let allocations = contract.rights_all().count() +
+                contract.fungible_all().count() +
+                contract.data_all().count() +
+                contract.attach_all().count();
+
+            stats.contracts.insert(contract_id, ContractStats {
+                bundles: bundles.len(),
+                transitions,
+                extensions,
+                witnesses: contract_witnesses
+                    .get(&contract_id)
+                    .map(BTreeSet::len)
+                    .unwrap_or_default(),
+                secret_seals: 0,
+                supplements: self
+                    .stash
+                    .supplements(ContentRef::Genesis(contract_id))?
+                    .count(),
+                global,
+                allocations,
+            });
+        }
+
+        // Secret seals are counted for each contract using them either as a
+        // revealed or as a concealed seal, in the same way as
+        // `Self::seals_used_by` does, but in a single pass over all contracts
+        let mut seal_contracts = BTreeMap::<_, BTreeSet<_>>::new();
+        for (bundle_id, contract_id) in &bundle_contracts {
+            let bundle = self.stash.bundle(*bundle_id)?;
+            for transition in bundle.known_transitions.values() {
+                for (_, assignments) in transition.assignments.iter() {
+                    for no in 0..assignments.len_u16() {
+                        if let Ok(Some(seal)) = assignments.revealed_seal_at(no) {
+                            seal_contracts.entry(seal).or_default().insert(*contract_id);
+                        }
+                    }
+                }
+            }
+        }
+        for seal in self.stash.secret_seals()? {
+            let mut contracts = seal_contracts.remove(&seal).unwrap_or_default();
+            for opout in self.index.opouts_by_terminals([seal.conceal()])? {
+                contracts.extend(op_contracts.get(&opout.op));
+            }
+            for contract_id in contracts {
+                if let Some(contract) = stats.contracts.get_mut(&contract_id) {
+                    contract.secret_seals += 1;
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Walks over all data of the stash, state and index and reports all
+    /// inconsistencies found between them.
+    ///
+    /// Unlike other methods, which fail on the first inconsistency they
+    /// encounter, this method fails only on provider connectivity errors.
+    pub fn check(&self) -> Result<CheckReport, StockError<S, H, P>> {
+        let mut report = CheckReport::default();
+        let mut absent_contracts = BTreeSet::new();
+        let mut absent_bundles = BTreeSet::new();
+        let mut absent_witnesses = BTreeSet::new();
+
+        let schema_ids = self
+            .stash
+            .schemata()?
+            .map(|schema_ifaces| schema_ifaces.schema.schema_id())
+            .collect::<BTreeSet<_>>();
+        let witness_ids = self.stash.witness_ids()?.collect::<BTreeSet<_>>();
+        let bundle_ids = self.stash.bundle_ids()?.collect::<BTreeSet<_>>();
+        let state_contracts = self.state.contract_ids()?.collect::<BTreeSet<_>>();
+        let index_contracts = self.index.contract_ids()?.collect::<BTreeSet<_>>();
+
+        let mut contract_ids = BTreeSet::new();
+        for genesis in self.stash.geneses()? {
+            let contract_id = genesis.contract_id();
+            contract_ids.insert(contract_id);
+            if !schema_ids.contains(&genesis.schema_id) {
+                report
+                    .stash
+                    .push(StashInconsistency::SchemaAbsent(genesis.schema_id));
+            }
+            if !state_contracts.contains(&contract_id) {
+                report
+                    .state
+                    .push(StateInconsistency::UnknownContract(contract_id));
+            }
+            if !index_contracts.contains(&contract_id) {
+                report
+                    .index
+                    .push(IndexInconsistency::ContractAbsent(contract_id));
+            }
+        }
+        absent_contracts.extend(state_contracts.difference(&contract_ids));
+        absent_contracts.extend(index_contracts.difference(&contract_ids));
+
+        for id in self.stash.extension_ids()? {
+            let contract_id = self.stash.extension(id)?.contract_id;
+            if !contract_ids.contains(&contract_id) {
+                absent_contracts.insert(contract_id);
+            }
+        }
+
+        let mut anchored = BTreeMap::new();
+        for witness_id in &witness_ids {
+            let witness = self.stash.witness(*witness_id)?;
+            anchored.extend(
+                witness
+                    .anchors
+                    .known_bundles()
+                    .map(|(contract_id, bundle_id)| (bundle_id, contract_id)),
+            );
+        }
+
+        for bundle_id in &bundle_ids {
+            let bundle = self.stash.bundle(*bundle_id)?;
+            let contract_id = anchored.get(bundle_id).copied().or_else(|| {
+                bundle
+                    .known_transitions
+                    .values()
+                    .next()
+                    .map(|transition| transition.contract_id)
+            });
+            if let Some(contract_id) = contract_id {
+                if !contract_ids.contains(&contract_id) {
+                    absent_contracts.insert(contract_id);
+                }
+                if !anchored.contains_key(bundle_id) {
+                    report
+                        .stash
+                        .push(StashInconsistency::BundleMissedInAnchors(*bundle_id, contract_id));
+                }
+            }
+
+            for opid in bundle.known_transitions.keys() {
+                match self.index.bundle_id_for_op(*opid) {
+                    Ok(id) if id == *bundle_id => {}
+                    Ok(id) => report.index.push(IndexInconsistency::DistinctBundleOp {
+                        opid: *opid,
+                        present: id,
+                        expected: *bundle_id,
+                    }),
+                    Err(IndexError::Inconsistency(err)) => report.index.push(err),
+                    Err(err) => return Err(err.into()),
+                }
+            }
+
+            match self.index.bundle_info(*bundle_id) {
+                Ok((_, present)) => match anchored.get(bundle_id) {
+                    Some(expected) if *expected != present => {
+                        report.index.push(IndexInconsistency::DistinctBundleContract {
+                            bundle_id: *bundle_id,
+                            present,
+                            expected: *expected,
+                        })
+                    }
+                    _ => {}
+                },
+                Err(IndexError::Inconsistency(err)) => report.index.push(err),
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        for bundle_id in self.index.bundle_ids()? {
+            if !bundle_ids.contains(&bundle_id) {
+                absent_bundles.insert(bundle_id);
+            }
+            match self.index.bundle_info(bundle_id) {
+                Ok((witnesses, _)) => absent_witnesses
+                    .extend(witnesses.filter(|witness_id| !witness_ids.contains(witness_id))),
+                Err(IndexError::Inconsistency(err)) if !bundle_ids.contains(&bundle_id) => {
+                    report.index.push(err)
+                }
+                Err(IndexError::Inconsistency(_)) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        for seal in self.stash.secret_seals()? {
+            if self.index.opouts_by_terminals([seal.conceal()])?.is_empty() {
+                report.orphan_seals.push(seal);
+            }
+        }
+
+        report.stash.extend(
+            absent_contracts
+                .into_iter()
+                .map(StashInconsistency::ContractAbsent),
+        );
+        report
+            .stash
+            .extend(absent_bundles.into_iter().map(StashInconsistency::BundleAbsent));
+        report.stash.extend(
+            absent_witnesses
+                .into_iter()
+                .map(StashInconsistency::WitnessAbsent),
+        );
+
+        Ok(report)
+    }
+
+    /// Re-computes contract state from the data kept in the stash, replacing
+    /// the current state provider with `provider`, which must be empty.
+    ///
+    /// Used to recover from a loss or corruption of the state data. If the
+    /// operation fails, the previous state provider is kept.
+    pub fn rebuild_state(
+        &mut self,
+        provider: H,
+        resolver: impl ResolveWitness,
+    ) -> Result<(), StockError<S, H, P>> {
+        let prev = mem::replace(&mut self.state, State::new(provider));
+        self.store_transaction(|stash, state, _| {
+            Self::replay_state(stash, state, resolver, |_| true)
+        })
+        .inspect_err(|_| self.state = prev)
+    }
+
+    /// Re-computes index from the data kept in the stash, replacing the
+    /// current index provider with `provider`, which must be empty.
+    ///
+    /// Used to recover from a loss or corruption of the index data. If the
+    /// operation fails, the previous index provider is kept.
+    pub fn rebuild_index(&mut self, provider: P) -> Result<(), StockError<S, H, P>> {
+        let prev = mem::replace(&mut self.index, Index::new(provider));
+        self.store_transaction(|stash, _, index| Self::replay_index(stash, index, |_| true))
+            .inspect_err(|_| self.index = prev)
+    }
+
+    /// Re-computes both contract state and index from the data kept in the
+    /// stash. See [`Self::rebuild_state`] and [`Self::rebuild_index`] for the
+    /// details.
+    pub fn reindex(
+        &mut self,
+        state: H,
+        index: P,
+        resolver: impl ResolveWitness,
+    ) -> Result<(), StockError<S, H, P>> {
+        let prev_state = mem::replace(&mut self.state, State::new(state));
+        let prev_index = mem::replace(&mut self.index, Index::new(index));
+        self.store_transaction(|stash, state, index| {
+            Self::replay_index(stash, index, |_| true)?;
+            Self::replay_state(stash, state, resolver, |_| true)
+        })
+        .inspect_err(|_| {
+            self.state = prev_state;
+            self.index = prev_index;
+        })
+    }
+
+    /// Replays all geneses, extensions and bundles known to the stash, anchored
+    /// to the witnesses known to the stash, into the contract state.
+    ///
+    /// Only contracts matching the `filter` are replayed.
+    fn replay_state(
+        stash: &Stash<S>,
+        state: &mut State<H>,
+        resolver: impl ResolveWitness,
+        filter: impl Fn(ContractId) -> bool,
+    ) -> Result<(), StockError<S, H, P>> {
+        for genesis in stash.geneses()? {
+            if !filter(genesis.contract_id()) {
+                continue;
+            }
+            let schema = &stash.schema(genesis.schema_id)?.schema;
+            state.register_contract(schema, genesis)?;
+        }
+
+        let bundle_ids = stash.bundle_ids()?.collect::<BTreeSet<_>>();
+        let extension_ids = stash.extension_ids()?.collect::<BTreeSet<_>>();
+        let mut ordered_extensions = BTreeMap::new();
+        for witness_id in stash.witness_ids()? {
+            let witness = stash.witness(witness_id)?;
+            for (contract_id, bundle_id) in witness.anchors.known_bundles() {
+                if !filter(contract_id) || !bundle_ids.contains(&bundle_id) {
+                    continue;
+                }
+                let bundle = stash.bundle(bundle_id)?;
+                let witness_ord = resolver
+                    .resolve_pub_witness_ord(witness_id)
+                    .map_err(|e| StockError::WitnessUnresolved(witness_id, e))?;
+                state.update_from_bundle(contract_id, bundle, witness_id, &resolver)?;
+
+                // Extensions are ordered by the latest witness spending them
+                for input in bundle.known_transitions.values().flat_map(|t| &t.inputs) {
+                    let id = input.prev_out.op;
+                    if !extension_ids.contains(&id) {
+                        continue;
+                    }
+                    match ordered_extensions.entry(id) {
+                        Entry::Vacant(entry) => {
+                            entry.insert((witness_id, witness_ord));
+                        }
+                        Entry::Occupied(mut entry) if entry.get().1 < witness_ord => {
+                            entry.insert((witness_id, witness_ord));
+                        }
+                        Entry::Occupied(_) => {}
+                    }
+                }
+            }
+        }
+
+        for (id, (witness_id, witness_ord)) in ordered_extensions {
+            let extension = stash.extension(id)?;
+            state.update_from_extension(extension, witness_id, witness_ord)?;
+        }
+        // Extensions which are not spent by any known transition don't have a
+        // witness and are not a part of the contract state.
+
+        Ok(())
+    }
+
+    /// Replays all geneses, extensions and bundles known to the stash, anchored
+    /// to the witnesses known to the stash, into the index.
+    ///
+    /// Only contracts matching the `filter` are replayed.
+    fn replay_index(
+        stash: &Stash<S>,
+        index: &mut Index<P>,
+        filter: impl Fn(ContractId) -> bool,
+    ) -> Result<(), StockError<S, H, P>> {
+        for genesis in stash.geneses()? {
+            if filter(genesis.contract_id()) {
+                index.index_contract(genesis)?;
+            }
+        }
+        for id in stash.extension_ids()? {
+            let extension = stash.extension(id)?;
+            if filter(extension.contract_id) {
+                index.index_extension(extension.contract_id, extension)?;
+            }
+        }
+        let bundle_ids = stash.bundle_ids()?.collect::<BTreeSet<_>>();
+        for witness_id in stash.witness_ids()? {
+            let witness = stash.witness(witness_id)?;
+            for (contract_id, bundle_id) in witness.anchors.known_bundles() {
+                if filter(contract_id) && bundle_ids.contains(&bundle_id) {
+                    index.index_bundle(contract_id, stash.bundle(bundle_id)?, witness_id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct UpdateRes {
+    pub succeeded: usize,
+    pub failed: HashMap<XWitnessId, String>,
+    /// Witnesses which status was changed, with their old and new status.
+    pub updated: HashMap<XWitnessId, (WitnessOrd, WitnessOrd)>,
+    /// Allocations affected by the witness status changes, ordered by the
+    /// contract and opout. Filled in by [`Stock::update_witnesses`]; state
+    /// providers leave it empty.
+    pub changes: Vec<AllocationChange>,
+}
+
+/// Change in the status of a contract allocation caused by an update of its
+/// witness transaction mining status.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct AllocationChange {
+    pub contract_id: ContractId,
+    pub opout: Opout,
+    pub witness_id: XWitnessId,
+    pub old: WitnessOrd,
+    pub new: WitnessOrd,
+}
+
+impl AllocationChange {
+    /// Detects whether the allocation was removed from the contract state,
+    /// i.e. the payment was reverted.
+    pub fn is_reverted(&self) -> bool { self.old.is_valid() && !self.new.is_valid() }
+
+    /// Detects whether previously reverted allocation is back to the contract
+    /// state.
+    pub fn is_restored(&self) -> bool { !self.old.is_valid() && self.new.is_valid() }
+
+    /// Detects whether the witness transaction was mined and then got
+    /// un-mined by a re-org.
+    pub fn is_reorged(&self) -> bool {
+        matches!(self.old, WitnessOrd::Mined(_)) && !matches!(self.new, WitnessOrd::Mined(_))
+    }
+}
+
+/// Data exclusively owned by a contract, which are removed (or, in a dry run,
+/// would be removed) by [`Stock::purge_contract`].
+///
+/// Besides the listed data, the contract genesis, its state and index entries
+/// are removed.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PurgeReport {
+    pub contract_id: ContractId,
+    pub bundles: BTreeSet<BundleId>,
+    pub extensions: BTreeSet<OpId>,
+    pub witnesses: BTreeSet<XWitnessId>,
+    pub secret_seals: BTreeSet<XChain<GraphSeal>>,
+    pub supplements: BTreeSet<SupplId>,
+}
+
+/// Stash data which are not reachable from any of the contract geneses, and
+/// which are removed (or, in a dry run, would be removed) by
+/// [`Stock::collect_garbage`].
+///
+/// Each removed item is mapped to the size of its serialized data.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct GcReport {
+    /// Schemata, with the size including their interface implementations.
+    pub schemata: BTreeMap<SchemaId, usize>,
+    /// Interface implementations of the removed schemata.
+    pub iimpls: BTreeSet<ImplId>,
+    pub ifaces: BTreeMap<IfaceId, usize>,
+    pub libs: BTreeMap<LibId, usize>,
+    /// Supplements to the removed schemata, interfaces and implementations.
+    pub supplements: BTreeMap<SupplId, usize>,
+    /// Size of the removed type definitions.
+    pub types: usize,
+}
+
+impl GcReport {
+    /// Detects whether there is nothing to remove.
+    pub fn is_empty(&self) -> bool { self.freed() == 0 }
+
+    /// Total size of the removed data.
+    pub fn freed(&self) -> usize {
+        self.schemata.values().sum::<usize>() +
+            self.ifaces.values().sum::<usize>() +
+            self.libs.values().sum::<usize>() +
+            self.supplements.values().sum::<usize>() +
+            self.types
+    }
+
+    pub(super) fn content_refs(&self) -> Vec<ContentRef> {
+        self.schemata
+            .keys()
+            .copied()
+            .map(ContentRef::Schema)
+            .chain(self.iimpls.iter().copied().map(ContentRef::IfaceImpl))
+            .chain(self.ifaces.keys().copied().map(ContentRef::Iface))
+            .collect()
+    }
+}
+
+/// Report on the consistency of the stock data, produced by [`Stock::check`].
+///
+/// Each inconsistency is put into the list of the provider which misses the
+/// data.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct CheckReport {
+    pub stash: Vec<StashInconsistency>,
+    pub state: Vec<StateInconsistency>,
+    pub index: Vec<IndexInconsistency>,
+    /// Secret seals known to the stash which are not used by any of the
+    /// indexed operations.
+    ///
+    /// These are not errors, since they may come from invoices which were not
+    /// paid yet.
+    pub orphan_seals: Vec<XChain<GraphSeal>>,
+}
+
+impl CheckReport {
+    /// Detects whether no inconsistencies were found. Orphan seals are not
+    /// taken into account.
+    pub fn is_consistent(&self) -> bool {
+        self.stash.is_empty() && self.state.is_empty() && self.index.is_empty()
+    }
+}
+
+/// Kind of the operation in the contract history.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+pub enum HistoryOpKind {
+    Genesis,
+    Extension,
+    Transition,
+    /// Blank state transition, which moves the contract state to new seals
+    /// when the previous ones are spent by a transfer of some other contract.
+    Blank,
+}
+
+/// Operation from the contract history, produced by [`Stock::history`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct HistoryEntry {
+    pub opid: OpId,
+    pub kind: HistoryOpKind,
+    /// Witness of the operation. If a bundle is anchored to several witnesses,
+    /// the one with the highest priority ordering is used, ignoring the
+    /// archived ones.
+    ///
+    /// Extensions inherit the witness of the latest transition spending them,
+    /// and have no witness if they are not spent by any of the known
+    /// transitions. Genesis never has a witness.
+    pub witness: Option<(XWitnessId, WitnessOrd)>,
+    /// Genesis timestamp, or the timestamp of the block mining the witness.
+    pub timestamp: Option<i64>,
+    /// Outputs spent by the operation, for which the seals are known.
+    pub payers: BTreeSet<XOutputSeal>,
+    /// Outputs receiving the state assigned by the operation, for which the
+    /// seals are revealed.
+    pub beneficiaries: BTreeSet<XOutputSeal>,
+    /// Number of assignments to the concealed seals, i.e. to the outputs of
+    /// unknown counterparties.
+    pub concealed: usize,
+    /// Whether the transition assigns all its state only to the outputs
+    /// matching the filter provided to [`Stock::history`], i.e. it is a
+    /// transfer to self.
+    pub own_change: bool,
+}
+
+impl HistoryEntry {
+    fn with<Seal: ExposedSeal>(
+        opid: OpId,
+        kind: HistoryOpKind,
+        assignments: &Assignments<Seal>,
+        to_output: impl Fn(XChain<Seal>) -> Option<XOutputSeal>,
+    ) -> (Self, BTreeMap<Opout, XOutputSeal>) {
+        let mut seals = BTreeMap::new();
+        let mut concealed = 0;
+        for (ty, typed_assigns) in assignments.iter() {
+            for no in 0..typed_assigns.len_u16() {
+                let seal = typed_assigns.revealed_seal_at(no).ok().flatten();
+                match seal.and_then(&to_output) {
+                    Some(seal) => {
+                        seals.insert(Opout::new(opid, *ty, no), seal);
+                    }
+                    None => concealed += 1,
+                }
+            }
+        }
+        let entry = HistoryEntry {
+            opid,
+            kind,
+            witness: None,
+            timestamp: None,
+            payers: empty!(),
+            beneficiaries: seals.values().copied().collect(),
+            concealed,
+            own_change: false,
+        };
+        (entry, seals)
+    }
+
+    /// Detects whether the operation is a state transition, including a blank
+    /// one.
+    pub fn is_transition(&self) -> bool {
+        matches!(self.kind, HistoryOpKind::Transition | HistoryOpKind::Blank)
+    }
+}
+
+/// Per-contract statistics of the stock data, part of [`StockStats`].
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct ContractStats {
+    pub bundles: usize,
+    /// Transitions known from the contract bundles.
+    pub transitions: usize,
+    pub extensions: usize,
+    /// Witnesses anchoring the contract bundles, including the ones shared
+    /// with other contracts.
+    pub witnesses: usize,
+    /// Secret seals used by the contract operations.
+    pub secret_seals: usize,
+    /// Supplements to the contract genesis.
+    pub supplements: usize,
+    /// Global state items which are valid under the current witness
+    /// ordering.
+    pub global: usize,
+    /// Owned state allocations which are valid under the current witness
+    /// ordering.
+    pub allocations: usize,
+}
+
+/// Statistics of the stock data, produced by [`Stock::stats`].
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct StockStats {
+    pub contracts: BTreeMap<ContractId, ContractStats>,
+    /// Size of the stash data in their strict-serialized form.
+    pub stash_size: usize,
+    /// Size of the contract state data in their strict-serialized form.
+    pub state_size: usize,
+    /// Size of the index data in their strict-serialized form.
+    pub index_size: usize,
+}
+
+impl StockStats {
+    /// Total size of the stock data in their strict-serialized form.
+    pub fn total_size(&self) -> usize { self.stash_size + self.state_size + self.index_size }
+}
+
+/// Resolves the witness transaction, whichever layer 1 it belongs to.
+fn resolve_witness_tx(
+    resolver: &impl ResolveWitness,
+    witness_id: XWitnessId,
+) -> Result<Tx, WitnessResolverError> {
+    resolver
+        .resolve_pub_witness(witness_id)
+        .map(|witness| match witness {
+            XChain::Bitcoin(tx) | XChain::Liquid(tx) => tx,
+            _ => unreachable!(),
+        })
+}
+
+/// Returns the first state transition of the bundled witness whose type or
+/// types of assignments are not declared by the schema.
+fn schema_mismatch(schema: &Schema, bundled_witness: &BundledWitness) -> Option<OpId> {
+    bundled_witness
+        .bundles()
+        .flat_map(|bundle| &bundle.known_transitions)
+        .find(|(_, transition)| !matches_schema(schema, transition))
+        .map(|(opid, _)| *opid)
+}
+
+/// Checks that the transition type and the types of its assignments are
+/// declared by the schema.
+fn matches_schema(schema: &Schema, transition: &Transition) -> bool {
+    let declared = if transition.transition_type == TransitionType::BLANK {
+        None
+    } else {
+        match schema.transitions.get(&transition.transition_type) {
+            Some(transition_schema) => Some(&transition_schema.assignments),
+            None => return false,
+        }
+    };
+    transition.assignments.keys().all(|ty| {
+        schema.owned_types.contains_key(ty) &&
+            declared.map_or(true, |declared| declared.contains_key(ty))
+    })
+}
+
+/// Conceals all transitions inside the bundles except the ones from `opids`,
+/// and seals of their assignments which are not present in the `revealed` set
+/// of outputs.
+fn conceal_bundles<'a>(
+    bundled_witnesses: impl IntoIterator<Item = &'a mut BundledWitness>,
+    opids: &BTreeSet<OpId>,
+    revealed: &BTreeSet<Opout>,
+) {
+    for bw in bundled_witnesses {
+        for bundle in bw.anchored_bundles.bundles_mut() {
+            bundle.conceal_transitions(opids);
+            for (opid, transition) in bundle.known_transitions.keyed_values_mut() {
+                conceal_seals(*opid, &mut transition.assignments, revealed);
+            }
+        }
+    }
+}
+
+/// Conceals seals of all operation assignments which are not present in the
+/// `revealed` set of outputs.
+fn conceal_seals<Seal: ExposedSeal>(
+    opid: OpId,
+    assignments: &mut Assignments<Seal>,
+    revealed: &BTreeSet<Opout>,
+) {
+    for (ty, typed_assigns) in assignments.keyed_values_mut() {
+        for no in 0..typed_assigns.len_u16() {
+            if !revealed.contains(&Opout::new(opid, *ty, no)) {
+                typed_assigns.conceal_seal(no);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use amplify::confinement::U32;
+    use amplify::ByteArray;
+    use baid64::FromBaid64Str;
+    use commit_verify::{Conceal, DigestExt, Sha256};
+    use rgb::vm::{WitnessOrd, XWitnessTx};
+    use rgb::{
+        AltLayer1, Assign, Extension, Genesis, GenesisSeal, RevealedValue, Schema,
+        TransitionBundle, TypedAssigns,
+    };
+    use strict_encoding::{StrictDumb, StrictSerialize, TypeName};
+
+    use super::*;
+    use crate::containers::{ConsignmentExt, FileContent, Supplement};
+    use crate::interface::{FilterExclude, FilterIncludeAll};
+    #[cfg(feature = "sqlite")]
+    use crate::persistence::fixtures::Sql;
+    use crate::persistence::fixtures::{self, Mem, Providers};
+    use crate::persistence::serialized_size;
+
+    struct DumbResolver;
+
+    impl ResolveWitness for DumbResolver {
+        fn resolve_pub_witness(
+            &self,
+            witness_id: XWitnessId,
+        ) -> Result<XWitnessTx, WitnessResolverError> {
+            Err(WitnessResolverError::Unknown(witness_id))
+        }
+
+        fn resolve_pub_witness_ord(
+            &self,
+            witness_id: XWitnessId,
+        ) -> Result<WitnessOrd, WitnessResolverError> {
+            Err(WitnessResolverError::Unknown(witness_id))
+        }
+    }
+
+    fn snapshot(stock: &Stock) -> [Vec<u8>; 3] { Mem::dump(stock) }
+
+    fn genesis(no: u8) -> Genesis {
+        let mut genesis = Genesis::strict_dumb();
+        genesis.timestamp = no as i64;
+        genesis
+    }
+
+    /// Bundle of two transitions spending both outputs of the genesis, together
+    /// with the seals they assign the rights to.
+    fn spending_bundle(genesis: &Genesis) -> (TransitionBundle, [XChain<GraphSeal>; 2]) {
+        let contract_id = genesis.contract_id();
+        let seals = [fixtures::seal(0), fixtures::seal(1)];
+        let bundle = fixtures::bundle([
+            fixtures::transition(contract_id, 0, [fixtures::opout(genesis.id(), 0)], [seals[0]]),
+            fixtures::transition(contract_id, 1, [fixtures::opout(genesis.id(), 1)], [seals[1]]),
+        ]);
+        (bundle, seals)
+    }
+
+    /// Transfer of a contract with the spending bundle anchored to the witness
+    /// `txid(1)`.
+    fn transfer() -> (ValidTransfer, TransitionBundle) {
+        let genesis = fixtures::genesis(1, 2);
+        let contract_id = genesis.contract_id();
+        let (bundle, seals) = spending_bundle(&genesis);
+        let mut stock = fixtures::stock([genesis]);
+        stock
+            .consume_fascia(
+                fixtures::fascia(1, [(contract_id, bundle.clone())]),
+                fixtures::Resolver::default(),
+            )
+            .unwrap();
+        let outputs = seals.map(|seal| seal.try_to_output_seal(fixtures::witness_id(1)).unwrap());
+        let transfer = stock.transfer(contract_id, outputs, []).unwrap();
+        (ValidTransfer::assume_valid(transfer), bundle)
+    }
+
+    /// Runs the tests against each of the provider sets.
+    macro_rules! provider_tests {
+        ($($name:ident),+ $(,)?) => {
+            mod mem {
+                $(
+                    #[test]
+                    fn $name() { super::$name::<super::Mem>() }
+                )+
+            }
+
+            #[cfg(feature = "sqlite")]
+            mod sqlite {
+                $(
+                    #[test]
+                    fn $name() { super::$name::<super::Sql>() }
+                )+
+            }
+        };
+    }
+
+    provider_tests!(
+        consignment_rollback_state,
+        consignment_rollback_index,
+        consignment_rollback_stash,
+        fascia_rollback,
+        allocation_changes,
+        spent_by,
+        disclosure,
+        disclosure_accept,
+        disclosure_forged,
+        consume_stream,
+        rebuild,
+        check,
+        archive_purge,
+        purge_shared,
+        backup,
+        history,
+        history_bundles,
+        stats_bundles,
+        layer1_filter,
+        collect_garbage,
+        transfer_conceals_other_recipients,
+    );
+
+    fn consignment_rollback_state<P: Providers>() {
+        let (transfer, _) = transfer();
+        let mut stock = fixtures::empty_stock::<P>();
+        let before = P::dump(&stock);
+        // The contract and its first transition are already added to the state
+        // when the resolver fails
+        assert!(matches!(
+            stock.accept_transfer(transfer.clone(), fixtures::Resolver::failing_after(1)),
+            Err(StockError::WitnessUnresolved(id, _)) if id == fixtures::witness_id(1)
+        ));
+        assert_eq!(P::dump(&stock), before);
+
+        stock
+            .accept_transfer(transfer, fixtures::Resolver::default())
+            .unwrap();
+        assert_ne!(P::dump(&stock), before);
+    }
+
+    fn consignment_rollback_index<P: Providers>() {
+        let (transfer, bundle) = transfer();
+        let contract_id = transfer.contract_id();
+        // Another contract already claims the bundle of the transfer
+        let other = fixtures::genesis(2, 1);
+        let other_id = other.contract_id();
+        let mut stock = fixtures::stock_with::<P>([other]);
+        stock
+            .consume_fascia(
+                fixtures::fascia(1, [(other_id, bundle)]),
+                fixtures::Resolver::default(),
+            )
+            .unwrap();
+        let before = P::dump(&stock);
+        // The state is fully updated and the transfer contract is registered in
+        // the index when the bundle fails to be indexed
+        assert!(matches!(
+            stock.accept_transfer(transfer, fixtures::Resolver::default()),
+            Err(StockError::IndexInconsistency(IndexInconsistency::DistinctBundleContract {
+                present,
+                expected,
+                ..
+            })) if present == other_id && expected == contract_id
+        ));
+        assert_eq!(P::dump(&stock), before);
+    }
+
+    fn consignment_rollback_stash<P: Providers>() {
+        let (transfer, _) = transfer();
+        // Another contract has a bundle anchored to the same witness with a
+        // different MPC commitment
+        let other = fixtures::genesis(2, 1);
+        let other_id = other.contract_id();
+        let bundle = fixtures::bundle([fixtures::transition(
+            other_id,
+            0,
+            [fixtures::opout(other.id(), 0)],
+            [fixtures::seal(0)],
+        )]);
+        let mut stock = fixtures::stock_with::<P>([other]);
+        stock
+            .consume_fascia(
+                fixtures::fascia(1, [(other_id, bundle)]),
+                fixtures::Resolver::default(),
+            )
+            .unwrap();
+        let before = P::dump(&stock);
+        // The state and index are updated, and the transfer bundle is added to
+        // the stash when the witness anchors fail to merge
+        assert!(matches!(
+            stock.accept_transfer(transfer, fixtures::Resolver::default()),
+            Err(StockError::StashData(StashDataError::Merge(_)))
+        ));
+        assert_eq!(P::dump(&stock), before);
+    }
+
+    fn fascia_rollback<P: Providers>() {
+        let genesis = fixtures::genesis(1, 2);
+        let contract_id = genesis.contract_id();
+        let (bundle, _) = spending_bundle(&genesis);
+        let fascia = fixtures::fascia(1, [(contract_id, bundle)]);
+        let mut stock = fixtures::stock_with::<P>([genesis]);
+        let before = P::dump(&stock);
+        // The witness, the bundle index and the first transition are already
+        // written when the resolver fails on the second transition
+        assert!(matches!(
+            stock.consume_fascia(fascia.clone(), fixtures::Resolver::failing_after(1)),
+            Err(StockError::WitnessUnresolved(id, _)) if id == fixtures::witness_id(1)
+        ));
+        assert_eq!(P::dump(&stock), before);
+
+        stock
+            .consume_fascia(fascia, fixtures::Resolver::default())
+            .unwrap();
+        assert_ne!(P::dump(&stock), before);
+    }
+
+    fn allocation_changes<P: Providers>() {
+        let genesis = fixtures::genesis(1, 2);
+        let contract_id = genesis.contract_id();
+        let (bundle, _) = spending_bundle(&genesis);
+        let bundle_id = bundle.bundle_id();
+        let opids = bundle.known_transitions.keys().copied().collect::<Vec<_>>();
+        // The witness also anchors a bundle of a contract unknown to the stock
+        let mut fascia = fixtures::fascia(1, [(contract_id, bundle)]);
+        fascia.anchor = fixtures::anchor([
+            (contract_id, bundle_id),
+            (ContractId::from_byte_array([0xFF; 32]), BundleId::from_byte_array([0xFF; 32])),
+        ]);
+        let witness_id = fixtures::witness_id(1);
+        let mut stock = fixtures::stock_with::<P>([genesis]);
+        stock
+            .consume_fascia(fascia, fixtures::Resolver::with([(witness_id, fixtures::mined(100))]))
+            .unwrap();
+
+        let changes = |old, new| {
+            let mut changes = opids
+                .iter()
+                .map(|opid| AllocationChange {
+                    contract_id,
+                    opout: fixtures::opout(*opid, 0),
+                    witness_id,
+                    old,
+                    new,
+                })
+                .collect::<Vec<_>>();
+            changes.sort_by_key(|change| change.opout);
+            changes
+        };
+
+        let res = stock
+            .update_witnesses(fixtures::Resolver::with([(witness_id, WitnessOrd::Archived)]), 0)
+            .unwrap();
+        let archived = (fixtures::mined(100), WitnessOrd::Archived);
+        assert_eq!(res.updated, map! { witness_id => archived });
+        assert_eq!(res.changes, changes(fixtures::mined(100), WitnessOrd::Archived));
+        assert!(res.changes.iter().all(AllocationChange::is_reverted));
+        assert!(res.changes.iter().all(AllocationChange::is_reorged));
+
+        let res = stock
+            .update_witnesses(fixtures::Resolver::default(), 0)
+            .unwrap();
+        assert_eq!(res.changes, changes(WitnessOrd::Archived, WitnessOrd::Tentative));
+        assert!(res.changes.iter().all(AllocationChange::is_restored));
+
+        let res = stock
+            .update_witnesses(fixtures::Resolver::default(), 0)
+            .unwrap();
+        assert!(res.updated.is_empty());
+        assert!(res.changes.is_empty());
+    }
+
+    fn spent_by<P: Providers>() {
+        let genesis = fixtures::genesis(1, 1);
+        let contract_id = genesis.contract_id();
+        let opout = fixtures::opout(genesis.id(), 0);
+        // The witness transaction `txid(1)` is replaced with `txid(2)`, which
+        // spends the same output with a different transition
+        let replaced = fixtures::transition(contract_id, 0, [opout], [fixtures::seal(0)]);
+        let replacing = fixtures::transition(contract_id, 1, [opout], [fixtures::seal(0)]);
+        let (replaced_id, replacing_id) = (replaced.id(), replacing.id());
+        let mut stock = fixtures::stock_with::<P>([genesis]);
+        for (witness, transition) in [(1, replaced), (2, replacing)] {
+            stock
+                .consume_fascia(
+                    fixtures::fascia(witness, [(contract_id, fixtures::bundle([transition]))]),
+                    fixtures::Resolver::with([(fixtures::witness_id(1), fixtures::mined(100))]),
+                )
+                .unwrap();
+        }
+        let spender = |opid, witness| Spender {
+            opid,
+            witness_id: fixtures::witness_id(witness),
+        };
+
+        assert_eq!(stock.spent_by(opout).unwrap(), Some(spender(replaced_id, 1)));
+        assert!(!stock.is_spent(fixtures::opout(replaced_id, 0)).unwrap());
+
+        let resolver = |ords: &[(u8, WitnessOrd)]| {
+            fixtures::Resolver::with(
+                ords.iter()
+                    .map(|(witness, ord)| (fixtures::witness_id(*witness), *ord)),
+            )
+        };
+        stock
+            .update_witnesses(resolver(&[(1, WitnessOrd::Archived)]), 0)
+            .unwrap();
+        assert_eq!(stock.spent_by(opout).unwrap(), Some(spender(replacing_id, 2)));
+        assert!(stock.is_spent(opout).unwrap());
+
+        stock
+            .update_witnesses(
+                resolver(&[(1, WitnessOrd::Archived), (2, WitnessOrd::Archived)]),
+                0,
+            )
+            .unwrap();
+        assert_eq!(stock.spent_by(opout).unwrap(), None);
+        assert!(!stock.is_spent(opout).unwrap());
+    }
+
+    fn disclosure<P: Providers>() {
+        let mut stock = fixtures::empty_stock::<P>();
+        let output = XOutputSeal::strict_dumb();
+        let disclosure = stock.disclose([output]).unwrap();
+        assert!(disclosure.bundles.is_empty());
+
+        let contract_id = ContractId::strict_dumb();
+        let bundles = Confined::from_collection_unsafe(bset![BundledWitness::strict_dumb()]);
+        let disclosure = Disclosure {
+            version: ContainerVer::V2,
+            bundles: Confined::from_collection_unsafe(bmap! { contract_id => bundles }),
+        };
+        let before = P::dump(&stock);
+        assert!(matches!(
+            stock.accept_disclosure(disclosure, DumbResolver),
+            Err(StockError::InvalidInput(DisclosureError::UnknownContract(id))) if id == contract_id
+        ));
+        assert_eq!(P::dump(&stock), before);
+    }
+
+    fn disclosure_accept<P: Providers>() {
+        let genesis = fixtures::genesis(1, 2);
+        let contract_id = genesis.contract_id();
+        let (bundle, seals) = spending_bundle(&genesis);
+        let (fascia, tx) = fixtures::committed_fascia([(contract_id, bundle.clone())]);
+        let witness_id = fascia.witness_id();
+        let mut stock = fixtures::stock_with::<P>([genesis.clone()]);
+        stock
+            .consume_fascia(fascia, fixtures::Resolver::default())
+            .unwrap();
+        let output = seals[0].try_to_output_seal(witness_id).unwrap();
+        let disclosure = stock.disclose([output]).unwrap();
+
+        let mut other = fixtures::stock_with::<P>([genesis]);
+        other
+            .accept_disclosure(disclosure.clone(), fixtures::Resolver::with_txs([tx]))
+            .unwrap();
+        let mut opids = bundle.known_transitions.keys().copied();
+        let (disclosed, concealed) = (opids.next().unwrap(), opids.next().unwrap());
+        assert_eq!(
+            other.transition(disclosed).unwrap(),
+            bundle.known_transitions.get(&disclosed).unwrap()
+        );
+        assert!(other.transition(concealed).is_err());
+        assert_eq!(other.disclose([output]).unwrap(), disclosure);
+    }
+
+    fn disclosure_forged<P: Providers>() {
+        let genesis = fixtures::genesis(1, 2);
+        let contract_id = genesis.contract_id();
+        let (bundle, seals) = spending_bundle(&genesis);
+        let (fascia, tx) = fixtures::committed_fascia([(contract_id, bundle.clone())]);
+        let witness_id = fascia.witness_id();
+        let mut stock = fixtures::stock_with::<P>([genesis.clone()]);
+        stock
+            .consume_fascia(fascia, fixtures::Resolver::default())
+            .unwrap();
+        let output = seals[0].try_to_output_seal(witness_id).unwrap();
+        let disclosure = stock.disclose([output]).unwrap();
+        let bw = disclosure
+            .bundles
+            .get(&contract_id)
+            .and_then(|bundles| bundles.first())
+            .unwrap()
+            .clone();
+        let forge = |f: &dyn Fn(&mut TransitionBundle)| {
+            let mut bw = bw.clone();
+            f(bw.anchored_bundles.bundles_mut().next().unwrap());
+            Disclosure {
+                version: ContainerVer::V2,
+                bundles: Confined::from_collection_unsafe(
+                    bmap! { contract_id => Confined::from_collection_unsafe(bset![bw]) },
+                ),
+            }
+        };
+        let opid = *bw.bundles().next().unwrap().known_transitions.keys().next().unwrap();
+
+        let mut other = fixtures::stock_with::<P>([genesis.clone()]);
+        let before = P::dump(&other);
+
+        assert!(matches!(
+            other.accept_disclosure(disclosure.clone(), fixtures::Resolver::default()),
+            Err(StockError::WitnessUnresolved(id, _)) if id == witness_id
+        ));
+        assert_eq!(P::dump(&other), before);
+
+        // Transition which doesn't match its id
+        let disclosure = forge(&|bundle| {
+            for (id, transition) in bundle.known_transitions.keyed_values_mut() {
+                if *id == opid {
+                    transition.nonce += 10;
+                }
+            }
+        });
+        assert!(matches!(
+            other.accept_disclosure(disclosure, fixtures::Resolver::with_txs([tx.clone()])),
+            Err(StockError::InvalidInput(DisclosureError::InvalidBundle(id, _)))
+                if id == contract_id
+        ));
+        assert_eq!(P::dump(&other), before);
+
+        // Transition of a type unknown to the schema
+        let disclosure = forge(&|bundle| {
+            let mut transition = bundle.known_transitions.get(&opid).unwrap().clone();
+            transition.transition_type = TransitionType::with(0xFFFE);
+            *bundle = fixtures::bundle([transition]);
+        });
+        assert!(matches!(
+            other.accept_disclosure(disclosure, fixtures::Resolver::with_txs([tx.clone()])),
+            Err(StockError::InvalidInput(DisclosureError::SchemaMismatch(id, _)))
+                if id == contract_id
+        ));
+        assert_eq!(P::dump(&other), before);
+
+        // Bundle which is not committed by the witness transaction
+        let forged = fixtures::transition(
+            contract_id,
+            10,
+            [fixtures::opout(genesis.id(), 0)],
+            [fixtures::seal(0)],
+        );
+        let forged_id = fixtures::bundle([forged.clone()]).bundle_id();
+        let disclosure = forge(&|bundle| *bundle = fixtures::bundle([forged.clone()]));
+        assert!(matches!(
+            other.accept_disclosure(disclosure, fixtures::Resolver::with_txs([tx.clone()])),
+            Err(StockError::InvalidInput(DisclosureError::InvalidAnchor(id, bundle_id)))
+                if id == contract_id && bundle_id == forged_id
+        ));
+        assert_eq!(P::dump(&other), before);
+    }
+
+    fn consume_stream<P: Providers>() {
+        let (transfer, txs) = fixtures::committed_transfer();
+        let mut contract = transfer.into_contract();
+        let contract_id = contract.contract_id();
+        let mut extension = Extension::strict_dumb();
+        extension.contract_id = contract_id;
+        let ext_id = extension.id();
+        contract.extensions.push(extension).unwrap();
+        let suppl = Supplement::new(ContentRef::Genesis(contract_id), Identity::strict_dumb());
+        contract.supplements.push(suppl.clone()).unwrap();
+        let mut data = vec![];
+        contract.save(&mut data).unwrap();
+        let reader = || ContractReader::new(data.as_slice()).unwrap();
+
+        let mut stock = fixtures::empty_stock::<P>();
+        assert!(matches!(
+            stock.consume_stream(reader(), fixtures::Resolver::with_txs(txs.clone())),
+            Err(StockError::InvalidInput(StreamError::UnknownContract(id))) if id == contract_id
+        ));
+
+        // Bundles read before the failure are rolled back
+        let mut stock = fixtures::stock_with::<P>([contract.genesis.clone()]);
+        let before = P::dump(&stock);
+        let witness_id = XWitnessId::Bitcoin(txs[1].txid());
+        assert!(matches!(
+            stock.consume_stream(reader(), fixtures::Resolver::with_txs([txs[0].clone()])),
+            Err(StockError::WitnessUnresolved(id, _)) if id == witness_id
+        ));
+        assert_eq!(P::dump(&stock), before);
+
+        let (header, trailer) = stock
+            .consume_stream(reader(), fixtures::Resolver::with_txs(txs))
+            .unwrap();
+        assert_eq!(header.terminals, contract.terminals);
+        assert_eq!(trailer.schema, contract.schema);
+        for bundle in contract.bundles.iter().flat_map(BundledWitness::bundles) {
+            for (opid, transition) in bundle.known_transitions.iter() {
+                assert_eq!(stock.transition(*opid).unwrap(), transition);
+            }
+        }
+        // Data preceding and following the bundles are stored as well
+        let stash = stock.as_stash_provider();
+        assert!(stash.extension(ext_id).is_ok());
+        assert_eq!(stash.supplement(ContentRef::Genesis(contract_id)).unwrap(), Some(&suppl));
+        assert_ne!(P::dump(&stock), before);
+    }
+
+    fn rebuild<P: Providers>() {
+        let schema = Schema::strict_dumb();
+        let mut genesis = Genesis::strict_dumb();
+        genesis.schema_id = schema.schema_id();
+        let contract_id = genesis.contract_id();
+
+        let mut stash = P::stash();
+        stash.replace_schema(schema).unwrap();
+        stash.replace_genesis(genesis).unwrap();
+        let mut stock = Stock::with(stash, P::state(), P::index());
+
+        stock.reindex(P::state(), P::index(), DumbResolver).unwrap();
+        assert!(stock.as_state_provider().contract_state(contract_id).is_ok());
+        assert!(stock
+            .as_index_provider()
+            .contract_ids()
+            .unwrap()
+            .any(|id| id == contract_id));
+
+        let reindexed = P::dump(&stock);
+        stock.rebuild_state(P::state(), DumbResolver).unwrap();
+        stock.rebuild_index(P::index()).unwrap();
+        assert_eq!(P::dump(&stock), reindexed);
+    }
+
+    fn check<P: Providers>() {
+        let schema = Schema::strict_dumb();
+        let mut genesis = Genesis::strict_dumb();
+        genesis.schema_id = schema.schema_id();
+        let contract_id = genesis.contract_id();
+
+        let mut stash = P::stash();
+        stash.replace_genesis(genesis.clone()).unwrap();
+        let mut stock = Stock::with(stash, P::state(), P::index());
+        let report = stock.check().unwrap();
+        assert_eq!(report.stash, vec![StashInconsistency::SchemaAbsent(schema.schema_id())]);
+        assert_eq!(report.state, vec![StateInconsistency::UnknownContract(contract_id)]);
+        assert_eq!(report.index, vec![IndexInconsistency::ContractAbsent(contract_id)]);
+
+        let mut stash = P::stash();
+        stash.replace_schema(schema).unwrap();
+        stash.replace_genesis(genesis).unwrap();
+        stock = Stock::with(stash, P::state(), P::index());
+        stock.reindex(P::state(), P::index(), DumbResolver).unwrap();
+        let seal = XChain::with(
+            rgbcore::Layer1::Bitcoin,
+            GraphSeal::new_random_vout(bp::dbc::Method::OpretFirst, Vout::from_u32(0)),
+        );
+        stock.store_secret_seal(seal).unwrap();
+        let report = stock.check().unwrap();
+        assert!(report.is_consistent());
+        assert_eq!(report.orphan_seals, vec![seal]);
+    }
+
+    fn archive_purge<P: Providers>() {
+        let schema = Schema::strict_dumb();
+        let mut stash = P::stash();
+        stash.replace_schema(schema.clone()).unwrap();
+        let mut contract_ids = vec![];
+        for no in 1..=2 {
+            let mut genesis = genesis(no);
+            genesis.schema_id = schema.schema_id();
+            contract_ids.push(genesis.contract_id());
+            stash.replace_genesis(genesis).unwrap();
+        }
+        let (kept, purged) = (contract_ids[0], contract_ids[1]);
+        let suppl = Supplement::new(ContentRef::Genesis(purged), Identity::strict_dumb());
+        let suppl_id = suppl.suppl_id();
+        stash.add_supplement(suppl).unwrap();
+        let mut stock = Stock::with(stash, P::state(), P::index());
+        stock.reindex(P::state(), P::index(), DumbResolver).unwrap();
+        let seal = XChain::with(
+            rgbcore::Layer1::Bitcoin,
+            GraphSeal::new_random_vout(bp::dbc::Method::OpretFirst, Vout::from_u32(0)),
+        );
+        stock.store_secret_seal(seal).unwrap();
+
+        assert!(stock.archive_contract(kept).unwrap());
+        assert!(!stock.archive_contract(kept).unwrap());
+        let listed = stock.contracts().unwrap().map(|info| info.id).collect::<Vec<_>>();
+        assert_eq!(listed, vec![purged]);
+        assert_eq!(stock.archived_contracts().unwrap().collect::<Vec<_>>(), vec![kept]);
+        assert!(stock.contract_state(kept).is_ok());
+        assert!(stock.unarchive_contract(kept).unwrap());
+        assert_eq!(stock.contracts().unwrap().count(), 2);
+
+        let before = P::dump(&stock);
+        let report = stock.purge_contract(purged, true).unwrap();
+        assert_eq!(P::dump(&stock), before);
+        assert_eq!(report.supplements, bset![suppl_id]);
+        assert!(report.bundles.is_empty() && report.witnesses.is_empty());
+        assert!(report.secret_seals.is_empty());
+
+        assert_eq!(stock.purge_contract(purged, false).unwrap(), report);
+        let listed = stock.contracts().unwrap().map(|info| info.id).collect::<Vec<_>>();
+        assert_eq!(listed, vec![kept]);
+        assert!(stock.contract_state(purged).is_err());
+        assert!(!stock
+            .as_index_provider()
+            .contract_ids()
+            .unwrap()
+            .any(|id| id == purged));
+        assert!(stock
+            .as_stash_provider()
+            .supplement(ContentRef::Genesis(purged))
+            .unwrap()
+            .is_none());
+        let report = stock.check().unwrap();
+        assert!(report.is_consistent());
+        assert_eq!(report.orphan_seals, vec![seal]);
+        assert!(stock.purge_contract(purged, true).is_err());
+    }
+
+    fn purge_shared<P: Providers>() {
+        let genesis = fixtures::genesis(1, 2);
+        let other = fixtures::genesis(2, 1);
+        let (contract_id, other_id) = (genesis.contract_id(), other.contract_id());
+        let [own1, own2, foreign, shared] = [0, 1, 2, 3].map(fixtures::seal);
+        let spend = |contract_id, genesis: &Genesis, no, seals: Vec<_>| {
+            fixtures::bundle([fixtures::transition(
+                contract_id,
+                no as u8,
+                [fixtures::opout(genesis.id(), no)],
+                seals,
+            )])
+        };
+        // The witness `txid(1)` anchors bundles of both contracts, while the
+        // witness `txid(2)` anchors a bundle of the purged contract only
+        let shared_bundle = spend(contract_id, &genesis, 0, vec![own1, shared]);
+        let own_bundle = spend(contract_id, &genesis, 1, vec![own2]);
+        let other_bundle = spend(other_id, &other, 0, vec![foreign, shared]);
+        let own_ids = bset![shared_bundle.bundle_id(), own_bundle.bundle_id()];
+        let other_bundle_id = other_bundle.bundle_id();
+        let mut stock = fixtures::stock_with::<P>([genesis, other]);
+        stock
+            .consume_fascia(
+                fixtures::fascia(1, [(contract_id, shared_bundle), (other_id, other_bundle)]),
+                fixtures::Resolver::default(),
+            )
+            .unwrap();
+        stock
+            .consume_fascia(
+                fixtures::fascia(2, [(contract_id, own_bundle)]),
+                fixtures::Resolver::default(),
+            )
+            .unwrap();
+        for seal in [own1, own2, foreign, shared] {
+            stock.store_secret_seal(seal).unwrap();
+        }
+
+        let report = stock.purge_contract(contract_id, true).unwrap();
+        assert_eq!(report.witnesses, bset![fixtures::witness_id(2)]);
+        assert_eq!(report.bundles, own_ids);
+        assert_eq!(report.secret_seals, bset![own1, own2]);
+
+        // The report of the other contract has no witnesses since the only one
+        // it uses is shared
+        let other_report = stock.purge_contract(other_id, true).unwrap();
+        assert!(other_report.witnesses.is_empty());
+        assert_eq!(other_report.bundles, bset![other_bundle_id]);
+        assert_eq!(other_report.secret_seals, bset![foreign]);
+
+        assert_eq!(stock.purge_contract(contract_id, false).unwrap(), report);
+        let stash = stock.as_stash_provider();
+        let witnesses = stash.witness_ids().unwrap().collect::<Vec<_>>();
+        assert_eq!(witnesses, vec![fixtures::witness_id(1)]);
+        let bundles = stash.bundle_ids().unwrap().collect::<Vec<_>>();
+        assert_eq!(bundles, vec![other_bundle_id]);
+        let seals = stash.secret_seals().unwrap().collect::<BTreeSet<_>>();
+        assert_eq!(seals, bset![foreign, shared]);
+    }
+
+    fn backup<P: Providers>() {
+        let schema = Schema::strict_dumb();
+        let mut stash = P::stash();
+        stash.replace_schema(schema.clone()).unwrap();
+        let mut contract_ids = vec![];
+        for no in 1..=2 {
+            let mut genesis = genesis(no);
+            genesis.schema_id = schema.schema_id();
+            contract_ids.push(genesis.contract_id());
+            stash.replace_genesis(genesis).unwrap();
+        }
+        let (selected, archived) = (contract_ids[0], contract_ids[1]);
+        let suppl = Supplement::new(ContentRef::Genesis(archived), Identity::strict_dumb());
+        stash.add_supplement(suppl).unwrap();
+        let mut stock = Stock::with(stash, P::state(), P::index());
+        stock.reindex(P::state(), P::index(), DumbResolver).unwrap();
+        stock.archive_contract(archived).unwrap();
+
+        let backup = stock.export_backup().unwrap();
+        let mut data = vec![];
+        backup.save(&mut data).unwrap();
+        let backup = Backup::load(data.as_slice()).unwrap();
+
+        let mut restored = fixtures::empty_stock::<P>();
+        let contracts = restored.import_backup(backup.clone(), DumbResolver).unwrap();
+        assert_eq!(contracts, contract_ids.iter().copied().collect());
+        assert_eq!(P::dump(&restored), P::dump(&stock));
+        assert_eq!(restored.archived_contracts().unwrap().collect::<Vec<_>>(), vec![archived]);
+
+        // Importing already known contracts doesn't change anything
+        restored.import_backup(backup.clone(), DumbResolver).unwrap();
+        assert_eq!(P::dump(&restored), P::dump(&stock));
+        assert!(restored.check().unwrap().is_consistent());
+
+        // Bindings to the implementations unknown to the stash are rejected
+        let mut forged = backup;
+        let impl_id = IfaceImpl::strict_dumb().impl_id();
+        let bindings = TinyOrdMap::from_iter_unsafe([(
+            TypeName::from_str("RGB20").unwrap(),
+            TinyVec::from_iter_unsafe([impl_id]),
+        )]);
+        forged.bindings.insert(selected, bindings).unwrap();
+        let mut restored = fixtures::empty_stock::<P>();
+        assert!(matches!(
+            restored.import_backup(forged, DumbResolver),
+            Err(StockError::StashInconsistency(StashInconsistency::ImplAbsent(id))) if id == impl_id
+        ));
+        assert_eq!(P::dump(&restored), P::dump(&fixtures::empty_stock::<P>()));
+
+        let backup = stock.export_contracts_backup([selected]).unwrap();
+        assert_eq!(backup.contract_ids().collect::<Vec<_>>(), vec![selected]);
+        assert!(backup.supplements.is_empty() && backup.archived.is_empty());
+        let mut restored = fixtures::empty_stock::<P>();
+        restored.import_backup(backup, DumbResolver).unwrap();
+        let listed = restored.contracts().unwrap().map(|info| info.id).collect::<Vec<_>>();
+        assert_eq!(listed, vec![selected]);
+        assert!(restored.check().unwrap().is_consistent());
+    }
+
+    fn history<P: Providers>() {
+        let schema = Schema::strict_dumb();
+        let mut genesis = genesis(1);
+        genesis.schema_id = schema.schema_id();
+        let contract_id = genesis.contract_id();
+        let mut extension = Extension::strict_dumb();
+        extension.contract_id = contract_id;
+        let ext_id = extension.id();
+
+        let mut stash = P::stash();
+        stash.replace_schema(schema).unwrap();
+        stash.replace_genesis(genesis.clone()).unwrap();
+        stash.replace_extension(extension).unwrap();
+        let mut stock = Stock::with(stash, P::state(), P::index());
+        stock.reindex(P::state(), P::index(), DumbResolver).unwrap();
+
+        let history = stock
+            .history(contract_id, FilterIncludeAll, 0, usize::MAX)
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].opid, genesis.id());
+        assert_eq!(history[0].kind, HistoryOpKind::Genesis);
+        assert_eq!(history[0].timestamp, Some(genesis.timestamp));
+        assert_eq!(history[1].opid, ext_id);
+        assert_eq!(history[1].kind, HistoryOpKind::Extension);
+        assert_eq!(history[1].witness, None);
+        assert!(history.iter().all(|entry| !entry.own_change));
+
+        let page = stock.history(contract_id, FilterIncludeAll, 1, 1).unwrap();
+        assert_eq!(page, history[1..]);
+        assert!(stock
+            .history(contract_id, FilterIncludeAll, 2, 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    fn history_bundles<P: Providers>() {
+        let genesis = fixtures::genesis(1, 3);
+        let contract_id = genesis.contract_id();
+        let opouts = [0, 1, 2].map(|no| fixtures::opout(genesis.id(), no));
+        let seals = [0, 1, 2, 3].map(fixtures::seal);
+        let transfer = fixtures::transition(contract_id, 0, [opouts[0]], [seals[0]]);
+        let mut blank = fixtures::transition(contract_id, 1, [opouts[1]], [seals[1]]);
+        blank.transition_type = TransitionType::BLANK;
+        let spent = fixtures::opout(transfer.id(), 0);
+        let change = fixtures::transition(contract_id, 2, [spent], [seals[2]]);
+        let reverted = fixtures::transition(contract_id, 3, [opouts[2]], [seals[3]]);
+        let [transfer_id, blank_id, change_id, reverted_id] =
+            [&transfer, &blank, &change, &reverted].map(Transition::id);
+
+        let resolver = || {
+            fixtures::Resolver::with([
+                (fixtures::witness_id(1), fixtures::mined(100)),
+                (fixtures::witness_id(2), fixtures::mined(50)),
+                (fixtures::witness_id(4), WitnessOrd::Archived),
+                (fixtures::witness_id(5), WitnessOrd::Archived),
+            ])
+        };
+        let mut stock = fixtures::stock_with::<P>([genesis.clone()]);
+        // The bundle of the transfer is anchored both to a mined and to an
+        // archived witness, while the only witness of the last transition is
+        // archived
+        for (witness, transition) in [
+            (1, transfer.clone()),
+            (5, transfer),
+            (2, blank),
+            (3, change),
+            (4, reverted),
+        ] {
+            let fascia = fixtures::fascia(witness, [(contract_id, fixtures::bundle([transition]))]);
+            stock.consume_fascia(fascia, resolver()).unwrap();
+        }
+
+        let history = stock
+            .history(contract_id, FilterIncludeAll, 0, usize::MAX)
+            .unwrap();
+        let listed = history.iter().map(|entry| entry.opid).collect::<Vec<_>>();
+        assert_eq!(listed, vec![genesis.id(), blank_id, transfer_id, change_id]);
+        assert!(!listed.contains(&reverted_id));
+        let kinds = history.iter().map(|entry| entry.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![
+            HistoryOpKind::Genesis,
+            HistoryOpKind::Blank,
+            HistoryOpKind::Transition,
+            HistoryOpKind::Transition
+        ]);
+        let witnesses = history.iter().map(|entry| entry.witness).collect::<Vec<_>>();
+        assert_eq!(witnesses, vec![
+            None,
+            Some((fixtures::witness_id(2), fixtures::mined(50))),
+            Some((fixtures::witness_id(1), fixtures::mined(100))),
+            Some((fixtures::witness_id(3), WitnessOrd::Tentative)),
+        ]);
+        assert_eq!(history[2].timestamp, Some(1_700_000_100));
+        assert_eq!(history[3].timestamp, None);
+
+        let genesis_output = fixtures::genesis_seal(1, 0).to_output_seal().unwrap();
+        let transfer_output = seals[0]
+            .try_to_output_seal(fixtures::witness_id(1))
+            .unwrap();
+        assert_eq!(history[2].payers, bset![genesis_output]);
+        assert_eq!(history[2].beneficiaries, bset![transfer_output]);
+        assert_eq!(history[3].payers, bset![transfer_output]);
+        assert!(history[1..].iter().all(|entry| entry.own_change));
+        let foreign = stock
+            .history(contract_id, FilterExclude(FilterIncludeAll), 0, usize::MAX)
+            .unwrap();
+        assert!(foreign.iter().all(|entry| !entry.own_change));
+
+        let page = stock.history(contract_id, FilterIncludeAll, 2, 1).unwrap();
+        assert_eq!(page, history[2..3]);
+    }
+
+    #[test]
+    fn stats() {
+        let schema = Schema::strict_dumb();
+        let mut stash = MemStash::in_memory();
+        stash.replace_schema(schema.clone()).unwrap();
+        let mut contract_ids = vec![];
+        for no in 1..=2 {
+            let mut genesis = genesis(no);
+            genesis.schema_id = schema.schema_id();
+            contract_ids.push(genesis.contract_id());
+            stash.replace_genesis(genesis).unwrap();
+        }
+        let suppl = Supplement::new(ContentRef::Genesis(contract_ids[0]), Identity::strict_dumb());
+        stash.add_supplement(suppl).unwrap();
+        let mut stock = Stock::with(stash, MemState::in_memory(), MemIndex::in_memory());
+        stock
+            .reindex(MemState::in_memory(), MemIndex::in_memory(), DumbResolver)
+            .unwrap();
+        stock.archive_contract(contract_ids[1]).unwrap();
+
+        let stats = stock.stats().unwrap();
+        let listed = stats.contracts.keys().copied().collect::<BTreeSet<_>>();
+        assert_eq!(listed, contract_ids.iter().copied().collect());
+        assert_eq!(stats.contracts[&contract_ids[0]].supplements, 1);
+        assert_eq!(stats.contracts[&contract_ids[1]].supplements, 0);
+        for contract in stats.contracts.values() {
+            assert_eq!(contract.bundles, 0);
+            assert_eq!(contract.transitions, 0);
+            assert_eq!(contract.witnesses, 0);
+        }
+        let [stash, state, index] = snapshot(&stock);
+        // Archival status and other data not a part of the RGBStorage type
+        let provider = stock.as_stash_provider();
+        let stash_extra = serialized_size(provider.debug_archived()) +
+            serialized_size(provider.debug_superseded_iimpls()) +
+            serialized_size(provider.debug_bindings());
+        let index_extra = serialized_size(stock.as_index_provider().debug_spender_index());
+        assert!(!provider.debug_archived().is_empty());
+        assert_eq!(stats.stash_size, stash.len() + stash_extra);
+        assert_eq!(stats.state_size, state.len());
+        assert_eq!(stats.index_size, index.len() + index_extra);
+        assert_eq!(
+            stats.total_size(),
+            stash.len() + stash_extra + state.len() + index.len() + index_extra
+        );
+    }
+
+    fn stats_bundles<P: Providers>() {
+        let genesis = fixtures::genesis(1, 2);
+        let other = fixtures::genesis(2, 1);
+        let (contract_id, other_id) = (genesis.contract_id(), other.contract_id());
+        let (bundle, seals) = spending_bundle(&genesis);
+        let other_bundle = fixtures::bundle([fixtures::transition(
+            other_id,
+            0,
+            [fixtures::opout(other.id(), 0)],
+            [seals[0]],
+        )]);
+        // The witness also anchors a bundle of the contract, which is not known
+        // to the stash
+        let mut fascia = fixtures::fascia(1, [(contract_id, bundle.clone())]);
+        fascia.anchor = fixtures::anchor([
+            (contract_id, bundle.bundle_id()),
+            (other_id, BundleId::from_byte_array([0xFF; 32])),
+        ]);
+        let mut stock = fixtures::stock_with::<P>([genesis, other]);
+        stock
+            .consume_fascia(fascia, fixtures::Resolver::default())
+            .unwrap();
+        stock
+            .consume_fascia(
+                fixtures::fascia(2, [(other_id, other_bundle)]),
+                fixtures::Resolver::default(),
+            )
+            .unwrap();
+        for seal in seals {
+            stock.store_secret_seal(seal).unwrap();
+        }
+
+        let stats = stock.stats().unwrap();
+        let contract = &stats.contracts[&contract_id];
+        assert_eq!((contract.bundles, contract.transitions, contract.witnesses), (1, 2, 1));
+        assert_eq!(contract.secret_seals, 2);
+        // The seal shared by both contracts is counted for each of them
+        let other = &stats.contracts[&other_id];
+        assert_eq!((other.bundles, other.transitions, other.witnesses), (1, 1, 2));
+        assert_eq!(other.secret_seals, 1);
+    }
+
+    fn layer1_filter<P: Providers>() {
+        let schema = Schema::strict_dumb();
+        let mut stash = P::stash();
+        stash.replace_schema(schema.clone()).unwrap();
+        let mut contract_ids = vec![];
+        for no in 1..=2 {
+            let mut genesis = genesis(no);
+            genesis.schema_id = schema.schema_id();
+            if no == 2 {
+                genesis.alt_layers1.push(AltLayer1::Liquid).unwrap();
+            }
+            contract_ids.push(genesis.contract_id());
+            stash.replace_genesis(genesis).unwrap();
+        }
+        let stock = Stock::with(stash, P::state(), P::index());
+
+        let listed = |layer1| {
+            stock
+                .contracts_on(layer1)
+                .unwrap()
+                .map(|info| info.id)
+                .collect::<BTreeSet<_>>()
+        };
+        assert_eq!(listed(Layer1::Bitcoin), contract_ids.iter().copied().collect());
+        assert_eq!(listed(Layer1::Liquid), bset![contract_ids[1]]);
+
+        let outpoint = XOutpoint::Bitcoin(strict_dumb!());
+        assert!(stock
+            .contract_assignments_on(Layer1::Liquid, contract_ids[0], [outpoint])
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            stock
+                .contracts_assigning_on(Layer1::Liquid, [XOutputSeal::Bitcoin(strict_dumb!())])
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    fn collect_garbage<P: Providers>() {
+        let schema = Schema::strict_dumb();
+        let mut unused = Schema::strict_dumb();
+        unused.name = TypeName::from_str("Unused").unwrap();
+        let unused_id = unused.schema_id();
+        let mut genesis = genesis(1);
+        genesis.schema_id = schema.schema_id();
+
+        let mut stash = P::stash();
+        stash.replace_schema(schema.clone()).unwrap();
+        stash.replace_schema(unused).unwrap();
+        stash.replace_genesis(genesis).unwrap();
+        let suppl = Supplement::new(ContentRef::Schema(unused_id), Identity::strict_dumb());
+        let suppl_id = suppl.suppl_id();
+        stash.add_supplement(suppl).unwrap();
+        let mut stock = Stock::with(stash, P::state(), P::index());
+
+        let before = P::dump(&stock);
+        let report = stock.collect_garbage(true).unwrap();
+        assert_eq!(P::dump(&stock), before);
+        assert_eq!(report.schemata.keys().copied().collect::<Vec<_>>(), vec![unused_id]);
+        assert_eq!(report.supplements.keys().copied().collect::<Vec<_>>(), vec![suppl_id]);
+        assert!(report.freed() > 0);
+
+        assert_eq!(stock.collect_garbage(false).unwrap(), report);
+        assert!(stock.as_stash_provider().schema(unused_id).is_err());
+        assert!(stock.as_stash_provider().schema(schema.schema_id()).is_ok());
+        assert!(stock
+            .as_stash_provider()
+            .supplement(ContentRef::Schema(unused_id))
+            .unwrap()
+            .is_none());
+        assert!(stock.collect_garbage(false).unwrap().is_empty());
+    }
+
+    #[derive(Default)]
+    struct Recorder(std::sync::Mutex<Vec<StockEvent>>);
+
+    impl StockObserver for Recorder {
+        fn on_event(&self, event: &StockEvent) { self.0.lock().unwrap().push(event.clone()) }
+    }
+
+    #[test]
+    fn observer() {
+        let recorder = Arc::new(Recorder::default());
+        let observer: Arc<dyn StockObserver> = recorder.clone();
+        let mut stock = Stock::in_memory();
+        stock.add_observer(observer.clone());
+
+        let seal = XChain::with(
+            rgbcore::Layer1::Bitcoin,
+            GraphSeal::new_random_vout(bp::dbc::Method::OpretFirst, Vout::from_u32(0)),
+        );
+        assert!(stock.store_secret_seal(seal).unwrap());
+        assert!(!stock.store_secret_seal(seal).unwrap());
+        stock.update_witnesses(DumbResolver, 0).unwrap();
+
+        assert!(stock.remove_observer(&observer));
+        assert!(!stock.remove_observer(&observer));
+        let other = XChain::with(
+            rgbcore::Layer1::Bitcoin,
+            GraphSeal::new_random_vout(bp::dbc::Method::OpretFirst, Vout::from_u32(1)),
+        );
+        stock.store_secret_seal(other).unwrap();
+
+        let events = recorder.0.lock().unwrap().clone();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], StockEvent::SecretSealStored(seal));
+        assert!(matches!(&events[1], StockEvent::WitnessesUpdated(res) if res.changes.is_empty()));
+    }
+
+    #[test]
+    fn snapshot_isolated_from_updates() {
+        let mut stock = Stock::in_memory();
+        let snapshot = stock.snapshot();
+        let seal = XChain::with(
+            rgbcore::Layer1::Bitcoin,
+            GraphSeal::new_random_vout(bp::dbc::Method::OpretFirst, Vout::from_u32(0)),
+        );
+        stock.store_secret_seal(seal).unwrap();
+
+        let shared = snapshot.clone();
+        let report = std::thread::spawn(move || shared.check().unwrap())
+            .join()
+            .unwrap();
+        assert!(report.orphan_seals.is_empty());
+        assert_eq!(stock.check().unwrap().orphan_seals, vec![seal]);
+        assert_eq!(stock.snapshot().check().unwrap().orphan_seals, vec![seal]);
+    }
+
+    fn transfer_conceals_other_recipients<P: Providers>() {
+        let genesis = fixtures::genesis(1, 2);
+        let contract_id = genesis.contract_id();
+        let [own, other, sibling] = [0, 1, 2].map(fixtures::seal);
+        // Two recipients get state from the same transition, and the third one
+        // from another transition of the same bundle
+        let transition =
+            fixtures::transition(contract_id, 0, [fixtures::opout(genesis.id(), 0)], [own, other]);
+        let sibling_transition =
+            fixtures::transition(contract_id, 1, [fixtures::opout(genesis.id(), 1)], [sibling]);
+        let (opid, sibling_id) = (transition.id(), sibling_transition.id());
+        let bundle = fixtures::bundle([transition, sibling_transition]);
+        let bundle_id = bundle.bundle_id();
+        let mut stock = fixtures::stock_with::<P>([genesis]);
+        stock
+            .consume_fascia(
+                fixtures::fascia(1, [(contract_id, bundle)]),
+                fixtures::Resolver::default(),
+            )
+            .unwrap();
+
+        let output = own.try_to_output_seal(fixtures::witness_id(1)).unwrap();
+        let transfer = stock.transfer(contract_id, [output], []).unwrap();
+        let bundles = transfer
+            .bundles
+            .iter()
+            .flat_map(BundledWitness::bundles)
+            .collect::<Vec<_>>();
+        assert_eq!(bundles.len(), 1);
+        assert_eq!(bundles[0].bundle_id(), bundle_id);
+        let known = &bundles[0].known_transitions;
+        assert_eq!(known.keys().copied().collect::<Vec<_>>(), vec![opid]);
+        assert!(!known.contains_key(&sibling_id));
+        let assigns = known
+            .get(&opid)
+            .unwrap()
+            .assignments
+            .get(&AssignmentType::strict_dumb())
+            .unwrap();
+        assert_eq!(assigns.filter_revealed_seals(), vec![own]);
+        assert_eq!(assigns.to_confidential_seals(), vec![own.conceal(), other.conceal()]);
+    }
+
+    #[test]
+    fn consign_conceals_foreign_seals() {
+        let ty = AssignmentType::strict_dumb();
+        let own = XChain::with(Layer1::Bitcoin, GenesisSeal::strict_dumb());
+        let foreign = own.map(|mut seal| {
+            seal.vout = Vout::from_u32(1);
+            seal
+        });
+        let assign = |seal| Assign::Revealed {
+            seal,
+            state: RevealedValue::strict_dumb(),
+            lock: none!(),
+        };
+        let mut genesis = Genesis::strict_dumb();
+        genesis.assignments = Assignments::from_inner(Confined::from_collection_unsafe(bmap! {
+            ty => TypedAssigns::Fungible(Confined::from_collection_unsafe(vec![
+                assign(own),
+                assign(foreign),
+            ])),
+        }));
+        let opid = genesis.id();
+
+        conceal_seals(opid, &mut genesis.assignments, &bset![Opout::new(opid, ty, 0)]);
+        assert_eq!(genesis.id(), opid);
+        let typed_assigns = genesis.assignments.get(&ty).unwrap();
+        assert_eq!(typed_assigns.filter_revealed_seals(), vec![own]);
+        assert_eq!(typed_assigns.to_confidential_seals(), vec![own.conceal(), foreign.conceal()]);
+    }
+
+    #[test]
+    fn test_consign() {
+        let mut stock = Stock::in_memory();
+        let seal = XChain::with(
+            rgbcore::Layer1::Bitcoin,
             GraphSeal::new_random_vout(bp::dbc::Method::OpretFirst, Vout::from_u32(0)),
         );
         let secret_seal = seal.conceal();
@@ -1478,6 +4631,90 @@ mod test {
         }
     }
 
+    #[test]
+    #[cfg(feature = "fs")]
+    fn store_load() {
+        use rgb::Layer1;
+
+        let dir = std::env::temp_dir().join(format!("rgb-std-stock-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut stock: Stock = Stock::new(dir.clone());
+        stock.store().unwrap();
+        assert_eq!(stock.generation(), Some(1));
+        let seal = XChain::with(
+            Layer1::Bitcoin,
+            GraphSeal::new_random_vout(Method::OpretFirst, Vout::from_u32(0)),
+        );
+        assert!(stock.store_secret_seal(seal).unwrap());
+        assert_eq!(stock.generation(), Some(2));
+
+        let loaded: Stock = Stock::load(dir.clone()).unwrap();
+        assert_eq!(loaded.generation(), Some(2));
+        assert_eq!(snapshot(&loaded), snapshot(&stock));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn load_recovering() {
+        use crate::persistence::fs::{INDEX_FILENAME, STASH_FILENAME, STATE_FILENAME};
+
+        let dir = std::env::temp_dir().join(format!("rgb-std-recovering-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let genesis = fixtures::genesis(1, 2);
+        let contract_id = genesis.contract_id();
+        let (bundle, _) = spending_bundle(&genesis);
+        let mut stock = fixtures::stock([genesis]);
+        stock
+            .consume_fascia(
+                fixtures::fascia(1, [(contract_id, bundle)]),
+                fixtures::Resolver::default(),
+            )
+            .unwrap();
+        stock.set_path(dir.clone());
+        stock.store().unwrap();
+
+        // The state is corrupted and the index is lost
+        std::fs::write(dir.join(STATE_FILENAME), b"corrupted").unwrap();
+        std::fs::remove_file(dir.join(INDEX_FILENAME)).unwrap();
+        assert!(Stock::<MemStash, MemState, MemIndex>::load(dir.clone()).is_err());
+        let mut loaded: Stock =
+            Stock::load_recovering(dir.clone(), fixtures::Resolver::default()).unwrap();
+        assert_eq!(snapshot(&loaded), snapshot(&stock));
+        loaded.store().unwrap();
+        assert!(Stock::<MemStash, MemState, MemIndex>::load(dir.clone()).is_ok());
+
+        // The index was written before the layout versioning and has no spenders
+        let legacy = stock
+            .as_index_provider()
+            .to_strict_serialized::<U32>()
+            .unwrap()
+            .release();
+        std::fs::write(dir.join(INDEX_FILENAME), legacy).unwrap();
+        assert!(Stock::<MemStash, MemState, MemIndex>::load(dir.clone()).is_err());
+        let loaded: Stock =
+            Stock::load_recovering(dir.clone(), fixtures::Resolver::default()).unwrap();
+        assert_eq!(snapshot(&loaded), snapshot(&stock));
+        let spenders = loaded.as_index_provider().debug_spender_index();
+        assert!(!spenders.is_empty());
+        assert_eq!(spenders, stock.as_index_provider().debug_spender_index());
+
+        // The stash can't be re-computed
+        std::fs::write(dir.join(STASH_FILENAME), b"corrupted").unwrap();
+        assert!(matches!(
+            Stock::<MemStash, MemState, MemIndex>::load_recovering(
+                dir.clone(),
+                fixtures::Resolver::default()
+            ),
+            Err(StockError::Persistence(_))
+        ));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
     #[test]
     fn test_blank_builder_ifacename() {
         let stock = Stock::in_memory();